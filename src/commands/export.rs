@@ -1,33 +1,64 @@
 use std::path::PathBuf;
 
 use chrono::{DateTime, Utc};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use humantime;
 use indicatif::ProgressBar;
 
 use crate::commands::data_source::DataSourceCommand;
 use crate::commands::ApplicationArguments;
-use crate::definitions::{DataDestination, DataSource, DataSourceConnection};
+use crate::definitions::{
+    DataDestination, DataSource, DataSourceBatchIterator, DataSourceConnection,
+};
 use crate::destinations::Destination;
 use crate::sources::Source;
+use crate::transform::expression::{parse_expression, Expression};
+use crate::transform::mask::{load_masking, parse_mask_rule, MaskIterator, MaskRule};
+use crate::transform::pipeline::{
+    load_steps, parse_compute, parse_rename, Step, TransformIterator,
+};
+use crate::transform::schema::{
+    load_overrides, parse_column_override, ColumnOverride, SchemaOverrideIterator,
+};
+use crate::utils::formatter::{parse_bool_format, BinaryFormat, ValueFormatter};
 
+#[cfg(feature = "use_arrow")]
+use crate::destinations::arrow::ArrowDestination;
+#[cfg(feature = "use_avro")]
+use crate::destinations::avro::AvroDestination;
 #[cfg(feature = "use_csv")]
 use crate::destinations::csv::CSVDestination;
 use crate::destinations::debug::DebugDestination;
+#[cfg(feature = "use_text")]
+use crate::destinations::fixed_width::FixedWidthDestination;
 #[cfg(feature = "use_html")]
 use crate::destinations::html::HTMLDestination;
 #[cfg(feature = "use_json")]
 use crate::destinations::json::JSONDestination;
+#[cfg(feature = "use_text")]
+use crate::destinations::latex::LatexDestination;
 #[cfg(feature = "use_ods")]
 use crate::destinations::ods::SpreadSheetODSDestination;
 #[cfg(feature = "use_parquet")]
 use crate::destinations::parquet::ParquetDestination;
 #[cfg(feature = "use_text")]
+use crate::destinations::rst::RstDestination;
+#[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+use crate::destinations::spreadsheet::{parse_sheet_query, SheetQuery};
+#[cfg(feature = "use_template")]
+use crate::destinations::template::TemplateDestination;
+#[cfg(feature = "use_text")]
 use crate::destinations::text::TextDestination;
 #[cfg(feature = "use_text")]
 use crate::destinations::text_vertical::TextVerticalDestination;
+#[cfg(feature = "use_toml")]
+use crate::destinations::toml::TOMLDestination;
 #[cfg(feature = "use_xlsx")]
 use crate::destinations::xlsx::SpreadSheetXLSXDestination;
+#[cfg(feature = "use_xml")]
+use crate::destinations::xml::XMLDestination;
+#[cfg(feature = "use_yaml")]
+use crate::destinations::yaml::YAMLDestination;
 #[cfg(feature = "use_mysql")]
 use crate::{commands::common::MysqlConfigOptions, sources::mysql::MysqlSource};
 #[cfg(feature = "use_postgres")]
@@ -44,6 +75,31 @@ pub fn export(
     export_command: &ExportCommand,
 ) {
     let time_start: DateTime<Utc> = Utc::now();
+    let overrides = load_overrides(
+        &export_command.columns,
+        export_command.schema_file.as_deref(),
+    );
+    let masking = load_masking(
+        &export_command.masks,
+        export_command.mask_file.as_deref(),
+        export_command.mask_salt.as_deref(),
+    );
+    let steps = transform_steps(export_command);
+    //format options are checked even when no destination writes values as text
+    #[cfg_attr(
+        not(any(
+            feature = "use_csv",
+            feature = "use_html",
+            feature = "use_json",
+            feature = "use_template",
+            feature = "use_text",
+            feature = "use_toml",
+            feature = "use_xml",
+            feature = "use_yaml"
+        )),
+        allow(unused_variables)
+    )]
+    let formatter = value_formatter(export_command);
     let (source, mut destination) = match src {
         #[cfg(feature = "use_mysql")]
         DataSourceCommand::Mysql(ref mysql_options) => {
@@ -51,23 +107,59 @@ pub fn export(
             let destination: Destination = match &export_command.destination {
                 #[cfg(feature = "use_csv")]
                 DestinationCommand::CSV(csv_options) => {
-                    Destination::CSV(Box::new(CSVDestination::init(csv_options)))
+                    Destination::CSV(Box::new(CSVDestination::init(csv_options, &formatter)))
                 }
                 DestinationCommand::Debug(debug_options) => {
                     Destination::Debug(DebugDestination::init(args, debug_options))
                 }
                 #[cfg(feature = "use_html")]
                 DestinationCommand::HTML(html_options) => {
-                    Destination::HTML(HTMLDestination::init(html_options))
+                    Destination::HTML(HTMLDestination::init(html_options, &formatter))
+                }
+                #[cfg(feature = "use_xml")]
+                DestinationCommand::XML(xml_options) => {
+                    Destination::XML(XMLDestination::init(xml_options, &formatter))
+                }
+                #[cfg(feature = "use_yaml")]
+                DestinationCommand::YAML(yaml_options) => {
+                    Destination::YAML(YAMLDestination::init(yaml_options, &formatter))
+                }
+                #[cfg(feature = "use_toml")]
+                DestinationCommand::TOML(toml_options) => {
+                    Destination::TOML(TOMLDestination::init(toml_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::Latex(latex_options) => {
+                    Destination::Latex(LatexDestination::init(latex_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::Rst(rst_options) => {
+                    Destination::Rst(RstDestination::init(rst_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::FixedWidth(fixed_width_options) => Destination::FixedWidth(
+                    FixedWidthDestination::init(fixed_width_options, &formatter),
+                ),
+                #[cfg(feature = "use_template")]
+                DestinationCommand::Template(template_options) => {
+                    Destination::Template(TemplateDestination::init(template_options, &formatter))
                 }
                 #[cfg(feature = "use_json")]
                 DestinationCommand::JSON(json_options) => {
-                    Destination::JSON(JSONDestination::init(args, json_options))
+                    Destination::JSON(JSONDestination::init(args, json_options, &formatter))
                 }
-                #[cfg(feature = "use_parquet")]
-                DestinationCommand::Parquet(parquet_options) => {
-                    Destination::Parquet(ParquetDestination::init(parquet_options))
+                #[cfg(feature = "use_arrow")]
+                DestinationCommand::Arrow(arrow_options) => {
+                    Destination::Arrow(ArrowDestination::init(arrow_options))
+                }
+                #[cfg(feature = "use_avro")]
+                DestinationCommand::Avro(avro_options) => {
+                    Destination::Avro(AvroDestination::init(avro_options))
                 }
+                #[cfg(feature = "use_parquet")]
+                DestinationCommand::Parquet(parquet_options) => Destination::Parquet(
+                    ParquetDestination::init(parquet_options, source_query(src)),
+                ),
                 #[cfg(feature = "use_sqlite")]
                 DestinationCommand::Sqlite(sqlite_options) => {
                     Destination::Sqlite(SqliteDestination::init(sqlite_options))
@@ -81,14 +173,15 @@ pub fn export(
                     SpreadSheetXLSXDestination::init(options),
                 )),
                 #[cfg(feature = "use_text")]
-                DestinationCommand::Text(text_options) => {
-                    Destination::Text(TextDestination::init(args, text_options))
-                }
+                DestinationCommand::Text(text_options) => Destination::Text(Box::new(
+                    TextDestination::init(args, text_options, &formatter),
+                )),
                 #[cfg(feature = "use_text")]
                 DestinationCommand::TextVertical(text_vertical_options) => {
                     Destination::TextVertical(TextVerticalDestination::init(
                         args,
                         text_vertical_options,
+                        &formatter,
                     ))
                 }
             };
@@ -101,23 +194,59 @@ pub fn export(
             let destination: Destination = match &export_command.destination {
                 #[cfg(feature = "use_csv")]
                 DestinationCommand::CSV(csv_options) => {
-                    Destination::CSV(Box::new(CSVDestination::init(csv_options)))
+                    Destination::CSV(Box::new(CSVDestination::init(csv_options, &formatter)))
                 }
                 DestinationCommand::Debug(debug_options) => {
                     Destination::Debug(DebugDestination::init(args, debug_options))
                 }
                 #[cfg(feature = "use_html")]
                 DestinationCommand::HTML(html_options) => {
-                    Destination::HTML(HTMLDestination::init(html_options))
+                    Destination::HTML(HTMLDestination::init(html_options, &formatter))
+                }
+                #[cfg(feature = "use_xml")]
+                DestinationCommand::XML(xml_options) => {
+                    Destination::XML(XMLDestination::init(xml_options, &formatter))
+                }
+                #[cfg(feature = "use_yaml")]
+                DestinationCommand::YAML(yaml_options) => {
+                    Destination::YAML(YAMLDestination::init(yaml_options, &formatter))
+                }
+                #[cfg(feature = "use_toml")]
+                DestinationCommand::TOML(toml_options) => {
+                    Destination::TOML(TOMLDestination::init(toml_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::Latex(latex_options) => {
+                    Destination::Latex(LatexDestination::init(latex_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::Rst(rst_options) => {
+                    Destination::Rst(RstDestination::init(rst_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::FixedWidth(fixed_width_options) => Destination::FixedWidth(
+                    FixedWidthDestination::init(fixed_width_options, &formatter),
+                ),
+                #[cfg(feature = "use_template")]
+                DestinationCommand::Template(template_options) => {
+                    Destination::Template(TemplateDestination::init(template_options, &formatter))
                 }
                 #[cfg(feature = "use_json")]
                 DestinationCommand::JSON(json_options) => {
-                    Destination::JSON(JSONDestination::init(args, json_options))
+                    Destination::JSON(JSONDestination::init(args, json_options, &formatter))
                 }
-                #[cfg(feature = "use_parquet")]
-                DestinationCommand::Parquet(parquet_options) => {
-                    Destination::Parquet(ParquetDestination::init(parquet_options))
+                #[cfg(feature = "use_arrow")]
+                DestinationCommand::Arrow(arrow_options) => {
+                    Destination::Arrow(ArrowDestination::init(arrow_options))
+                }
+                #[cfg(feature = "use_avro")]
+                DestinationCommand::Avro(avro_options) => {
+                    Destination::Avro(AvroDestination::init(avro_options))
                 }
+                #[cfg(feature = "use_parquet")]
+                DestinationCommand::Parquet(parquet_options) => Destination::Parquet(
+                    ParquetDestination::init(parquet_options, source_query(src)),
+                ),
                 #[cfg(feature = "use_sqlite")]
                 DestinationCommand::Sqlite(sqlite_options) => {
                     Destination::Sqlite(SqliteDestination::init(sqlite_options))
@@ -131,14 +260,15 @@ pub fn export(
                     SpreadSheetXLSXDestination::init(options),
                 )),
                 #[cfg(feature = "use_text")]
-                DestinationCommand::Text(text_options) => {
-                    Destination::Text(TextDestination::init(args, text_options))
-                }
+                DestinationCommand::Text(text_options) => Destination::Text(Box::new(
+                    TextDestination::init(args, text_options, &formatter),
+                )),
                 #[cfg(feature = "use_text")]
                 DestinationCommand::TextVertical(text_vertical_options) => {
                     Destination::TextVertical(TextVerticalDestination::init(
                         args,
                         text_vertical_options,
+                        &formatter,
                     ))
                 }
             };
@@ -150,23 +280,59 @@ pub fn export(
             let destination: Destination = match &export_command.destination {
                 #[cfg(feature = "use_csv")]
                 DestinationCommand::CSV(csv_options) => {
-                    Destination::CSV(Box::new(CSVDestination::init(csv_options)))
+                    Destination::CSV(Box::new(CSVDestination::init(csv_options, &formatter)))
                 }
                 DestinationCommand::Debug(debug_options) => {
                     Destination::Debug(DebugDestination::init(args, debug_options))
                 }
                 #[cfg(feature = "use_html")]
                 DestinationCommand::HTML(html_options) => {
-                    Destination::HTML(HTMLDestination::init(html_options))
+                    Destination::HTML(HTMLDestination::init(html_options, &formatter))
+                }
+                #[cfg(feature = "use_xml")]
+                DestinationCommand::XML(xml_options) => {
+                    Destination::XML(XMLDestination::init(xml_options, &formatter))
+                }
+                #[cfg(feature = "use_yaml")]
+                DestinationCommand::YAML(yaml_options) => {
+                    Destination::YAML(YAMLDestination::init(yaml_options, &formatter))
+                }
+                #[cfg(feature = "use_toml")]
+                DestinationCommand::TOML(toml_options) => {
+                    Destination::TOML(TOMLDestination::init(toml_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::Latex(latex_options) => {
+                    Destination::Latex(LatexDestination::init(latex_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::Rst(rst_options) => {
+                    Destination::Rst(RstDestination::init(rst_options, &formatter))
+                }
+                #[cfg(feature = "use_text")]
+                DestinationCommand::FixedWidth(fixed_width_options) => Destination::FixedWidth(
+                    FixedWidthDestination::init(fixed_width_options, &formatter),
+                ),
+                #[cfg(feature = "use_template")]
+                DestinationCommand::Template(template_options) => {
+                    Destination::Template(TemplateDestination::init(template_options, &formatter))
                 }
                 #[cfg(feature = "use_json")]
                 DestinationCommand::JSON(json_options) => {
-                    Destination::JSON(JSONDestination::init(args, json_options))
+                    Destination::JSON(JSONDestination::init(args, json_options, &formatter))
                 }
-                #[cfg(feature = "use_parquet")]
-                DestinationCommand::Parquet(parquet_options) => {
-                    Destination::Parquet(ParquetDestination::init(parquet_options))
+                #[cfg(feature = "use_arrow")]
+                DestinationCommand::Arrow(arrow_options) => {
+                    Destination::Arrow(ArrowDestination::init(arrow_options))
+                }
+                #[cfg(feature = "use_avro")]
+                DestinationCommand::Avro(avro_options) => {
+                    Destination::Avro(AvroDestination::init(avro_options))
                 }
+                #[cfg(feature = "use_parquet")]
+                DestinationCommand::Parquet(parquet_options) => Destination::Parquet(
+                    ParquetDestination::init(parquet_options, source_query(src)),
+                ),
                 #[cfg(feature = "use_sqlite")]
                 DestinationCommand::Sqlite(sqlite_options) => {
                     Destination::Sqlite(SqliteDestination::init(sqlite_options))
@@ -180,27 +346,31 @@ pub fn export(
                     SpreadSheetXLSXDestination::init(options),
                 )),
                 #[cfg(feature = "use_text")]
-                DestinationCommand::Text(text_options) => {
-                    Destination::Text(TextDestination::init(args, text_options))
-                }
+                DestinationCommand::Text(text_options) => Destination::Text(Box::new(
+                    TextDestination::init(args, text_options, &formatter),
+                )),
                 #[cfg(feature = "use_text")]
                 DestinationCommand::TextVertical(text_vertical_options) => {
                     Destination::TextVertical(TextVerticalDestination::init(
                         args,
                         text_vertical_options,
+                        &formatter,
                     ))
                 }
             };
             (source, destination)
         }
     };
+    #[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+    let sheets = sheet_queries(src, &export_command.destination);
+    #[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+    let single_result = sheets.is_empty();
+    #[cfg(not(any(feature = "use_ods", feature = "use_xlsx")))]
+    let single_result = true;
     destination.prepare();
     let mut source_connection = source.connect();
-    let mut it = source_connection.batch_iterator(export_command.batch_size);
-    destination.prepare_for_results(&*it);
-    let mut processed = 0;
     let progress_bar = if args.verbose {
-        let pb = ProgressBar::new(it.get_count().unwrap_or_default());
+        let pb = ProgressBar::new(0);
         pb.set_style(
             indicatif::ProgressStyle::default_bar()
                 .template("Processed {pos:>7}/{len:7} rows in {elapsed_precise}")
@@ -210,21 +380,48 @@ pub fn export(
     } else {
         None
     };
-
-    loop {
-        let rows_option = it.next();
-        match rows_option {
-            Some(rows) => {
-                destination.add_rows(&rows);
-                processed += rows.len();
-                if let Some(ref pb) = progress_bar {
-                    pb.inc(rows.len() as u64);
-                }
-            }
-            None => {
-                break;
-            }
-        }
+    let mut processed = 0;
+    if single_result {
+        let mut it = TransformIterator::wrap(
+            MaskIterator::wrap(
+                SchemaOverrideIterator::wrap(
+                    source_connection.batch_iterator(export_command.batch_size),
+                    &overrides,
+                ),
+                &masking,
+            ),
+            &steps,
+        );
+        destination.prepare_for_results(&*it);
+        //pager consumes rows itself, as far as user scrolls
+        #[cfg(feature = "use_pager")]
+        let paged = match &mut destination {
+            Destination::Text(text_destination) => text_destination.page(&mut *it),
+            _ => None,
+        };
+        #[cfg(not(feature = "use_pager"))]
+        let paged = None;
+        processed += match paged {
+            Some(count) => count,
+            None => copy_rows(&mut *it, &mut destination, progress_bar.as_ref()),
+        };
+    }
+    //each query lands on its own sheet, all of them use the same connection
+    #[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+    for sheet in sheets.iter() {
+        let mut it = TransformIterator::wrap(
+            MaskIterator::wrap(
+                SchemaOverrideIterator::wrap(
+                    source_connection.query_batch_iterator(&sheet.query, export_command.batch_size),
+                    &overrides,
+                ),
+                &masking,
+            ),
+            &steps,
+        );
+        destination.set_sheet_title(&sheet.name);
+        destination.prepare_for_results(&*it);
+        processed += copy_rows(&mut *it, &mut destination, progress_bar.as_ref());
     }
     destination.close();
     let duration = Utc::now()
@@ -244,6 +441,158 @@ pub fn export(
     }
 }
 
+/// pass all rows from iterator to destination
+fn copy_rows(
+    it: &mut dyn DataSourceBatchIterator,
+    destination: &mut Destination,
+    progress_bar: Option<&ProgressBar>,
+) -> usize {
+    let mut processed = 0;
+    if let Some(pb) = progress_bar {
+        pb.inc_length(it.get_count().unwrap_or_default());
+    }
+    loop {
+        let rows_option = it.next();
+        match rows_option {
+            Some(rows) => {
+                destination.add_rows(&rows);
+                processed += rows.len();
+                if let Some(pb) = progress_bar {
+                    pb.inc(rows.len() as u64);
+                }
+            }
+            None => {
+                break;
+            }
+        }
+    }
+    processed
+}
+
+/// value formatter from config file, with options given in command line
+fn value_formatter(export_command: &ExportCommand) -> ValueFormatter {
+    let mut formatter = ValueFormatter::from_config();
+    if let Some(date_format) = &export_command.date_format {
+        formatter.date_format = date_format.clone();
+    }
+    if let Some(time_format) = &export_command.time_format {
+        formatter.time_format = time_format.clone();
+    }
+    if let Some(datetime_format) = &export_command.datetime_format {
+        formatter.datetime_format = datetime_format.clone();
+    }
+    if export_command.float_precision.is_some() {
+        formatter.float_precision = export_command.float_precision;
+    }
+    if export_command.thousands_separator.is_some() {
+        formatter.thousands_separator = export_command.thousands_separator.clone();
+    }
+    if export_command.decimal_comma {
+        formatter.decimal_comma = true;
+    }
+    if let Some((true_value, false_value)) = &export_command.bool_format {
+        formatter.true_value = true_value.clone();
+        formatter.false_value = false_value.clone();
+    }
+    if export_command.null_marker.is_some() {
+        formatter.null_marker = export_command.null_marker.clone();
+    }
+    if let Some(binary_format) = export_command.binary_format {
+        formatter.binary_format = binary_format;
+    }
+    formatter.validate();
+    formatter
+}
+
+/// transformation steps from --transform-file and command line
+fn transform_steps(export_command: &ExportCommand) -> Vec<Step> {
+    let mut steps: Vec<Step> = export_command
+        .computed
+        .iter()
+        .map(|(name, expression)| Step::Compute(name.clone(), expression.clone()))
+        .collect();
+    steps.extend(export_command.filters.iter().cloned().map(Step::Filter));
+    if !export_command.drop.is_empty() {
+        steps.push(Step::Drop(export_command.drop.clone()));
+    }
+    if !export_command.select.is_empty() {
+        steps.push(Step::Select(export_command.select.clone()));
+    }
+    if !export_command.rename.is_empty() {
+        steps.push(Step::Rename(export_command.rename.clone()));
+    }
+    load_steps(export_command.transform_file.as_deref(), steps)
+}
+
+/// source query given with -q or -f, if any
+#[cfg(feature = "use_parquet")]
+fn source_query(src: &DataSourceCommand) -> Option<String> {
+    let (query, query_file) = match src {
+        #[cfg(feature = "use_mysql")]
+        DataSourceCommand::Mysql(options) => (&options.query, &options.query_file),
+        #[cfg(feature = "use_postgres")]
+        DataSourceCommand::Postgres(options) => (&options.query, &options.query_file),
+        #[cfg(feature = "use_sqlite")]
+        DataSourceCommand::Sqlite(options) => (&options.query, &options.query_file),
+    };
+    match (query, query_file) {
+        (Some(query), _) => Some(query.clone()),
+        (None, Some(path)) => std::fs::read_to_string(path).ok(),
+        (None, None) => None,
+    }
+}
+
+/// parse key=value
+#[cfg(feature = "use_parquet")]
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected key=value, got {}", value)),
+    }
+}
+
+/// queries given with --sheet for spreadsheet destinations
+#[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+fn sheet_queries(src: &DataSourceCommand, destination: &DestinationCommand) -> Vec<SheetQuery> {
+    let sheets = match destination {
+        #[cfg(feature = "use_ods")]
+        DestinationCommand::ODS(options) => options.sheets.clone(),
+        #[cfg(feature = "use_xlsx")]
+        DestinationCommand::XLSX(options) => options.sheets.clone(),
+        _ => vec![],
+    };
+    if sheets.is_empty() {
+        return sheets;
+    }
+    let has_query = match src {
+        #[cfg(feature = "use_mysql")]
+        DataSourceCommand::Mysql(options) => {
+            options.query.is_some() || options.query_file.is_some()
+        }
+        #[cfg(feature = "use_postgres")]
+        DataSourceCommand::Postgres(options) => {
+            options.query.is_some() || options.query_file.is_some()
+        }
+        #[cfg(feature = "use_sqlite")]
+        DataSourceCommand::Sqlite(options) => {
+            options.query.is_some() || options.query_file.is_some()
+        }
+    };
+    if has_query {
+        eprintln!("export: --sheet replaces source query, don't use it together with -q or -f");
+        std::process::exit(1);
+    }
+    //sheet names are case insensitive
+    let mut seen = std::collections::HashSet::new();
+    for sheet in sheets.iter() {
+        if !seen.insert(sheet.name.to_lowercase()) {
+            eprintln!("export: sheet name '{}' is used more than once", sheet.name);
+            std::process::exit(1);
+        }
+    }
+    sheets
+}
+
 #[derive(Debug, Parser)]
 pub struct ExportCommand {
     #[arg(
@@ -253,6 +602,107 @@ pub struct ExportCommand {
         default_value = "500"
     )]
     batch_size: u64,
+    #[arg(
+        long = "column",
+        help = "override column type and optionally name: name:type[:newname] (types: u64, i64, u32, i32, u16, i16, u8, i8, f64, f32, string, bytes, bool, date, datetime, time, timestamp, decimal, json)",
+        value_parser = parse_column_override
+    )]
+    columns: Vec<ColumnOverride>,
+    #[arg(
+        long = "schema-file",
+        help = "toml file with column type and name overrides ([[column]] entries with name, type and optional rename)"
+    )]
+    schema_file: Option<String>,
+    #[arg(
+        long = "mask",
+        help = "mask values of column (name, or /regex/) before transformations: column=hash|fake|null|redact[:N]|shift-date:DAYS",
+        value_parser = parse_mask_rule
+    )]
+    masks: Vec<MaskRule>,
+    #[arg(
+        long = "mask-file",
+        help = "toml file with salt and masking rules ([[rule]] entries with column or pattern, and method), applied before ones from command line"
+    )]
+    mask_file: Option<String>,
+    #[arg(
+        long = "mask-salt",
+        help = "secret used by hash, fake and shift-date masking, the same salt gives the same values in every export"
+    )]
+    mask_salt: Option<String>,
+    #[arg(
+        long = "compute",
+        help = "add column computed from expression, or replace existing one: \"name = expression\"",
+        value_parser = parse_compute
+    )]
+    computed: Vec<(String, Expression)>,
+    #[arg(
+        long = "filter",
+        help = "keep only rows matching expression, ie. \"age >= 18 and country = 'PL'\"",
+        value_parser = parse_expression
+    )]
+    filters: Vec<Expression>,
+    #[arg(long = "drop", value_delimiter = ',', help = "remove given columns")]
+    drop: Vec<String>,
+    #[arg(
+        long = "select",
+        value_delimiter = ',',
+        help = "keep only given columns, in given order"
+    )]
+    select: Vec<String>,
+    #[arg(long = "rename", help = "rename column: old=new", value_parser = parse_rename)]
+    rename: Vec<(String, String)>,
+    #[arg(
+        long = "transform-file",
+        help = "toml file with transformation steps ([[step]] entries with compute, filter, drop, select or rename), applied before ones from command line. Command line steps are applied in order: compute, filter, drop, select, rename"
+    )]
+    transform_file: Option<String>,
+    #[arg(
+        long = "date-format",
+        help = "format of dates in text based destinations, ie. %d.%m.%Y (default %Y-%m-%d)"
+    )]
+    date_format: Option<String>,
+    #[arg(
+        long = "time-format",
+        help = "format of times in text based destinations (default %H:%M:%S)"
+    )]
+    time_format: Option<String>,
+    #[arg(
+        long = "datetime-format",
+        help = "format of datetimes in text based destinations (default %Y-%m-%d %H:%M:%S)"
+    )]
+    datetime_format: Option<String>,
+    #[arg(
+        long = "float-precision",
+        help = "digits after decimal point of floats (shortest exact representation by default)"
+    )]
+    float_precision: Option<usize>,
+    #[arg(
+        long = "thousands-separator",
+        help = "text put between thousands of numbers in text destinations, ie. ' '"
+    )]
+    thousands_separator: Option<String>,
+    #[arg(
+        long = "decimal-comma",
+        help = "use comma as decimal point in text destinations"
+    )]
+    decimal_comma: bool,
+    #[arg(
+        long = "bool-format",
+        help = "texts for true and false in text destinations, ie. yes,no",
+        value_parser = parse_bool_format
+    )]
+    bool_format: Option<(String, String)>,
+    #[arg(
+        long = "null-marker",
+        help = "text written for NULL values in text destinations (destination default if not given)"
+    )]
+    null_marker: Option<String>,
+    #[arg(
+        long = "binary-format",
+        value_enum,
+        help = "how binary values are written as text"
+    )]
+    binary_format: Option<BinaryFormat>,
     #[command(subcommand)]
     pub destination: DestinationCommand,
 }
@@ -284,33 +734,256 @@ pub enum DestinationCommand {
     #[cfg(feature = "use_xlsx")]
     #[command(name = "xlsx", about = "XLSX spreadsheet")]
     XLSX(SpreadSheetDestinationOptions),
+    #[cfg(feature = "use_arrow")]
+    #[command(
+        name = "arrow",
+        visible_alias = "feather",
+        about = "Apache Arrow IPC (Feather) file or stream"
+    )]
+    Arrow(ArrowDestinationOptions),
+    #[cfg(feature = "use_avro")]
+    #[command(name = "avro", about = "Avro object container file")]
+    Avro(AvroDestinationOptions),
     #[cfg(feature = "use_parquet")]
     #[command(name = "parquet", about = "parquet file")]
     Parquet(ParquetDestinationOptions),
     #[cfg(feature = "use_sqlite")]
     #[command(name = "sqlite", about = "Sqlite file")]
     Sqlite(SqliteDestinationOptions),
+    #[cfg(feature = "use_template")]
+    #[command(
+        name = "template",
+        about = "Custom format rendered with jinja-like template"
+    )]
+    Template(TemplateDestinationOptions),
     #[cfg(feature = "use_text")]
     #[command(name = "text", about = "Text")]
     Text(TextDestinationOptions),
     #[cfg(feature = "use_text")]
     #[command(name = "text-vertical", about = "Text (columns displayed vertically)")]
     TextVertical(TextVerticalDestinationOptions),
+    #[cfg(feature = "use_text")]
+    #[command(name = "latex", about = "LaTeX table")]
+    Latex(LatexDestinationOptions),
+    #[cfg(feature = "use_text")]
+    #[command(name = "rst", about = "reStructuredText table")]
+    Rst(RstDestinationOptions),
+    #[cfg(feature = "use_text")]
+    #[command(name = "fixed-width", about = "Fixed-width text records")]
+    FixedWidth(FixedWidthDestinationOptions),
     #[cfg(feature = "use_html")]
     #[command(name = "html", about = "HTML")]
     HTML(HTMLDestinationOptions),
     #[cfg(feature = "use_json")]
     #[command(name = "json", about = "JSON")]
     JSON(JSONDestinationOptions),
+    #[cfg(feature = "use_xml")]
+    #[command(name = "xml", about = "XML")]
+    XML(XMLDestinationOptions),
+    #[cfg(feature = "use_yaml")]
+    #[command(name = "yaml", visible_alias = "yml", about = "YAML")]
+    YAML(YAMLDestinationOptions),
+    #[cfg(feature = "use_toml")]
+    #[command(name = "toml", about = "TOML (array of tables)")]
+    TOML(TOMLDestinationOptions),
     #[command(name = "debug", about = "Debug output")]
     Debug(DebugDestinationOptions),
 }
 
+#[cfg(feature = "use_arrow")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ArrowFormat {
+    File,
+    Stream,
+}
+
+#[cfg(feature = "use_arrow")]
+#[derive(Clone, Debug, Parser)]
+pub struct ArrowDestinationOptions {
+    #[arg(help = "arrow filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        short = 'f',
+        long = "format",
+        help = "IPC format (default: stream for stdout, file otherwise)"
+    )]
+    pub format: Option<ArrowFormat>,
+    #[arg(
+        long = "decimal-precision",
+        help = "precision of decimal columns (at most 38)",
+        default_value = "38"
+    )]
+    pub decimal_precision: u8,
+    #[arg(
+        long = "decimal-scale",
+        help = "scale of decimal columns",
+        default_value = "10"
+    )]
+    pub decimal_scale: i8,
+    #[arg(
+        long = "coerce",
+        help = "convert values that don't match column type, ie. integers of other width or numeric text"
+    )]
+    pub coerce: bool,
+    #[arg(
+        long = "strict",
+        help = "fail on values that can't be converted without loss, instead of writing null"
+    )]
+    pub strict: bool,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
+#[cfg(feature = "use_avro")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AvroCodec {
+    Null,
+    Deflate,
+    Snappy,
+    Zstd,
+}
+
+#[cfg(feature = "use_avro")]
+#[derive(Clone, Debug, Parser)]
+pub struct AvroDestinationOptions {
+    #[arg(help = "avro filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        long = "schema-name",
+        help = "name of generated record schema (may include namespace, ie. com.example.Row)",
+        default_value = "Row"
+    )]
+    pub schema_name: String,
+    #[arg(long = "codec", help = "compression codec", default_value = "null")]
+    pub codec: AvroCodec,
+    #[arg(
+        long = "decimal-precision",
+        help = "precision of decimal columns (at most 38)",
+        default_value = "38"
+    )]
+    pub decimal_precision: u32,
+    #[arg(
+        long = "decimal-scale",
+        help = "scale of decimal columns",
+        default_value = "10"
+    )]
+    pub decimal_scale: u32,
+    #[arg(
+        long = "round",
+        help = "round float values in decimal columns to decimal scale, instead of failing on ones with more fractional digits"
+    )]
+    pub round: bool,
+    #[arg(
+        long = "avsc",
+        num_args = 0..=1,
+        help = "also write schema to .avsc file (defaults to avro filename with .avsc extension)"
+    )]
+    pub avsc: Option<Option<String>>,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
+#[cfg(feature = "use_parquet")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ParquetCompression {
+    None,
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+    Brotli,
+}
+
+#[cfg(feature = "use_parquet")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ParquetStatistics {
+    None,
+    Chunk,
+    Page,
+}
+
 #[cfg(feature = "use_parquet")]
 #[derive(Clone, Debug, Parser)]
 pub struct ParquetDestinationOptions {
     #[arg(help = "parquet filename")]
     pub filename: String,
+    #[arg(
+        long = "compression",
+        help = "compression codec",
+        default_value = "none"
+    )]
+    pub compression: ParquetCompression,
+    #[arg(
+        long = "compression-level",
+        help = "compression level (gzip: 0-9, zstd: 1-22, brotli: 0-11)"
+    )]
+    pub compression_level: Option<u32>,
+    #[arg(
+        long = "max-row-group-size",
+        help = "maximum number of rows in a row group"
+    )]
+    pub max_row_group_size: Option<usize>,
+    #[arg(long = "data-page-size", help = "target size of data page in bytes")]
+    pub data_page_size: Option<usize>,
+    #[arg(long = "no-dictionary", help = "disable dictionary encoding")]
+    pub no_dictionary: bool,
+    #[arg(
+        long = "dictionary-column",
+        help = "enable dictionary encoding for given column (overrides --no-dictionary)"
+    )]
+    pub dictionary_columns: Vec<String>,
+    #[arg(
+        long = "no-dictionary-column",
+        help = "disable dictionary encoding for given column"
+    )]
+    pub no_dictionary_columns: Vec<String>,
+    #[arg(
+        long = "statistics",
+        help = "level of column statistics",
+        default_value = "page"
+    )]
+    pub statistics: ParquetStatistics,
+    #[arg(
+        long = "metadata",
+        help = "add key=value to file metadata",
+        value_parser = parse_key_value
+    )]
+    pub metadata: Vec<(String, String)>,
+    #[arg(
+        long = "source-metadata",
+        help = "add source query (dbfish.query) and export time (dbfish.exported_at) to file metadata"
+    )]
+    pub source_metadata: bool,
+    #[arg(
+        long = "decimal-precision",
+        help = "precision of decimal columns (at most 38)",
+        default_value = "38"
+    )]
+    pub decimal_precision: u8,
+    #[arg(
+        long = "decimal-scale",
+        help = "scale of decimal columns",
+        default_value = "10"
+    )]
+    pub decimal_scale: i8,
+    #[arg(
+        long = "coerce",
+        help = "convert values that don't match column type, ie. integers of other width or numeric text"
+    )]
+    pub coerce: bool,
+    #[arg(
+        long = "strict",
+        help = "fail on values that can't be converted without loss, instead of writing null"
+    )]
+    pub strict: bool,
     #[arg(
         short = 't',
         long = "truncate",
@@ -319,6 +992,15 @@ pub struct ParquetDestinationOptions {
     pub truncate: Option<u64>,
 }
 
+#[cfg(feature = "use_sqlite")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SqliteIfExists {
+    Fail,
+    ReplaceTable,
+    Append,
+    Upsert,
+}
+
 #[cfg(feature = "use_sqlite")]
 #[derive(Clone, Debug, Parser)]
 pub struct SqliteDestinationOptions {
@@ -332,6 +1014,28 @@ pub struct SqliteDestinationOptions {
         help = "truncate data to given amount of graphemes"
     )]
     pub truncate: Option<u64>,
+    #[arg(
+        long = "if-exists",
+        help = "what to do if table already exists",
+        default_value = "replace-table"
+    )]
+    pub if_exists: SqliteIfExists,
+    #[arg(
+        long = "primary-key",
+        help = "primary key column (repeat for composite key). Required for upsert"
+    )]
+    pub primary_key: Vec<String>,
+    #[arg(
+        long = "index",
+        help = "create index on given column once data is loaded. Use comma to index several columns together"
+    )]
+    pub index: Vec<String>,
+    #[arg(
+        long = "commit-interval",
+        help = "commit transaction every N rows (0 means one transaction for entire export)",
+        default_value = "0"
+    )]
+    pub commit_interval: u64,
 }
 
 #[cfg(feature = "use_csv")]
@@ -347,6 +1051,63 @@ pub struct CSVDestinationOptions {
     pub truncate: Option<u64>,
     #[arg(long = "no-headers", help = "skip header")]
     pub no_headers: bool,
+    #[arg(
+        short = 'd',
+        long = "delimiter",
+        help = "field delimiter (use '\\t' or 'tab' for tabulator)",
+        default_value = ","
+    )]
+    pub delimiter: String,
+    #[arg(
+        long = "tsv",
+        help = "tab separated values (same as --delimiter tab)",
+        conflicts_with = "delimiter"
+    )]
+    pub tsv: bool,
+    #[arg(long = "quote", help = "quote character", default_value = "\"")]
+    pub quote: String,
+    #[arg(
+        long = "quote-style",
+        help = "when to quote fields",
+        default_value = "necessary"
+    )]
+    pub quote_style: CSVQuoteStyle,
+    #[arg(
+        long = "escape-style",
+        help = "how to escape quote characters inside quoted fields",
+        default_value = "double"
+    )]
+    pub escape_style: CSVEscapeStyle,
+    #[arg(long = "crlf", help = "use CRLF line endings")]
+    pub crlf: bool,
+    #[arg(
+        long = "bom",
+        help = "write UTF-8 byte order mark (helps Excel detect encoding)"
+    )]
+    pub bom: bool,
+    #[arg(
+        long = "null",
+        help = "text written unquoted for NULL values (ie. '\\N'), export --null-marker or empty by default. When it is empty, empty texts are quoted"
+    )]
+    pub null: Option<String>,
+}
+
+#[cfg(feature = "use_csv")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CSVQuoteStyle {
+    Always,
+    Necessary,
+    NonNumeric,
+    Never,
+}
+
+#[cfg(feature = "use_csv")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CSVEscapeStyle {
+    ///double quote character ("")
+    Double,
+    ///prefix quote character with backslash (\")
+    Backslash,
 }
 
 #[derive(Clone, Debug, Parser)]
@@ -372,6 +1133,84 @@ pub struct SpreadSheetDestinationOptions {
         help = "truncate data to given amount of graphemes"
     )]
     pub truncate: Option<u64>,
+    #[arg(
+        long = "header-format",
+        help = "xlsx: header cell format, space separated list of bold, italic, underline, border, wrap, fg:<colour>, bg:<colour>",
+        default_value = "bold"
+    )]
+    pub header_format: String,
+    #[arg(long = "no-autofilter", help = "xlsx: don't add autofilter to header")]
+    pub no_autofilter: bool,
+    #[arg(long = "no-freeze-header", help = "xlsx: don't freeze header row")]
+    pub no_freeze_header: bool,
+    #[arg(
+        long = "freeze-columns",
+        help = "xlsx: number of leading columns to freeze",
+        default_value = "0"
+    )]
+    pub freeze_columns: u16,
+    #[arg(
+        long = "no-autofit",
+        help = "xlsx: don't size columns to their content"
+    )]
+    pub no_autofit: bool,
+    #[arg(
+        long = "autofit-rows",
+        help = "xlsx: number of rows sampled to compute column widths",
+        default_value = "1000"
+    )]
+    pub autofit_rows: usize,
+    #[arg(
+        long = "table",
+        help = "xlsx: format data as Excel table with given style (e.g. medium9, light1, dark2)",
+        num_args = 0..=1,
+        default_missing_value = "medium9"
+    )]
+    pub table: Option<String>,
+    #[arg(
+        long = "on-row-limit",
+        help = "what to do when sheet is full",
+        default_value = "new-sheet"
+    )]
+    pub on_row_limit: SheetOverflow,
+    #[arg(
+        long = "max-sheet-rows",
+        help = "maximum number of rows in a sheet, header included (default and upper limit: 1048576)"
+    )]
+    pub max_sheet_rows: Option<usize>,
+    #[arg(
+        long = "sheet",
+        help = "export given query to its own named sheet: name=query or name=@file.sql. Can be repeated, replaces source query",
+        value_parser = parse_sheet_query
+    )]
+    pub sheets: Vec<SheetQuery>,
+    #[arg(
+        long = "locale",
+        help = "ods: locale of number and date formats, e.g. de-DE (default: system locale)"
+    )]
+    pub locale: Option<String>,
+    #[arg(
+        long = "low-memory",
+        help = "write rows to disk as they arrive instead of building whole workbook in memory (xlsx: --table is not available)"
+    )]
+    pub low_memory: bool,
+    #[arg(
+        long = "low-memory-threshold",
+        help = "switch to --low-memory when source reports more rows than that (see source --count option)",
+        default_value = "500000"
+    )]
+    pub low_memory_threshold: u64,
+}
+
+#[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SheetOverflow {
+    ///continue in next sheet of the same workbook
+    NewSheet,
+    ///stop export with an error
+    Error,
+    ///continue in next workbook file (name_2.xlsx, name_3.xlsx...)
+    NewFile,
 }
 
 #[cfg(feature = "use_text")]
@@ -385,6 +1224,45 @@ pub struct TextDestinationOptions {
         help = "truncate data to given amount of graphemes"
     )]
     pub truncate: Option<u64>,
+    #[arg(
+        short = 's',
+        long = "stream",
+        help = "print rows as they arrive instead of keeping whole result in memory"
+    )]
+    pub stream: bool,
+    #[arg(
+        long = "sample-rows",
+        help = "number of rows used to compute column widths when streaming (0 uses --max-width)",
+        default_value = "100"
+    )]
+    pub sample_rows: usize,
+    #[arg(long = "max-width", help = "maximum column width when streaming")]
+    pub max_width: Option<usize>,
+    #[arg(
+        long = "page-size",
+        help = "repeat header every N rows when streaming (0 to disable)",
+        default_value = "50"
+    )]
+    pub page_size: usize,
+    #[arg(
+        long = "overflow",
+        help = "what to do with values wider than column when streaming",
+        default_value = "ellipsis"
+    )]
+    pub overflow: TextOverflow,
+    #[cfg(feature = "use_pager")]
+    #[arg(
+        long = "no-pager",
+        help = "don't open interactive viewer when result doesn't fit on terminal"
+    )]
+    pub no_pager: bool,
+}
+
+#[cfg(feature = "use_text")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TextOverflow {
+    Wrap,
+    Ellipsis,
 }
 
 #[cfg(feature = "use_text")]
@@ -402,6 +1280,104 @@ pub struct TextVerticalDestinationOptions {
     pub sort_columns: bool,
 }
 
+#[cfg(feature = "use_text")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LatexEnvironment {
+    Tabular,
+    Longtable,
+}
+
+#[cfg(feature = "use_text")]
+#[derive(Clone, Debug, Parser)]
+pub struct LatexDestinationOptions {
+    #[arg(help = "latex filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        short = 'e',
+        long = "environment",
+        help = "table environment. longtable can span several pages",
+        default_value = "tabular"
+    )]
+    pub environment: LatexEnvironment,
+    #[arg(
+        long = "caption",
+        help = "table caption. tabular is then placed in table float"
+    )]
+    pub caption: Option<String>,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
+#[cfg(feature = "use_text")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RstStyle {
+    Grid,
+    Simple,
+}
+
+#[cfg(feature = "use_text")]
+#[derive(Clone, Debug, Parser)]
+pub struct RstDestinationOptions {
+    #[arg(help = "rst filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        short = 's',
+        long = "style",
+        help = "table style",
+        default_value = "grid"
+    )]
+    pub style: RstStyle,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
+#[cfg(feature = "use_text")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FixedWidthOverflow {
+    Fail,
+    Truncate,
+}
+
+#[cfg(feature = "use_text")]
+#[derive(Clone, Debug, Parser)]
+pub struct FixedWidthDestinationOptions {
+    #[arg(help = "output filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        short = 'l',
+        long = "layout",
+        help = "layout file (toml) with column widths, alignment and padding. Without it widths are computed from data"
+    )]
+    pub layout: Option<String>,
+    #[arg(
+        long = "on-overflow",
+        help = "what to do with values longer than column width",
+        default_value = "fail"
+    )]
+    pub overflow: FixedWidthOverflow,
+    #[arg(long = "crlf", help = "use CRLF line endings")]
+    pub crlf: bool,
+    #[arg(
+        long = "trailer",
+        help = "add trailer record. {count} or {count:N} (zero padded to N digits) is replaced with row count"
+    )]
+    pub trailer: Option<String>,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
 #[cfg(feature = "use_html")]
 #[derive(Clone, Debug, Parser)]
 pub struct HTMLDestinationOptions {
@@ -415,6 +1391,98 @@ pub struct HTMLDestinationOptions {
     pub truncate: Option<u64>,
     #[arg(long = "title", help = "html page title")]
     pub title: Option<String>,
+    #[arg(
+        long = "self-contained",
+        help = "interactive page that works offline: sorting, search, filters, column toggles, csv download"
+    )]
+    pub self_contained: bool,
+}
+
+#[cfg(feature = "use_template")]
+#[derive(Clone, Debug, Parser)]
+pub struct TemplateDestinationOptions {
+    #[arg(help = "output filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        short = 'T',
+        long = "template",
+        help = "template file with {% block header %}, {% block row %} and {% block footer %} sections"
+    )]
+    pub template: String,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
+#[cfg(feature = "use_xml")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum XMLMode {
+    Elements,
+    Attributes,
+}
+
+#[cfg(feature = "use_xml")]
+#[derive(Clone, Debug, Parser)]
+pub struct XMLDestinationOptions {
+    #[arg(help = "xml filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(long = "root", help = "root element name", default_value = "rows")]
+    pub root: String,
+    #[arg(long = "row", help = "row element name", default_value = "row")]
+    pub row: String,
+    #[arg(
+        long = "mode",
+        help = "store columns as child elements (NULL marked with xsi:nil) or row attributes (NULL omitted)",
+        default_value = "elements"
+    )]
+    pub mode: XMLMode,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
+#[cfg(feature = "use_yaml")]
+#[derive(Clone, Debug, Parser)]
+pub struct YAMLDestinationOptions {
+    #[arg(help = "yaml filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        short = 'k',
+        long = "key-column",
+        help = "write mapping keyed by values of this column instead of sequence of rows"
+    )]
+    pub key_column: Option<String>,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
+}
+
+#[cfg(feature = "use_toml")]
+#[derive(Clone, Debug, Parser)]
+pub struct TOMLDestinationOptions {
+    #[arg(help = "toml filename. Use '-' for stdout")]
+    pub filename: String,
+    #[arg(
+        long = "table",
+        help = "name of array of tables",
+        default_value = "rows"
+    )]
+    pub table: String,
+    #[arg(
+        short = 't',
+        long = "truncate",
+        help = "truncate data to given amount of graphemes"
+    )]
+    pub truncate: Option<u64>,
 }
 
 #[cfg(feature = "use_json")]
@@ -593,3 +1661,167 @@ impl SqliteSourceOptions {
         }
     }
 }
+
+#[cfg(all(test, feature = "use_sqlite"))]
+mod tests {
+    use super::*;
+    use crate::commands::UseColor;
+    #[cfg(feature = "use_xlsx")]
+    use crate::utils::testing::zip_entry;
+    use crate::utils::testing::{exits_with, temp_path};
+
+    const INIT: [&str; 4] = [
+        "-i",
+        "create table people(id integer, name text, age integer, country text)",
+        "-i",
+        "insert into people values (1, 'Ann', 34, 'PL'), (2, 'Bob', 17, 'PL'), (3, 'Cy', 52, 'DE')",
+    ];
+
+    /// export from in-memory sqlite database with people table
+    fn run_export(source_args: &[&str], export_args: &[&str]) {
+        let source_options =
+            SqliteSourceOptions::try_parse_from(["sqlite"].iter().chain(&INIT).chain(source_args))
+                .unwrap();
+        let export_command =
+            ExportCommand::try_parse_from(["export"].iter().chain(export_args)).unwrap();
+        let args = ApplicationArguments {
+            verbose: false,
+            color: UseColor::No,
+        };
+        export(
+            &args,
+            &DataSourceCommand::Sqlite(source_options),
+            &export_command,
+        );
+    }
+
+    #[cfg(feature = "use_xlsx")]
+    #[test]
+    fn test_sheet_queries() {
+        let filename = temp_path("sheets.xlsx");
+        run_export(
+            &[],
+            &[
+                "xlsx",
+                filename.as_str(),
+                "--sheet",
+                "Summary=select count(*) as people from people",
+                "--sheet",
+                "Adults=select name from people where age >= 18 order by id",
+            ],
+        );
+        let workbook = zip_entry(&filename, "xl/workbook.xml");
+        assert!(
+            workbook.contains(r#"<sheet name="Summary" sheetId="1""#),
+            "{}",
+            workbook
+        );
+        assert!(
+            workbook.contains(r#"<sheet name="Adults" sheetId="2""#),
+            "{}",
+            workbook
+        );
+        let summary = zip_entry(&filename, "xl/worksheets/sheet1.xml");
+        assert!(summary.contains(r#"<c r="A2"><v>3</v></c>"#), "{}", summary);
+        //Adults sheet has header and two rows, each sheet has own autofilter
+        let adults = zip_entry(&filename, "xl/worksheets/sheet2.xml");
+        assert!(
+            adults.contains(r#"<autoFilter ref="A1:A3"/>"#),
+            "{}",
+            adults
+        );
+        let strings = zip_entry(&filename, "xl/sharedStrings.xml");
+        for text in ["people", "name", "Ann", "Cy"] {
+            assert!(strings.contains(&format!("<t>{}</t>", text)), "{}", strings);
+        }
+        assert!(!strings.contains("<t>Bob</t>"), "{}", strings);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[cfg(feature = "use_xlsx")]
+    #[test]
+    fn test_sheet_queries_errors() {
+        let stderr = exits_with(module_path!(), "test_sheet_queries_errors", || {
+            let filename = temp_path("sheets.xlsx");
+            run_export(
+                &["-q", "select 1"],
+                &["xlsx", filename.as_str(), "--sheet", "One=select 1"],
+            );
+        });
+        assert!(
+            stderr.contains("--sheet replaces source query"),
+            "{}",
+            stderr
+        );
+        assert!(parse_sheet_query("Sum:mary=select 1").is_err());
+        assert!(parse_sheet_query("Summary= ").is_err());
+    }
+
+    #[cfg(feature = "use_csv")]
+    #[test]
+    fn test_transform_steps() {
+        let transform_file = temp_path("transform.toml");
+        std::fs::write(
+            &transform_file,
+            "[[step]]\ncompute = \"label = name || '/' || country\"\n",
+        )
+        .unwrap();
+        let filename = temp_path("transform.csv");
+        run_export(
+            &["-q", "select * from people order by id"],
+            &[
+                "--transform-file",
+                transform_file.as_str(),
+                "--filter",
+                "age >= 18",
+                "--compute",
+                "age = age + 1",
+                "--drop",
+                "country",
+                "--select",
+                "label,age,id",
+                "--rename",
+                "label=tag",
+                "csv",
+                filename.as_str(),
+            ],
+        );
+        //file steps go first, then command line ones: compute, filter, drop, select, rename.
+        //Bob is 18 after compute, so filter keeps him
+        assert_eq!(
+            std::fs::read_to_string(&filename).unwrap(),
+            "tag,age,id\nAnn/PL,35,1\nBob/PL,18,2\nCy/DE,53,3\n"
+        );
+        std::fs::remove_file(&filename).unwrap();
+        std::fs::remove_file(&transform_file).unwrap();
+    }
+
+    #[cfg(feature = "use_csv")]
+    #[test]
+    fn test_decimal_format() {
+        let filename = temp_path("decimal.csv");
+        run_export(
+            &[
+                "-q",
+                "select id, age * 1000.5 as amount from people order by id",
+            ],
+            &[
+                "--column",
+                "amount:decimal",
+                "--decimal-comma",
+                "--thousands-separator",
+                " ",
+                "csv",
+                filename.as_str(),
+                "--delimiter",
+                ";",
+            ],
+        );
+        //decimals are formatted like other numbers
+        assert_eq!(
+            std::fs::read_to_string(&filename).unwrap(),
+            "id;amount\n1;34 017\n2;17 008,5\n3;52 026\n"
+        );
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
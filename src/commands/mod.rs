@@ -40,6 +40,7 @@ pub struct ApplicationArguments {
     pub color: UseColor,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 pub enum SourceLevelCommand {
     Export(export::ExportCommand),
@@ -74,7 +75,11 @@ pub enum CommandSource {
     #[cfg(feature = "use_sqlite")]
     #[command(name = "sqlite", about = "sqlite")]
     Sqlite(export::SqliteSourceOptions),
-    #[command(name = "sources", about="manage data sources", rename_all = "verbatim")]
+    #[command(
+        name = "sources",
+        about = "manage data sources",
+        rename_all = "verbatim"
+    )]
     Sources(sources::SourcesCommand),
 }
 
@@ -17,6 +17,20 @@ lazy_static! {
         }
         hm
     };
+    /// content of ~/.dbfish/config.toml, empty table if file does not exist
+    pub static ref CONFIG: toml::Value = {
+        let filename = get_config_directory().join("config.toml");
+        if filename.exists() {
+            let content = std::fs::read_to_string(&filename)
+                .unwrap_or_else(|_| panic!("could not read config file: {:?}", &filename));
+            content.parse::<toml::Value>().unwrap_or_else(|e| {
+                eprintln!("could not parse config file {:?}: {}", &filename, e);
+                std::process::exit(1);
+            })
+        } else {
+            toml::Value::Table(toml::value::Table::new())
+        }
+    };
     pub static ref USER_DEFINED_SOURCES_NAMES: String = {
         let mut s = String::new();
         let mut sources: Vec<String> = USER_DEFINED_SOURCES.keys().cloned().collect();
@@ -68,7 +82,7 @@ pub fn get_sources_list() -> Vec<(String, SourceConfigCommand)> {
     } else {
         vec![]
     };
-    entries.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
+    entries.sort_by_key(|a| a.0.to_lowercase());
     entries
 }
 
@@ -25,7 +25,26 @@ pub enum ColumnType {
     Custom(String), //things database-specific, or outside of this list. We can't map all types
 }
 
-#[derive(Debug)]
+impl ColumnType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            ColumnType::U64
+                | ColumnType::I64
+                | ColumnType::U32
+                | ColumnType::I32
+                | ColumnType::U16
+                | ColumnType::I16
+                | ColumnType::U8
+                | ColumnType::I8
+                | ColumnType::F64
+                | ColumnType::F32
+                | ColumnType::Decimal
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum Value {
     U64(u64),
     I64(i64),
@@ -76,7 +95,13 @@ pub trait DataSourceConnection<'conn> {
     fn batch_iterator(
         &'conn mut self,
         batch_size: u64,
-    ) -> Box<(dyn DataSourceBatchIterator<'conn> + 'conn)>;
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn>;
+    /// like batch_iterator, but runs given query instead of the one from source options
+    fn query_batch_iterator(
+        &'conn mut self,
+        query: &str,
+        batch_size: u64,
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn>;
 }
 
 pub trait DataSource<'source, 'conn, C>
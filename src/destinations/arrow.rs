@@ -0,0 +1,683 @@
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+use arrow::array::temporal_conversions::time_to_time64ns;
+use arrow::array::types::{
+    ArrowPrimitiveType, Date32Type, Decimal128Type, Float32Type, Float64Type, Int16Type, Int32Type,
+    Int64Type, Int8Type, Time64NanosecondType, TimestampMicrosecondType, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+use arrow::array::{
+    ArrayRef, BinaryBuilder, BooleanBuilder, NullBuilder, PrimitiveBuilder, StringBuilder,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaBuilder, TimeUnit};
+use arrow::ipc::writer::{FileWriter, StreamWriter};
+use arrow::record_batch::RecordBatch;
+
+use crate::commands::export::{ArrowDestinationOptions, ArrowFormat};
+use crate::definitions::{
+    ColumnInfo, ColumnType, DataDestination, DataSourceBatchIterator, Row, Value,
+};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::{parse_decimal, truncate_text_with_note};
+
+/// how values are converted to arrow arrays. Shared by arrow and parquet destinations
+#[derive(Clone, Copy, Debug)]
+pub struct ArrowConversion {
+    /// destination name used in error messages
+    pub destination: &'static str,
+    pub truncate: Option<u64>,
+    pub decimal_precision: u8,
+    pub decimal_scale: i8,
+    /// convert values that don't match column type (ie. I64 value in U64 column)
+    pub coerce: bool,
+    /// exit on values that can't be converted without loss, instead of writing null
+    pub strict: bool,
+}
+
+impl ArrowConversion {
+    pub fn new(destination: &'static str, truncate: Option<u64>) -> ArrowConversion {
+        ArrowConversion {
+            destination,
+            truncate,
+            decimal_precision: 38,
+            decimal_scale: 10,
+            coerce: false,
+            strict: false,
+        }
+    }
+
+    /// exit if decimal precision and scale can't be used
+    pub fn validate(&self) {
+        if self.decimal_precision == 0
+            || self.decimal_precision > 38
+            || self.decimal_scale < 0
+            || self.decimal_scale as u8 > self.decimal_precision
+        {
+            eprintln!(
+                "{}: decimal precision must be within 1..38 and not smaller than scale",
+                self.destination
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// map dbfish column types to arrow schema. Shared by arrow and parquet destinations
+pub fn column_info_to_schema(columns: &[ColumnInfo], conversion: &ArrowConversion) -> Schema {
+    let mut schema_builder = SchemaBuilder::new();
+    for col in columns.iter() {
+        let data_type = match col.data_type {
+            ColumnType::U64 => DataType::UInt64,
+            ColumnType::I64 => DataType::Int64,
+            ColumnType::U32 => DataType::UInt32,
+            ColumnType::I32 => DataType::Int32,
+            ColumnType::U16 => DataType::UInt16,
+            ColumnType::I16 => DataType::Int16,
+            ColumnType::U8 => DataType::UInt8,
+            ColumnType::I8 => DataType::Int8,
+            //Utf8 is 32bit, LargeUtf8 64bit
+            ColumnType::String | ColumnType::JSON | ColumnType::Custom(_) => DataType::Utf8,
+            ColumnType::Bytes => DataType::Binary,
+            ColumnType::F64 => DataType::Float64,
+            ColumnType::F32 => DataType::Float32,
+            ColumnType::Bool => DataType::Boolean,
+            ColumnType::None => DataType::Null,
+            ColumnType::DateTime => DataType::Timestamp(TimeUnit::Microsecond, None),
+            ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            ColumnType::Date => DataType::Date32,
+            ColumnType::Time => DataType::Time64(TimeUnit::Nanosecond),
+            ColumnType::Decimal => {
+                DataType::Decimal128(conversion.decimal_precision, conversion.decimal_scale)
+            }
+        };
+        schema_builder.push(Field::new(col.name.clone(), data_type, true));
+    }
+    schema_builder.finish()
+}
+
+/// numeric value, used to convert between numeric types
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+fn value_as_number(value: &Value) -> Option<Number> {
+    match value {
+        Value::U64(v) => Some(Number::Int(i128::from(*v))),
+        Value::I64(v) => Some(Number::Int(i128::from(*v))),
+        Value::U32(v) => Some(Number::Int(i128::from(*v))),
+        Value::I32(v) => Some(Number::Int(i128::from(*v))),
+        Value::U16(v) => Some(Number::Int(i128::from(*v))),
+        Value::I16(v) => Some(Number::Int(i128::from(*v))),
+        Value::U8(v) => Some(Number::Int(i128::from(*v))),
+        Value::I8(v) => Some(Number::Int(i128::from(*v))),
+        Value::F64(v) => Some(Number::Float(*v)),
+        Value::F32(v) => Some(Number::Float(f64::from(*v))),
+        Value::String(s) => {
+            let s = s.trim();
+            s.parse()
+                .map(Number::Int)
+                .or_else(|_| s.parse().map(Number::Float))
+                .ok()
+        }
+        _ => None,
+    }
+}
+
+/// integer value, if it can be represented exactly
+fn value_as_int<T: TryFrom<i128>>(value: &Value) -> Option<T> {
+    let int = match value_as_number(value)? {
+        Number::Int(v) => v,
+        Number::Float(v) if v.fract() == 0.0 && v.abs() < 2f64.powi(100) => v as i128,
+        Number::Float(_) => return None,
+    };
+    T::try_from(int).ok()
+}
+
+/// float value, if it can be represented exactly
+fn value_as_f64(value: &Value) -> Option<f64> {
+    match value_as_number(value)? {
+        Number::Int(v) if v as f64 as i128 == v => Some(v as f64),
+        Number::Int(_) => None,
+        Number::Float(v) => Some(v),
+    }
+}
+
+fn value_as_f32(value: &Value) -> Option<f32> {
+    let v = value_as_f64(value)?;
+    if v as f32 as f64 == v || v.is_nan() {
+        Some(v as f32)
+    } else {
+        None
+    }
+}
+
+/// unscaled decimal value, if it fits in given precision without rounding
+fn value_as_decimal(value: &Value, precision: u8, scale: i8, coerce: bool) -> Option<i128> {
+    let scale = u32::try_from(scale).ok()?;
+    let unscaled = match value {
+        Value::String(s) | Value::Custom(s) => parse_decimal(s, scale)?,
+        _ if !coerce => return None,
+        Value::F64(_) | Value::F32(_) => parse_decimal(&value_as_f64(value)?.to_string(), scale)?,
+        _ => value_as_int::<i128>(value)?.checked_mul(10i128.checked_pow(scale)?)?,
+    };
+    if unscaled.unsigned_abs() < 10u128.pow(u32::from(precision)) {
+        Some(unscaled)
+    } else {
+        None
+    }
+}
+
+/// convert value with given function, applying coerce and strict settings.
+/// None means null should be written
+fn convert<T>(
+    value: &Value,
+    field: &Field,
+    conversion: &ArrowConversion,
+    exact: impl Fn(&Value) -> Option<T>,
+    coerced: impl Fn(&Value) -> Option<T>,
+) -> Option<T> {
+    if let Value::None = value {
+        return None;
+    }
+    let result = exact(value).or_else(|| {
+        if conversion.coerce {
+            coerced(value)
+        } else {
+            None
+        }
+    });
+    if result.is_none() && conversion.strict {
+        eprintln!(
+            "{}: can't convert value {:?} of column {} to {}",
+            conversion.destination,
+            value,
+            field.name(),
+            field.data_type()
+        );
+        std::process::exit(1);
+    }
+    result
+}
+
+fn primitive_array<T: ArrowPrimitiveType>(
+    rows: &[Row],
+    col_idx: usize,
+    field: &Field,
+    conversion: &ArrowConversion,
+    exact: impl Fn(&Value) -> Option<T::Native>,
+    coerced: impl Fn(&Value) -> Option<T::Native>,
+) -> ArrayRef {
+    //data type carries decimal precision and timestamp timezone
+    let mut array =
+        PrimitiveBuilder::<T>::with_capacity(rows.len()).with_data_type(field.data_type().clone());
+    for row in rows {
+        array.append_option(convert(&row[col_idx], field, conversion, &exact, &coerced));
+    }
+    Arc::new(array.finish())
+}
+
+/// text representation of numeric values, used when coercing them into text columns
+fn number_to_string(value: &Value) -> Option<String> {
+    match value_as_number(value)? {
+        _ if matches!(value, Value::String(_)) => None,
+        Number::Int(v) => Some(v.to_string()),
+        Number::Float(v) => Some(v.to_string()),
+    }
+}
+
+/// build one arrow array per schema field out of given rows
+pub fn rows_to_arrays(
+    schema: &Schema,
+    rows: &[Row],
+    conversion: &ArrowConversion,
+) -> Vec<ArrayRef> {
+    let mut arrays: Vec<ArrayRef> = vec![];
+
+    for (col_idx, field) in schema.fields.iter().enumerate() {
+        let array: ArrayRef = match field.data_type() {
+            DataType::UInt64 => primitive_array::<UInt64Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::U64(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::Int64 => primitive_array::<Int64Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::I64(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::UInt32 => primitive_array::<UInt32Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::U32(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::Int32 => primitive_array::<Int32Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::I32(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::UInt16 => primitive_array::<UInt16Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::U16(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::Int16 => primitive_array::<Int16Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::I16(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::UInt8 => primitive_array::<UInt8Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::U8(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::Int8 => primitive_array::<Int8Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::I8(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_int,
+            ),
+            DataType::Float64 => primitive_array::<Float64Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::F64(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_f64,
+            ),
+            DataType::Float32 => primitive_array::<Float32Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::F32(v) => Some(*v),
+                    _ => None,
+                },
+                value_as_f32,
+            ),
+            DataType::Decimal128(precision, scale) => primitive_array::<Decimal128Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| value_as_decimal(v, *precision, *scale, false),
+                |v| value_as_decimal(v, *precision, *scale, true),
+            ),
+            DataType::Timestamp(_unit, _timezone) => primitive_array::<TimestampMicrosecondType>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::Timestamp(t) => i64::try_from(*t).ok()?.checked_mul(1_000_000),
+                    Value::DateTime(dt) => Some(dt.and_utc().timestamp_micros()),
+                    _ => None,
+                },
+                |_| None,
+            ),
+            DataType::Date32 => primitive_array::<Date32Type>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::Date(d) => Some(Date32Type::from_naive_date(*d)),
+                    _ => None,
+                },
+                |_| None,
+            ),
+            DataType::Time64(_unit) => primitive_array::<Time64NanosecondType>(
+                rows,
+                col_idx,
+                field,
+                conversion,
+                |v| match v {
+                    Value::Time(t) => Some(time_to_time64ns(*t)),
+                    _ => None,
+                },
+                |_| None,
+            ),
+            DataType::Boolean => {
+                let mut array = BooleanBuilder::with_capacity(rows.len());
+                for row in rows {
+                    array.append_option(convert(
+                        &row[col_idx],
+                        field,
+                        conversion,
+                        |v| match v {
+                            Value::Bool(v) => Some(*v),
+                            _ => None,
+                        },
+                        |v| match value_as_int::<u8>(v)? {
+                            0 => Some(false),
+                            1 => Some(true),
+                            _ => None,
+                        },
+                    ));
+                }
+                Arc::new(array.finish())
+            }
+            DataType::Utf8 => {
+                let mut array = StringBuilder::new();
+                for row in rows {
+                    array.append_option(convert(
+                        &row[col_idx],
+                        field,
+                        conversion,
+                        |v| match v {
+                            Value::String(s) | Value::Custom(s) => {
+                                Some(truncate_text_with_note(s.clone(), conversion.truncate))
+                            }
+                            //truncated json would not be valid anymore
+                            Value::JSON(s) => Some(s.clone()),
+                            _ => None,
+                        },
+                        number_to_string,
+                    ));
+                }
+                Arc::new(array.finish())
+            }
+            DataType::Binary => {
+                let mut array = BinaryBuilder::new();
+                for row in rows {
+                    array.append_option(convert(
+                        &row[col_idx],
+                        field,
+                        conversion,
+                        |v| match v {
+                            Value::Bytes(b) => Some(b.clone()),
+                            _ => None,
+                        },
+                        |v| match v {
+                            Value::String(s) => Some(s.clone().into_bytes()),
+                            _ => number_to_string(v).map(String::into_bytes),
+                        },
+                    ));
+                }
+                Arc::new(array.finish())
+            }
+            DataType::Null => {
+                let mut array = NullBuilder::new();
+                for row in rows {
+                    let _: Option<()> =
+                        convert(&row[col_idx], field, conversion, |_| None, |_| None);
+                    array.append_null();
+                }
+                Arc::new(array.finish())
+            }
+            _ => panic!("arrow: unsupported data type {}", field.data_type()),
+        };
+        arrays.push(array);
+    }
+    arrays
+}
+
+pub enum ArrowWriter {
+    File(FileWriter<BufWriter<FileOrStdout>>),
+    Stream(StreamWriter<BufWriter<FileOrStdout>>),
+}
+
+pub struct ArrowDestination {
+    conversion: ArrowConversion,
+    filename: String,
+    format: ArrowFormat,
+    writer: Option<ArrowWriter>,
+    schema: Option<Arc<Schema>>,
+}
+
+impl ArrowDestination {
+    pub fn init(arrow_options: &ArrowDestinationOptions) -> ArrowDestination {
+        let format = match arrow_options.format {
+            Some(format) => format,
+            None if arrow_options.filename == "-" => ArrowFormat::Stream,
+            None => ArrowFormat::File,
+        };
+        let conversion = ArrowConversion {
+            decimal_precision: arrow_options.decimal_precision,
+            decimal_scale: arrow_options.decimal_scale,
+            coerce: arrow_options.coerce,
+            strict: arrow_options.strict,
+            ..ArrowConversion::new("arrow", arrow_options.truncate)
+        };
+        conversion.validate();
+        ArrowDestination {
+            filename: arrow_options.filename.clone(),
+            format,
+            conversion,
+            schema: None,
+            writer: None,
+        }
+    }
+}
+
+impl DataDestination for ArrowDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let schema = Arc::new(column_info_to_schema(
+            &result_iterator.get_column_info(),
+            &self.conversion,
+        ));
+        self.schema = Some(schema.clone());
+        let output = match self.filename.as_str() {
+            "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                termcolor::ColorChoice::Never,
+            )),
+            _ => FileOrStdout::File(std::fs::File::create(&self.filename).unwrap()),
+        };
+        self.writer = Some(match self.format {
+            ArrowFormat::File => {
+                ArrowWriter::File(FileWriter::try_new_buffered(output, &schema).unwrap())
+            }
+            ArrowFormat::Stream => {
+                ArrowWriter::Stream(StreamWriter::try_new_buffered(output, &schema).unwrap())
+            }
+        });
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let schema = self.schema.as_ref().unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            rows_to_arrays(schema, rows, &self.conversion),
+        )
+        .unwrap();
+        match self.writer.as_mut().unwrap() {
+            ArrowWriter::File(writer) => writer.write(&batch).unwrap(),
+            ArrowWriter::Stream(writer) => writer.write(&batch).unwrap(),
+        }
+    }
+
+    fn close(&mut self) {
+        match self.writer.as_mut().unwrap() {
+            ArrowWriter::File(writer) => {
+                writer.finish().unwrap();
+                writer.get_mut().flush().unwrap();
+            }
+            ArrowWriter::Stream(writer) => {
+                writer.finish().unwrap();
+                writer.get_mut().flush().unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{child_stdout, exits_with, export, temp_path};
+    use arrow::array::{Array, Decimal128Array, Int64Array, StringArray, UInt64Array};
+    use arrow::ipc::reader::{FileReader, StreamReader};
+    use clap::Parser;
+
+    const COLUMNS: [(&str, ColumnType); 4] = [
+        ("id", ColumnType::I64),
+        ("name", ColumnType::String),
+        ("amount", ColumnType::Decimal),
+        ("count", ColumnType::U64),
+    ];
+
+    fn export_arrow(filename: &str, args: &[&str]) {
+        let options = ArrowDestinationOptions::try_parse_from(
+            [
+                "arrow",
+                filename,
+                "--decimal-precision",
+                "10",
+                "--decimal-scale",
+                "2",
+            ]
+            .iter()
+            .chain(args),
+        )
+        .unwrap();
+        export(
+            &mut ArrowDestination::init(&options),
+            &COLUMNS,
+            vec![
+                vec![vec![
+                    Value::I64(1),
+                    Value::String("a".to_string()),
+                    Value::String("-1.5".to_string()),
+                    Value::U64(3),
+                ]],
+                vec![vec![
+                    Value::I64(2),
+                    Value::None,
+                    Value::String("0.125".to_string()),
+                    Value::I64(4),
+                ]],
+            ],
+        );
+    }
+
+    fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> &'a T {
+        batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<T>()
+            .unwrap()
+    }
+
+    /// both batches read back, checking values which don't need conversion
+    fn check_batches(batches: Vec<RecordBatch>) {
+        assert_eq!(batches.len(), 2);
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch
+                .schema()
+                .field_with_name("amount")
+                .unwrap()
+                .data_type(),
+            &DataType::Decimal128(10, 2)
+        );
+        assert_eq!(column::<Int64Array>(&batch, "id").values(), &[1, 2]);
+        let names = column::<StringArray>(&batch, "name");
+        assert_eq!(names.value(0), "a");
+        assert!(names.is_null(1));
+        assert_eq!(column::<Decimal128Array>(&batch, "amount").value(0), -150);
+        let counts = column::<UInt64Array>(&batch, "count");
+        assert_eq!(counts.value(0), 3);
+    }
+
+    #[test]
+    fn test_file() {
+        let filename = temp_path("export.arrow");
+        export_arrow(&filename, &[]);
+        let reader = FileReader::try_new(std::fs::File::open(&filename).unwrap(), None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        //decimal with too many fractional digits and I64 in U64 column are written as null
+        assert!(column::<Decimal128Array>(&batches[1], "amount").is_null(0));
+        assert!(column::<UInt64Array>(&batches[1], "count").is_null(0));
+        check_batches(batches);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_stream() {
+        let filename = temp_path("export.arrows");
+        export_arrow(&filename, &["--format", "stream", "--coerce"]);
+        let reader = StreamReader::try_new(std::fs::File::open(&filename).unwrap(), None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(column::<UInt64Array>(&batches[1], "count").value(0), 4);
+        check_batches(batches);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_stdout() {
+        //stdout gets stream format by default
+        let stdout = child_stdout(module_path!(), "test_stdout", || {
+            export_arrow("-", &["--coerce"]);
+        });
+        let reader = StreamReader::try_new(stdout.as_slice(), None).unwrap();
+        check_batches(reader.collect::<Result<Vec<_>, _>>().unwrap());
+    }
+
+    #[test]
+    fn test_strict() {
+        let stderr = exits_with(module_path!(), "test_strict", || {
+            export_arrow(&temp_path("strict.arrow"), &["--coerce", "--strict"]);
+        });
+        assert!(
+            stderr.contains(
+                "arrow: can't convert value String(\"0.125\") of column amount to Decimal128(10, 2)"
+            ),
+            "{}",
+            stderr
+        );
+    }
+}
@@ -0,0 +1,570 @@
+use std::io::BufWriter;
+
+use apache_avro::types::Value as AvroValue;
+use apache_avro::{Codec, Decimal, Schema};
+use chrono::Timelike;
+
+use crate::commands::export::{AvroCodec, AvroDestinationOptions};
+use crate::definitions::{
+    ColumnInfo, ColumnType, DataDestination, DataSourceBatchIterator, Row, Value,
+};
+use crate::transform::schema::{value_as_f64, value_as_i128};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::{escape_binary_data, parse_decimal, truncate_text_with_note, unique_names};
+
+/// avro type used for a column, derived from its ColumnType
+#[derive(Clone, Copy, Debug)]
+pub enum AvroFieldType {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Date,
+    TimeMicros,
+    TimestampMicros,
+    LocalTimestampMicros,
+    Decimal,
+}
+
+impl AvroFieldType {
+    pub fn from_column_type(column_type: &ColumnType) -> AvroFieldType {
+        match column_type {
+            ColumnType::U64 | ColumnType::I64 | ColumnType::U32 => AvroFieldType::Long,
+            ColumnType::I32
+            | ColumnType::U16
+            | ColumnType::I16
+            | ColumnType::U8
+            | ColumnType::I8 => AvroFieldType::Int,
+            ColumnType::F64 => AvroFieldType::Double,
+            ColumnType::F32 => AvroFieldType::Float,
+            ColumnType::String | ColumnType::JSON | ColumnType::Custom(_) => AvroFieldType::String,
+            ColumnType::Bytes => AvroFieldType::Bytes,
+            ColumnType::Bool => AvroFieldType::Boolean,
+            ColumnType::None => AvroFieldType::Null,
+            ColumnType::Date => AvroFieldType::Date,
+            ColumnType::Time => AvroFieldType::TimeMicros,
+            ColumnType::DateTime => AvroFieldType::LocalTimestampMicros,
+            ColumnType::Timestamp => AvroFieldType::TimestampMicros,
+            ColumnType::Decimal => AvroFieldType::Decimal,
+        }
+    }
+
+    pub fn to_schema_json(self, precision: u32, scale: u32) -> String {
+        match self {
+            AvroFieldType::Null => "\"null\"".to_string(),
+            AvroFieldType::Boolean => "\"boolean\"".to_string(),
+            AvroFieldType::Int => "\"int\"".to_string(),
+            AvroFieldType::Long => "\"long\"".to_string(),
+            AvroFieldType::Float => "\"float\"".to_string(),
+            AvroFieldType::Double => "\"double\"".to_string(),
+            AvroFieldType::Bytes => "\"bytes\"".to_string(),
+            AvroFieldType::String => "\"string\"".to_string(),
+            AvroFieldType::Date => "{\"type\": \"int\", \"logicalType\": \"date\"}".to_string(),
+            AvroFieldType::TimeMicros => {
+                "{\"type\": \"long\", \"logicalType\": \"time-micros\"}".to_string()
+            }
+            AvroFieldType::TimestampMicros => {
+                "{\"type\": \"long\", \"logicalType\": \"timestamp-micros\"}".to_string()
+            }
+            AvroFieldType::LocalTimestampMicros => {
+                "{\"type\": \"long\", \"logicalType\": \"local-timestamp-micros\"}".to_string()
+            }
+            AvroFieldType::Decimal => format!(
+                "{{\"type\": \"bytes\", \"logicalType\": \"decimal\", \"precision\": {}, \"scale\": {}}}",
+                precision, scale
+            ),
+        }
+    }
+}
+
+/// avro names must match [A-Za-z_][A-Za-z0-9_]*
+pub fn avro_field_name(name: &str) -> String {
+    let mut result: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if result.is_empty() || result.starts_with(|c: char| c.is_ascii_digit()) {
+        result.insert(0, '_');
+    }
+    result
+}
+
+/// unique avro field names for given columns
+pub fn avro_field_names(columns: &[ColumnInfo]) -> Vec<String> {
+    unique_names(
+        columns
+            .iter()
+            .map(|column| avro_field_name(&column.name))
+            .collect(),
+    )
+}
+
+pub fn avro_schema_json(
+    schema_name: &str,
+    columns: &[ColumnInfo],
+    precision: u32,
+    scale: u32,
+) -> String {
+    let fields = columns
+        .iter()
+        .zip(avro_field_names(columns))
+        .map(|(column, name)| {
+            let field_type = AvroFieldType::from_column_type(&column.data_type);
+            let type_json = match field_type {
+                AvroFieldType::Null => field_type.to_schema_json(precision, scale),
+                _ => format!(
+                    "[\"null\", {}]",
+                    field_type.to_schema_json(precision, scale)
+                ),
+            };
+            format!(
+                "    {{\"name\": \"{}\", \"type\": {}, \"default\": null}}",
+                name, type_json
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",\n");
+    format!(
+        "{{\n  \"type\": \"record\",\n  \"name\": \"{}\",\n  \"fields\": [\n{}\n  ]\n}}\n",
+        schema_name, fields
+    )
+}
+
+fn decimal_value(unscaled: i128) -> AvroValue {
+    //minimal two's complement big endian representation
+    let bytes = unscaled.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() - 1
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    AvroValue::Decimal(Decimal::from(&bytes[start..]))
+}
+
+/// unscaled decimal value, if it fits in given precision. Floats are rounded to scale
+/// only when asked to, otherwise ones with more fractional digits can't be converted
+fn value_as_decimal(value: &Value, precision: u32, scale: u32, round: bool) -> Option<i128> {
+    let unscaled = match value {
+        Value::String(s) | Value::Custom(s) => parse_decimal(s, scale)?,
+        Value::F64(v) if !round => parse_decimal(&v.to_string(), scale)?,
+        Value::F32(v) if !round => parse_decimal(&v.to_string(), scale)?,
+        Value::F64(_) | Value::F32(_) => parse_decimal(
+            &format!("{:.*}", scale as usize, value_as_f64(value)?),
+            scale,
+        )?,
+        _ => value_as_i128(value)?.checked_mul(10i128.checked_pow(scale)?)?,
+    };
+    if unscaled.unsigned_abs() < 10u128.pow(precision) {
+        Some(unscaled)
+    } else {
+        None
+    }
+}
+
+/// how values are converted, taken from destination options
+#[derive(Clone, Copy, Debug)]
+pub struct AvroConversion {
+    pub precision: u32,
+    pub scale: u32,
+    pub round: bool,
+    pub truncate: Option<u64>,
+}
+
+pub fn value_to_avro(
+    value: &Value,
+    field_type: AvroFieldType,
+    conversion: &AvroConversion,
+) -> Option<AvroValue> {
+    let avro_value = match (field_type, value) {
+        (_, Value::None) => AvroValue::Null,
+        (AvroFieldType::Null, _) => return None,
+        (AvroFieldType::Boolean, Value::Bool(v)) => AvroValue::Boolean(*v),
+        (AvroFieldType::Boolean, _) => AvroValue::Boolean(value_as_i128(value)? != 0),
+        (AvroFieldType::Int, _) => AvroValue::Int(i32::try_from(value_as_i128(value)?).ok()?),
+        (AvroFieldType::Long, _) => AvroValue::Long(i64::try_from(value_as_i128(value)?).ok()?),
+        (AvroFieldType::Float, _) => AvroValue::Float(value_as_f64(value)? as f32),
+        (AvroFieldType::Double, _) => AvroValue::Double(value_as_f64(value)?),
+        (AvroFieldType::Bytes, Value::Bytes(v)) => AvroValue::Bytes(v.clone()),
+        (AvroFieldType::Bytes, Value::String(v)) => AvroValue::Bytes(v.as_bytes().to_vec()),
+        //sqlite reports every column as binary, store other values as their text representation
+        (AvroFieldType::Bytes, Value::F64(v)) => AvroValue::Bytes(v.to_string().into_bytes()),
+        (AvroFieldType::Bytes, Value::F32(v)) => AvroValue::Bytes(v.to_string().into_bytes()),
+        (AvroFieldType::Bytes, _) => {
+            AvroValue::Bytes(value_as_i128(value)?.to_string().into_bytes())
+        }
+        (AvroFieldType::String, Value::String(v)) | (AvroFieldType::String, Value::Custom(v)) => {
+            AvroValue::String(truncate_text_with_note(v.to_string(), conversion.truncate))
+        }
+        (AvroFieldType::String, Value::JSON(v)) => AvroValue::String(v.clone()),
+        (AvroFieldType::String, Value::Bytes(v)) => AvroValue::String(escape_binary_data(v)),
+        (AvroFieldType::Date, Value::Date(d)) => AvroValue::Date(
+            d.signed_duration_since(chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap())
+                .num_days() as i32,
+        ),
+        (AvroFieldType::TimeMicros, Value::Time(t)) => AvroValue::TimeMicros(
+            i64::from(t.num_seconds_from_midnight()) * 1_000_000
+                + i64::from(t.nanosecond() / 1_000),
+        ),
+        (AvroFieldType::LocalTimestampMicros, Value::DateTime(dt)) => {
+            AvroValue::LocalTimestampMicros(dt.and_utc().timestamp_micros())
+        }
+        (AvroFieldType::TimestampMicros, Value::Timestamp(t)) => {
+            AvroValue::TimestampMicros(i64::try_from(*t).ok()?.checked_mul(1_000_000)?)
+        }
+        (AvroFieldType::TimestampMicros, Value::DateTime(dt)) => {
+            AvroValue::TimestampMicros(dt.and_utc().timestamp_micros())
+        }
+        (AvroFieldType::Decimal, _) => decimal_value(value_as_decimal(
+            value,
+            conversion.precision,
+            conversion.scale,
+            conversion.round,
+        )?),
+        _ => return None,
+    };
+    Some(match (field_type, avro_value) {
+        (AvroFieldType::Null, v) => v,
+        (_, AvroValue::Null) => AvroValue::Union(0, Box::new(AvroValue::Null)),
+        (_, v) => AvroValue::Union(1, Box::new(v)),
+    })
+}
+
+/// avro writer together with schema it borrows
+#[ouroboros::self_referencing]
+struct AvroWriter {
+    schema: Schema,
+    #[borrows(schema)]
+    #[covariant]
+    writer: apache_avro::Writer<'this, BufWriter<FileOrStdout>>,
+}
+
+pub struct AvroDestination {
+    filename: String,
+    schema_name: String,
+    codec: Codec,
+    conversion: AvroConversion,
+    avsc_filename: Option<String>,
+    //original column name, avro field name and type
+    columns: Vec<(String, String, AvroFieldType)>,
+    writer: Option<AvroWriter>,
+}
+
+impl AvroDestination {
+    pub fn init(avro_options: &AvroDestinationOptions) -> AvroDestination {
+        let codec = match avro_options.codec {
+            AvroCodec::Null => Codec::Null,
+            AvroCodec::Deflate => Codec::Deflate(Default::default()),
+            AvroCodec::Snappy => Codec::Snappy,
+            AvroCodec::Zstd => Codec::Zstandard(Default::default()),
+        };
+        if avro_options.decimal_precision == 0
+            || avro_options.decimal_precision > 38
+            || avro_options.decimal_scale > avro_options.decimal_precision
+        {
+            eprintln!("avro: decimal precision must be within 1..38 and not smaller than scale");
+            std::process::exit(1);
+        }
+        let avsc_filename = match &avro_options.avsc {
+            None => None,
+            Some(Some(filename)) => Some(filename.clone()),
+            Some(None) if avro_options.filename == "-" => {
+                eprintln!("avro: provide schema filename for --avsc when writing to stdout");
+                std::process::exit(1);
+            }
+            Some(None) => Some(
+                std::path::Path::new(&avro_options.filename)
+                    .with_extension("avsc")
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+        };
+        AvroDestination {
+            filename: avro_options.filename.clone(),
+            schema_name: avro_options.schema_name.clone(),
+            codec,
+            conversion: AvroConversion {
+                precision: avro_options.decimal_precision,
+                scale: avro_options.decimal_scale,
+                round: avro_options.round,
+                truncate: avro_options.truncate,
+            },
+            avsc_filename,
+            columns: vec![],
+            writer: None,
+        }
+    }
+}
+
+impl DataDestination for AvroDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
+        let schema_json = avro_schema_json(
+            &self.schema_name,
+            &column_info,
+            self.conversion.precision,
+            self.conversion.scale,
+        );
+        let schema = match Schema::parse_str(&schema_json) {
+            Ok(schema) => schema,
+            Err(e) => {
+                eprintln!("avro: invalid schema: {}\n\n{}", e, schema_json);
+                std::process::exit(1);
+            }
+        };
+        if let Some(avsc_filename) = &self.avsc_filename {
+            std::fs::write(avsc_filename, &schema_json).unwrap();
+        }
+        self.columns = column_info
+            .iter()
+            .zip(avro_field_names(&column_info))
+            .map(|(column, field_name)| {
+                (
+                    column.name.clone(),
+                    field_name,
+                    AvroFieldType::from_column_type(&column.data_type),
+                )
+            })
+            .collect();
+        let output = match self.filename.as_str() {
+            "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                termcolor::ColorChoice::Never,
+            )),
+            _ => FileOrStdout::File(std::fs::File::create(&self.filename).unwrap()),
+        };
+        let codec = self.codec;
+        self.writer = Some(AvroWriter::new(schema, |schema| {
+            apache_avro::Writer::with_codec(schema, BufWriter::new(output), codec).unwrap()
+        }));
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let writer = self.writer.as_mut().unwrap();
+        let conversion = &self.conversion;
+        for row in rows {
+            let record = row
+                .iter()
+                .zip(self.columns.iter())
+                .map(|(value, (name, field_name, field_type))| {
+                    match value_to_avro(value, *field_type, conversion) {
+                        Some(avro_value) => (field_name.clone(), avro_value),
+                        None => {
+                            eprintln!(
+                                "avro: can't convert value {:?} of column {} to {:?}",
+                                value, name, field_type
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                })
+                .collect::<Vec<(String, AvroValue)>>();
+            writer
+                .with_writer_mut(|writer| writer.append_value(AvroValue::Record(record)))
+                .unwrap();
+        }
+    }
+
+    fn close(&mut self) {
+        //flushes underlying writer too
+        self.writer
+            .take()
+            .unwrap()
+            .with_writer_mut(|writer| writer.flush())
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{exits_with, export, temp_path};
+    use clap::Parser;
+
+    const COLUMNS: [(&str, ColumnType); 6] = [
+        ("id", ColumnType::I64),
+        ("1 col", ColumnType::String),
+        ("1_col", ColumnType::String),
+        ("amount", ColumnType::Decimal),
+        ("day", ColumnType::Date),
+        ("nothing", ColumnType::None),
+    ];
+
+    fn destination(filename: &str) -> AvroDestination {
+        AvroDestination::init(
+            &AvroDestinationOptions::try_parse_from([
+                "avro",
+                filename,
+                "--decimal-precision",
+                "10",
+                "--decimal-scale",
+                "2",
+                "--avsc",
+            ])
+            .unwrap(),
+        )
+    }
+
+    fn some(value: AvroValue) -> AvroValue {
+        AvroValue::Union(1, Box::new(value))
+    }
+
+    fn null() -> AvroValue {
+        AvroValue::Union(0, Box::new(AvroValue::Null))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let filename = temp_path("export.avro");
+        export(
+            &mut destination(&filename),
+            &COLUMNS,
+            vec![
+                vec![vec![
+                    Value::I64(1),
+                    Value::String("a".to_string()),
+                    Value::Bytes(vec![0, b'b']),
+                    Value::String("-1.5".to_string()),
+                    Value::Date(chrono::NaiveDate::from_ymd_opt(1970, 1, 11).unwrap()),
+                    Value::None,
+                ]],
+                //values of other types are coerced
+                vec![vec![
+                    Value::String("7".to_string()),
+                    Value::None,
+                    Value::None,
+                    Value::F64(0.25),
+                    Value::None,
+                    Value::None,
+                ]],
+            ],
+        );
+        let avsc_filename = std::path::Path::new(&filename).with_extension("avsc");
+        let schema = Schema::parse_str(&std::fs::read_to_string(&avsc_filename).unwrap()).unwrap();
+        let reader = apache_avro::Reader::new(std::fs::File::open(&filename).unwrap()).unwrap();
+        assert_eq!(reader.writer_schema(), &schema);
+        let records = reader
+            .map(|record| match record.unwrap() {
+                AvroValue::Record(fields) => fields,
+                other => panic!("expected record, got {:?}", other),
+            })
+            .collect::<Vec<Vec<(String, AvroValue)>>>();
+        let names: Vec<&str> = records[0].iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["id", "_1_col", "_1_col_2", "amount", "day", "nothing"]
+        );
+        let values = |idx: usize| -> Vec<AvroValue> {
+            records[idx]
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect()
+        };
+        assert_eq!(
+            values(0),
+            [
+                some(AvroValue::Long(1)),
+                some(AvroValue::String("a".to_string())),
+                some(AvroValue::String("\\x00b".to_string())),
+                some(AvroValue::Decimal(vec![0xff, 0x6a].into())),
+                some(AvroValue::Date(10)),
+                AvroValue::Null,
+            ]
+        );
+        assert_eq!(
+            values(1),
+            [
+                some(AvroValue::Long(7)),
+                null(),
+                null(),
+                some(AvroValue::Decimal(vec![25].into())),
+                null(),
+                AvroValue::Null,
+            ]
+        );
+        std::fs::remove_file(&filename).unwrap();
+        std::fs::remove_file(&avsc_filename).unwrap();
+    }
+
+    #[test]
+    fn test_decimals() {
+        let conversion = AvroConversion {
+            precision: 4,
+            scale: 2,
+            round: false,
+            truncate: None,
+        };
+        let decimal = |value: Value, conversion: &AvroConversion| {
+            value_to_avro(&value, AvroFieldType::Decimal, conversion)
+        };
+        assert_eq!(
+            decimal(Value::String("99.99".to_string()), &conversion),
+            Some(some(decimal_value(9999)))
+        );
+        assert_eq!(
+            decimal(Value::I64(-12), &conversion),
+            Some(some(decimal_value(-1200)))
+        );
+        assert_eq!(
+            decimal(Value::F32(0.1), &conversion),
+            Some(some(decimal_value(10)))
+        );
+        //wider than precision
+        assert_eq!(decimal(Value::String("100".to_string()), &conversion), None);
+        assert_eq!(decimal(Value::I64(100), &conversion), None);
+        //floats are rounded only when asked to
+        assert_eq!(decimal(Value::F64(0.125), &conversion), None);
+        assert_eq!(decimal(Value::F64(f64::NAN), &conversion), None);
+        let rounding = AvroConversion {
+            round: true,
+            ..conversion
+        };
+        assert_eq!(
+            decimal(Value::F64(0.126), &rounding),
+            Some(some(decimal_value(13)))
+        );
+        assert_eq!(decimal(Value::F64(99.999), &rounding), None);
+    }
+
+    #[test]
+    fn test_conversion_failure() {
+        let conversion = AvroConversion {
+            precision: 10,
+            scale: 2,
+            round: false,
+            truncate: None,
+        };
+        assert_eq!(
+            value_to_avro(
+                &Value::String("x".to_string()),
+                AvroFieldType::Long,
+                &conversion
+            ),
+            None
+        );
+        //too large for int
+        assert_eq!(
+            value_to_avro(&Value::I64(1 << 40), AvroFieldType::Int, &conversion),
+            None
+        );
+        let stderr = exits_with(module_path!(), "test_conversion_failure", || {
+            let filename = temp_path("invalid.avro");
+            export(
+                &mut destination(&filename),
+                &COLUMNS[..1],
+                vec![vec![vec![Value::String("x".to_string())]]],
+            );
+        });
+        assert!(
+            stderr.contains("avro: can't convert value String(\"x\") of column id to Long"),
+            "{}",
+            stderr
+        );
+    }
+}
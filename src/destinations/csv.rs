@@ -1,82 +1,312 @@
-use csv;
+use std::io::{BufWriter, Write};
+
+use csv_core::{QuoteStyle, Terminator, WriterBuilder};
 use termcolor;
 
-use crate::commands::export::CSVDestinationOptions;
-use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::commands::export::{CSVDestinationOptions, CSVEscapeStyle, CSVQuoteStyle};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row};
 use crate::utils::fileorstdout::FileOrStdout;
-use crate::utils::{escape_binary_data, truncate_text_with_note};
+use crate::utils::formatter::ValueFormatter;
 
 pub struct CSVDestination {
-    csv_writer: csv::Writer<FileOrStdout>,
+    writer: BufWriter<FileOrStdout>,
+    //quotes and escapes fields, NULL markers are written around it
+    csv_writer: csv_core::Writer,
+    //current record, written out once complete
+    record: Vec<u8>,
     truncate: Option<u64>,
     no_headers: bool,
+    formatter: ValueFormatter,
+    null: String,
+    column_types: Vec<ColumnType>,
+    //empty texts are quoted to tell them apart from NULL written as empty field
+    quote_empty: bool,
+}
+
+/// single byte character for csv option. Accepts "\t" and "tab" for tabulator
+pub fn csv_option_char(option_name: &str, value: &str) -> u8 {
+    match value {
+        "\\t" | "tab" => b'\t',
+        _ if value.len() == 1 => value.as_bytes()[0],
+        _ => {
+            eprintln!(
+                "csv: {} must be a single ascii character, got {:?}",
+                option_name, value
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// append output of csv writer call, which writes at most max_len bytes
+fn append(record: &mut Vec<u8>, max_len: usize, write: impl FnOnce(&mut [u8]) -> usize) {
+    let start = record.len();
+    record.resize(start + max_len, 0);
+    let written = write(&mut record[start..]);
+    record.truncate(start + written);
 }
 
 impl CSVDestination {
-    pub fn init(csv_options: &CSVDestinationOptions) -> CSVDestination {
-        let csv_writer = csv::Writer::from_writer(match csv_options.filename.as_str() {
+    pub fn init(csv_options: &CSVDestinationOptions, formatter: &ValueFormatter) -> CSVDestination {
+        let mut writer = BufWriter::new(match csv_options.filename.as_str() {
             "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
                 termcolor::ColorChoice::Never,
             )),
             _ => FileOrStdout::File(std::fs::File::create(&csv_options.filename).unwrap()),
         });
+        if csv_options.bom {
+            writer.write_all(b"\xEF\xBB\xBF").unwrap();
+        }
+        let delimiter = if csv_options.tsv {
+            b'\t'
+        } else {
+            csv_option_char("delimiter", &csv_options.delimiter)
+        };
+        let quote = csv_option_char("quote", &csv_options.quote);
+        let mut builder = WriterBuilder::new();
+        builder
+            .delimiter(delimiter)
+            .quote(quote)
+            .quote_style(match csv_options.quote_style {
+                CSVQuoteStyle::Always => QuoteStyle::Always,
+                CSVQuoteStyle::Necessary => QuoteStyle::Necessary,
+                CSVQuoteStyle::NonNumeric => QuoteStyle::NonNumeric,
+                CSVQuoteStyle::Never => QuoteStyle::Never,
+            })
+            .terminator(if csv_options.crlf {
+                Terminator::CRLF
+            } else {
+                Terminator::Any(b'\n')
+            });
+        match csv_options.escape_style {
+            CSVEscapeStyle::Double => builder.double_quote(true),
+            CSVEscapeStyle::Backslash => builder.double_quote(false).escape(b'\\'),
+        };
+        //destination option wins over export one
+        let null = csv_options
+            .null
+            .clone()
+            .unwrap_or_else(|| formatter.null("").to_string());
         CSVDestination {
-            csv_writer,
+            writer,
+            csv_writer: builder.build(),
+            record: vec![],
             truncate: csv_options.truncate,
             no_headers: csv_options.no_headers,
+            formatter: formatter.clone(),
+            column_types: vec![],
+            quote_empty: null.is_empty() && csv_options.quote_style != CSVQuoteStyle::Never,
+            null,
         }
     }
 
-    pub fn row_to_csv_row(row: &Row, truncate: Option<u64>) -> Vec<String> {
+    pub fn row_to_csv_row(
+        row: &Row,
+        column_types: &[ColumnType],
+        truncate: Option<u64>,
+        formatter: &ValueFormatter,
+    ) -> Vec<Option<String>> {
         row.iter()
-            .map(|v| match v {
-                Value::U64(value) => value.to_string(),
-                Value::I64(value) => value.to_string(),
-                Value::U32(value) => value.to_string(),
-                Value::I32(value) => value.to_string(),
-                Value::U16(value) => value.to_string(),
-                Value::I16(value) => value.to_string(),
-                Value::U8(value) => value.to_string(),
-                Value::I8(value) => value.to_string(),
-                Value::F64(value) => value.to_string(),
-                Value::F32(value) => value.to_string(),
-                Value::String(value) => truncate_text_with_note(value.to_string(), truncate),
-                Value::Bool(value) => value.to_string(),
-                Value::Bytes(value) => escape_binary_data(value),
-                Value::None => "".to_string(),
-                Value::Timestamp(value) => value.to_string(),
-                Value::Date(date) => format!("{}", date.format("%Y-%m-%d")),
-                Value::Time(time) => format!("{}", time.format("%H:%M:%S")),
-                Value::DateTime(datetime) => format!("{}", datetime.format("%Y-%m-%d %H:%M:%S")),
-                _ => panic!("csv: unsupported type: {:?}", v),
-            })
+            .zip(column_types)
+            .map(|(v, data_type)| formatter.format(v, data_type, truncate))
             .collect()
     }
+
+    /// write one record. NULL marker (None field) is never quoted, so that it can't be
+    /// mistaken for text
+    fn write_record(&mut self, fields: &[Option<String>]) {
+        self.record.clear();
+        //single field written without csv writer would get quotes added by terminator
+        let mut raw_only = true;
+        for (idx, field) in fields.iter().enumerate() {
+            if idx > 0 {
+                append(&mut self.record, 2, |output| {
+                    self.csv_writer.delimiter(output).1
+                });
+            }
+            match field {
+                None => self.record.extend_from_slice(self.null.as_bytes()),
+                Some(text) if text.is_empty() && self.quote_empty => {
+                    let quote = self.csv_writer.get_quote();
+                    self.record.extend_from_slice(&[quote, quote]);
+                }
+                Some(text) => {
+                    raw_only = false;
+                    //worst case: every character escaped, plus opening quote
+                    append(&mut self.record, text.len() * 2 + 1, |output| {
+                        self.csv_writer.field(text.as_bytes(), output).2
+                    });
+                }
+            }
+        }
+        if fields.len() == 1 && raw_only {
+            match self.csv_writer.get_terminator() {
+                Terminator::CRLF => self.record.extend_from_slice(b"\r\n"),
+                Terminator::Any(terminator) => self.record.push(terminator),
+                _ => unreachable!(),
+            }
+        } else {
+            //closing quote and terminator
+            append(&mut self.record, 4, |output| {
+                self.csv_writer.terminator(output).1
+            });
+        }
+        self.writer.write_all(&self.record).unwrap();
+    }
 }
 
 impl DataDestination for CSVDestination {
     fn prepare(&mut self) {}
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
         if !self.no_headers {
-            let headers: Vec<String> = result_iterator
-                .get_column_info()
-                .iter()
-                .map(|c| c.name.clone())
-                .collect();
-            self.csv_writer.write_record(headers).unwrap();
+            let headers: Vec<Option<String>> =
+                column_info.iter().map(|c| Some(c.name.clone())).collect();
+            self.write_record(&headers);
         }
+        self.column_types = column_info.into_iter().map(|c| c.data_type).collect();
     }
 
     fn add_rows(&mut self, rows: &[Row]) {
         for row in rows {
-            self.csv_writer
-                .write_record(CSVDestination::row_to_csv_row(row, self.truncate))
-                .unwrap();
+            let fields = CSVDestination::row_to_csv_row(
+                row,
+                &self.column_types,
+                self.truncate,
+                &self.formatter,
+            );
+            self.write_record(&fields);
         }
     }
 
     fn close(&mut self) {
-        self.csv_writer.flush().unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{ColumnType, Value};
+    use crate::utils::testing::{exits_with, export, temp_path};
+    use clap::Parser;
+
+    fn csv(args: &[&str], formatter: &ValueFormatter) -> Vec<u8> {
+        let filename = temp_path("export.csv");
+        let options =
+            CSVDestinationOptions::try_parse_from(["csv", filename.as_str()].iter().chain(args))
+                .unwrap();
+        export(
+            &mut CSVDestination::init(&options, formatter),
+            &[("id", ColumnType::I64), ("name", ColumnType::String)],
+            vec![vec![
+                vec![Value::I64(1), Value::String("a;\"b\"".to_string())],
+                vec![Value::I64(2), Value::None],
+            ]],
+        );
+        let output = std::fs::read(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_default_dialect() {
+        assert_eq!(
+            csv(&[], &ValueFormatter::default()),
+            b"id,name\n1,\"a;\"\"b\"\"\"\n2,\n"
+        );
+    }
+
+    #[test]
+    fn test_dialect_options() {
+        assert_eq!(
+            csv(
+                &[
+                    "--delimiter",
+                    ";",
+                    "--escape-style",
+                    "backslash",
+                    "--quote-style",
+                    "non-numeric",
+                    "--crlf",
+                    "--bom",
+                    "--no-headers",
+                    "--null",
+                    "\\N",
+                ],
+                &ValueFormatter::default()
+            ),
+            b"\xEF\xBB\xBF1;\"a;\\\"b\\\"\"\r\n2;\\N\r\n"
+        );
+        assert_eq!(
+            csv(&["--tsv", "--quote", "'"], &ValueFormatter::default()),
+            b"id\tname\n1\ta;\"b\"\n2\t\n"
+        );
+    }
+
+    #[test]
+    fn test_null_marker() {
+        let formatter = ValueFormatter {
+            null_marker: Some("NULL".to_string()),
+            ..ValueFormatter::default()
+        };
+        assert!(csv(&[], &formatter).ends_with(b"2,NULL\n"));
+        //destination option wins
+        assert!(csv(&["--null", ""], &formatter).ends_with(b"2,\n"));
+        //marker is not quoted, whatever the quote style is
+        assert!(csv(&["--quote-style", "always"], &formatter).ends_with(b"\"2\",NULL\n"));
+    }
+
+    #[test]
+    fn test_empty_text() {
+        let export_csv = |args: &[&str], columns: &[(&str, ColumnType)], rows: Vec<Row>| {
+            let filename = temp_path("empty.csv");
+            let options = CSVDestinationOptions::try_parse_from(
+                ["csv", filename.as_str(), "--no-headers"]
+                    .iter()
+                    .chain(args),
+            )
+            .unwrap();
+            export(
+                &mut CSVDestination::init(&options, &ValueFormatter::default()),
+                columns,
+                vec![rows],
+            );
+            let output = std::fs::read_to_string(&filename).unwrap();
+            std::fs::remove_file(&filename).unwrap();
+            output
+        };
+        let rows = vec![
+            vec![Value::String(String::new()), Value::None],
+            vec![Value::None, Value::String(String::new())],
+        ];
+        let columns = [("a", ColumnType::String), ("b", ColumnType::String)];
+        //empty text is quoted only when NULL is written as empty field
+        assert_eq!(export_csv(&[], &columns, rows.clone()), "\"\",\n,\"\"\n");
+        assert_eq!(
+            export_csv(&["--null", "\\N"], &columns, rows.clone()),
+            ",\\N\n\\N,\n"
+        );
+        assert_eq!(
+            export_csv(&["--quote-style", "never"], &columns, rows),
+            ",\n,\n"
+        );
+        assert_eq!(
+            export_csv(
+                &["--null", "\\N", "--quote-style", "always"],
+                &columns[..1],
+                vec![vec![Value::None], vec![Value::String(String::new())]]
+            ),
+            "\\N\n\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_delimiter() {
+        let stderr = exits_with(module_path!(), "test_invalid_delimiter", || {
+            csv(&["--delimiter", "ab"], &ValueFormatter::default());
+        });
+        assert!(stderr.contains("csv: delimiter must be a single ascii character"));
     }
 }
@@ -0,0 +1,378 @@
+use std::io::Write;
+
+use serde_derive::Deserialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::commands::export::{FixedWidthDestinationOptions, FixedWidthOverflow};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::formatter::ValueFormatter;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Alignment {
+    Left,
+    Right,
+}
+
+/// layout file (toml):
+///
+/// ```toml
+/// padding = " "
+///
+/// [[column]]
+/// name = "id"
+/// width = 8
+/// align = "right"
+/// zero_pad = true
+///
+/// [[column]]
+/// name = "name"
+/// width = 20
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct Layout {
+    #[serde(default = "default_padding")]
+    pub padding: char,
+    pub column: Vec<LayoutColumn>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayoutColumn {
+    pub name: String,
+    pub width: usize,
+    pub align: Option<Alignment>,
+    pub padding: Option<char>,
+    /// pad numbers with leading zeros, ignored for other columns
+    #[serde(default)]
+    pub zero_pad: bool,
+}
+
+fn default_padding() -> char {
+    ' '
+}
+
+/// text of formatted value, None being NULL
+fn field_text<'a>(formatter: &'a ValueFormatter, value: &'a Option<String>) -> &'a str {
+    value.as_deref().unwrap_or_else(|| formatter.null(""))
+}
+
+/// resolved layout of one output field
+#[derive(Clone, Debug)]
+struct Field {
+    column_idx: usize,
+    name: String,
+    width: usize,
+    align: Alignment,
+    padding: char,
+    zero_pad: bool,
+}
+
+pub struct FixedWidthDestination {
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    writer: FileOrStdout,
+    layout: Option<Layout>,
+    overflow: FixedWidthOverflow,
+    line_ending: &'static str,
+    trailer: Option<String>,
+    fields: Vec<Field>,
+    column_types: Vec<ColumnType>,
+    //without layout widths are known only after seeing all rows, None is NULL
+    pending_rows: Vec<Vec<Option<String>>>,
+    row_count: u64,
+}
+
+impl FixedWidthDestination {
+    pub fn init(
+        options: &FixedWidthDestinationOptions,
+        formatter: &ValueFormatter,
+    ) -> FixedWidthDestination {
+        let layout = options.layout.as_ref().map(|filename| {
+            let content = std::fs::read_to_string(filename).unwrap_or_else(|e| {
+                eprintln!(
+                    "fixed-width: could not read layout file {}: {}",
+                    filename, e
+                );
+                std::process::exit(1);
+            });
+            toml::from_str::<Layout>(&content).unwrap_or_else(|e| {
+                eprintln!("fixed-width: invalid layout file {}: {}", filename, e);
+                std::process::exit(1);
+            })
+        });
+        FixedWidthDestination {
+            truncate: options.truncate,
+            formatter: formatter.clone(),
+            writer: match options.filename.as_ref() {
+                "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                    termcolor::ColorChoice::Never,
+                )),
+                _ => FileOrStdout::File(std::fs::File::create(options.filename.clone()).unwrap()),
+            },
+            layout,
+            overflow: options.overflow,
+            line_ending: if options.crlf { "\r\n" } else { "\n" },
+            trailer: options.trailer.clone(),
+            fields: vec![],
+            column_types: vec![],
+            pending_rows: vec![],
+            row_count: 0,
+        }
+    }
+
+    fn format_field(&self, field: &Field, value: &Option<String>) -> String {
+        let text = field_text(&self.formatter, value);
+        let length = text.graphemes(true).count();
+        if length > field.width {
+            return match self.overflow {
+                FixedWidthOverflow::Fail => {
+                    eprintln!(
+                        "fixed-width: value {:?} of column {} in row {} is longer than {} characters",
+                        text,
+                        field.name,
+                        self.row_count + 1,
+                        field.width
+                    );
+                    std::process::exit(1);
+                }
+                FixedWidthOverflow::Truncate => text.graphemes(true).take(field.width).collect(),
+            };
+        }
+        let fill = field.width - length;
+        //zero_pad is set only for numeric columns
+        if field.zero_pad && value.is_some() {
+            //keep sign in front of zeros
+            let (sign, digits) = match text.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", text),
+            };
+            return format!("{}{}{}", sign, "0".repeat(fill), digits);
+        }
+        let padding = field.padding.to_string().repeat(fill);
+        match field.align {
+            Alignment::Left => format!("{}{}", text, padding),
+            Alignment::Right => format!("{}{}", padding, text),
+        }
+    }
+
+    fn format_record(&self, values: &[Option<String>]) -> String {
+        let mut record = String::new();
+        for field in self.fields.iter() {
+            record.push_str(&self.format_field(field, &values[field.column_idx]));
+        }
+        record.push_str(self.line_ending);
+        record
+    }
+
+    /// trailer text with {count} or {count:N} (zero padded to N digits) replaced by row count
+    fn format_trailer(&self, trailer: &str) -> String {
+        let mut result = String::new();
+        let mut rest = trailer;
+        while let Some(start) = rest.find("{count") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            let placeholder = &rest[start..start + end + 1];
+            let width = match placeholder.strip_prefix("{count:") {
+                Some(width) => width
+                    .trim_end_matches('}')
+                    .parse::<usize>()
+                    .unwrap_or_else(|_| {
+                        eprintln!("fixed-width: invalid trailer placeholder {}", placeholder);
+                        std::process::exit(1);
+                    }),
+                None => 0,
+            };
+            result.push_str(&format!("{:0width$}", self.row_count, width = width));
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+impl DataDestination for FixedWidthDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
+        self.fields = match &self.layout {
+            Some(layout) => layout
+                .column
+                .iter()
+                .map(|column| {
+                    let Some(column_idx) = column_info.iter().position(|c| c.name == column.name)
+                    else {
+                        eprintln!(
+                            "fixed-width: layout column {} is not present in query results",
+                            column.name
+                        );
+                        std::process::exit(1);
+                    };
+                    Field {
+                        column_idx,
+                        name: column.name.clone(),
+                        width: column.width,
+                        align: column.align.unwrap_or(
+                            if column.zero_pad || column_info[column_idx].data_type.is_numeric() {
+                                Alignment::Right
+                            } else {
+                                Alignment::Left
+                            },
+                        ),
+                        padding: column.padding.unwrap_or(layout.padding),
+                        zero_pad: column.zero_pad && column_info[column_idx].data_type.is_numeric(),
+                    }
+                })
+                .collect(),
+            None => column_info
+                .iter()
+                .enumerate()
+                .map(|(column_idx, column)| Field {
+                    column_idx,
+                    name: column.name.clone(),
+                    width: 0,
+                    align: if column.data_type.is_numeric() {
+                        Alignment::Right
+                    } else {
+                        Alignment::Left
+                    },
+                    padding: ' ',
+                    zero_pad: false,
+                })
+                .collect(),
+        };
+        self.column_types = column_info.into_iter().map(|c| c.data_type).collect();
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let mut batch = String::new();
+        for row in rows {
+            let values: Vec<Option<String>> = row
+                .iter()
+                .zip(&self.column_types)
+                .map(|(value, data_type)| {
+                    self.formatter
+                        .format(value, data_type, self.truncate)
+                        .map(|text| text.replace(['\r', '\n'], " "))
+                })
+                .collect();
+            if self.layout.is_some() {
+                batch.push_str(&self.format_record(&values));
+            } else {
+                for field in self.fields.iter_mut() {
+                    field.width = field.width.max(
+                        field_text(&self.formatter, &values[field.column_idx])
+                            .graphemes(true)
+                            .count(),
+                    );
+                }
+                self.pending_rows.push(values);
+            }
+            self.row_count += 1;
+        }
+        self.writer.write_all(batch.as_bytes()).unwrap();
+    }
+
+    fn close(&mut self) {
+        let mut output = String::new();
+        for values in self.pending_rows.iter() {
+            output.push_str(&self.format_record(values));
+        }
+        if let Some(trailer) = &self.trailer {
+            output.push_str(&self.format_trailer(trailer));
+            output.push_str(self.line_ending);
+        }
+        self.writer.write_all(output.as_bytes()).unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{ColumnType, Value};
+    use crate::utils::testing::{exits_with, export, temp_path};
+    use clap::Parser;
+
+    fn fixed_width(args: &[&str], rows: Vec<Row>) -> String {
+        let filename = temp_path("fixed-width.txt");
+        let options = FixedWidthDestinationOptions::try_parse_from(
+            ["fixed-width", filename.as_str()].iter().chain(args.iter()),
+        )
+        .unwrap();
+        export(
+            &mut FixedWidthDestination::init(&options, &ValueFormatter::default()),
+            &[("id", ColumnType::I64), ("code", ColumnType::String)],
+            vec![rows],
+        );
+        let output = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        output
+    }
+
+    fn row(id: Value, code: &str) -> Row {
+        vec![id, Value::String(code.to_string())]
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = temp_path("layout.toml");
+        std::fs::write(
+            &layout,
+            "padding = '.'\n\
+             [[column]]\nname = 'id'\nwidth = 5\nzero_pad = true\n\
+             [[column]]\nname = 'code'\nwidth = 4\nzero_pad = true\n",
+        )
+        .unwrap();
+        let output = fixed_width(
+            &[
+                "--layout",
+                &layout,
+                "--on-overflow",
+                "truncate",
+                "--trailer",
+                "T{count:3}",
+            ],
+            vec![
+                row(Value::I64(-42), "12"),
+                row(Value::None, "abcdef"),
+                row(Value::I64(7), "1e3"),
+            ],
+        );
+        std::fs::remove_file(&layout).unwrap();
+        assert_eq!(output, "-0042..12\n.....abcd\n00007.1e3\nT003\n");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let layout = temp_path("overflow-layout.toml");
+        std::fs::write(
+            &layout,
+            "[[column]]\nname = 'id'\nwidth = 2\n[[column]]\nname = 'code'\nwidth = 3\n",
+        )
+        .unwrap();
+        let stderr = exits_with(module_path!(), "test_overflow", || {
+            fixed_width(&["--layout", &layout], vec![row(Value::I64(1), "abcd")]);
+        });
+        std::fs::remove_file(&layout).unwrap();
+        assert!(
+            stderr.contains(
+                "fixed-width: value \"abcd\" of column code in row 1 is longer than 3 characters"
+            ),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn test_without_layout() {
+        let output = fixed_width(
+            &["--crlf"],
+            vec![row(Value::I64(100), "a"), row(Value::I64(5), "b\nc")],
+        );
+        assert_eq!(output, "100a  \r\n  5b c\r\n");
+    }
+}
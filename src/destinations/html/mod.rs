@@ -4,22 +4,72 @@ use std::io::Write;
 use askama_escape::{self, escape};
 
 use crate::commands::export::HTMLDestinationOptions;
-use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row, Value};
 use crate::utils::fileorstdout::FileOrStdout;
-use crate::utils::{escape_binary_data, truncate_text_with_note};
+use crate::utils::formatter::ValueFormatter;
+
+//javascript numbers can't represent larger integers exactly
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// javascript string literal, safe to embed in <script> element
+fn js_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            //"</script>" or "<!--" would end script element
+            '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => {
+                result.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// numbers are kept as numbers, so that page can sort and filter them numerically
+fn value_to_js(value: &Value, truncate: Option<u64>, formatter: &ValueFormatter) -> String {
+    match value {
+        Value::None => "null".to_string(),
+        Value::U64(v) if *v <= MAX_SAFE_INTEGER => v.to_string(),
+        Value::I64(v) if v.unsigned_abs() <= MAX_SAFE_INTEGER => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::U16(v) => v.to_string(),
+        Value::I16(v) => v.to_string(),
+        Value::U8(v) => v.to_string(),
+        Value::I8(v) => v.to_string(),
+        Value::F64(v) if v.is_finite() => v.to_string(),
+        Value::F32(v) if v.is_finite() => v.to_string(),
+        //decimal text isn't formatted either, like other numbers
+        _ => js_string(&formatter.format_or_null(value, &ColumnType::None, truncate, "")),
+    }
+}
 
 pub struct HTMLDestination {
     truncate: Option<u64>,
+    formatter: ValueFormatter,
     column_names: Vec<String>,
+    column_types: Vec<ColumnType>,
     writer: FileOrStdout,
     title: String,
+    self_contained: bool,
 }
 
 impl HTMLDestination {
-    pub fn init(options: &HTMLDestinationOptions) -> HTMLDestination {
+    pub fn init(options: &HTMLDestinationOptions, formatter: &ValueFormatter) -> HTMLDestination {
         HTMLDestination {
             truncate: options.truncate,
+            formatter: formatter.clone(),
             column_names: vec![],
+            column_types: vec![],
             writer: match options.filename.as_ref() {
                 "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
                     termcolor::ColorChoice::Auto,
@@ -27,6 +77,7 @@ impl HTMLDestination {
                 _ => FileOrStdout::File(std::fs::File::create(options.filename.clone()).unwrap()),
             },
             title: options.title.clone().unwrap_or_default(),
+            self_contained: options.self_contained,
         }
     }
 }
@@ -35,6 +86,27 @@ impl DataDestination for HTMLDestination {
     fn prepare(&mut self) {}
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
+        self.column_names = column_info.iter().map(|col| col.name.clone()).collect();
+        self.column_types = column_info.into_iter().map(|col| col.data_type).collect();
+        if self.self_contained {
+            //rows are written as javascript array, page renders only visible part of it
+            let mut prefix = include_str!("interactive_prefix.html").replace(
+                "{title}",
+                &escape(&self.title, askama_escape::Html).to_string(),
+            );
+            prefix.push_str(&format!(
+                "<script>\nconst TITLE = {};\nconst COLUMNS = [{}];\nconst ROWS = [\n",
+                js_string(&self.title),
+                self.column_names
+                    .iter()
+                    .map(|name| js_string(name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+            self.writer.write_all(prefix.as_bytes()).unwrap();
+            return;
+        }
         self.writer
             .write_all(
                 format!(
@@ -45,11 +117,6 @@ impl DataDestination for HTMLDestination {
             )
             .unwrap();
 
-        self.column_names = result_iterator
-            .get_column_info()
-            .iter()
-            .map(|col| col.name.clone())
-            .collect();
         self.writer.write_all(b"<thead><tr>\n").unwrap();
         for name in self.column_names.iter() {
             self.writer
@@ -64,37 +131,29 @@ impl DataDestination for HTMLDestination {
         self.writer.write_all(b"</tr></thead><tbody>\n").unwrap();
     }
     fn add_rows(&mut self, rows: &[Row]) {
+        if self.self_contained {
+            let mut batch = String::new();
+            for row in rows {
+                batch.push('[');
+                batch.push_str(
+                    &row.iter()
+                        .map(|value| value_to_js(value, self.truncate, &self.formatter))
+                        .collect::<Vec<String>>()
+                        .join(","),
+                );
+                batch.push_str("],\n");
+            }
+            self.writer.write_all(batch.as_bytes()).unwrap();
+            return;
+        }
         for row in rows {
             //<column index, value, original length, truncated>
             let mut row_data: Vec<String> = Vec::with_capacity(self.column_names.len());
-            for col in row.iter() {
+            for (col, data_type) in row.iter().zip(&self.column_types) {
                 let content = escape(
-                    &match col {
-                        Value::U64(value) => value.to_string(),
-                        Value::I64(value) => value.to_string(),
-                        Value::U32(value) => value.to_string(),
-                        Value::I32(value) => value.to_string(),
-                        Value::U16(value) => value.to_string(),
-                        Value::I16(value) => value.to_string(),
-                        Value::U8(value) => value.to_string(),
-                        Value::I8(value) => value.to_string(),
-                        Value::F64(value) => value.to_string(),
-                        Value::F32(value) => value.to_string(),
-                        Value::String(value) => {
-                            truncate_text_with_note(value.to_string(), self.truncate)
-                        }
-                        Value::Bool(value) => value.to_string(),
-                        Value::Bytes(value) => escape_binary_data(value),
-                        Value::None => "".to_string(),
-                        Value::Timestamp(value) => value.to_string(),
-                        Value::Date(date) => format!("{}", date.format("%Y-%m-%d")),
-                        Value::Time(time) => format!("{}", time.format("%H:%M:%S")),
-                        Value::DateTime(datetime) => {
-                            format!("{}", datetime.format("%Y-%m-%d %H:%M:%S"))
-                        }
-
-                        _ => panic!("text: unsupported type: {:?}", col),
-                    },
+                    &self
+                        .formatter
+                        .format_or_null(col, data_type, self.truncate, ""),
                     askama_escape::Html,
                 )
                 .to_string();
@@ -113,9 +172,76 @@ impl DataDestination for HTMLDestination {
     }
 
     fn close(&mut self) {
-        self.writer
-            .write_all(include_bytes!("html_suffix.html"))
-            .unwrap();
+        let suffix: &[u8] = if self.self_contained {
+            include_bytes!("interactive_suffix.html")
+        } else {
+            include_bytes!("html_suffix.html")
+        };
+        self.writer.write_all(suffix).unwrap();
         self.writer.flush().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::ColumnType;
+    use crate::utils::testing::{export, temp_path};
+    use clap::Parser;
+
+    fn html(args: &[&str]) -> String {
+        let filename = temp_path("export.html");
+        let options =
+            HTMLDestinationOptions::try_parse_from(["html", filename.as_str()].iter().chain(args))
+                .unwrap();
+        export(
+            &mut HTMLDestination::init(&options, &ValueFormatter::default()),
+            &[("id", ColumnType::U64), ("name", ColumnType::String)],
+            vec![vec![
+                vec![Value::U64(1), Value::String("</script><b>".to_string())],
+                vec![Value::U64(u64::MAX), Value::None],
+            ]],
+        );
+        let output = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_js_values() {
+        let formatter = ValueFormatter::default();
+        assert_eq!(value_to_js(&Value::I64(-5), None, &formatter), "-5");
+        //too large for javascript number
+        assert_eq!(
+            value_to_js(&Value::U64(MAX_SAFE_INTEGER + 1), None, &formatter),
+            "\"9007199254740992\""
+        );
+        assert_eq!(
+            value_to_js(&Value::F64(f64::NAN), None, &formatter),
+            "\"NaN\""
+        );
+        assert_eq!(value_to_js(&Value::None, None, &formatter), "null");
+        assert_eq!(js_string("a\"\n\u{2028}"), "\"a\\\"\\n\\u2028\"");
+    }
+
+    #[test]
+    fn test_self_contained() {
+        let output = html(&["--self-contained", "--title", "<Users>"]);
+        assert!(output.contains("&lt;Users&gt;"));
+        assert!(output.contains(
+            "const TITLE = \"\\u003cUsers\\u003e\";\nconst COLUMNS = [\"id\", \"name\"];\n\
+             const ROWS = [\n[1,\"\\u003c/script\\u003e\\u003cb\\u003e\"],\n\
+             [\"18446744073709551615\",null],\n"
+        ));
+        assert!(!output.contains("</script><b>"));
+        assert!(output.ends_with(include_str!("interactive_suffix.html")));
+    }
+
+    #[test]
+    fn test_static_table() {
+        let output = html(&[]);
+        assert!(output.contains("    <th>name</th>\n"));
+        assert!(output.contains("    <td>&lt;/script&gt;&lt;b&gt;</td>\n"));
+        assert!(output.ends_with(include_str!("html_suffix.html")));
+    }
+}
@@ -7,11 +7,13 @@ use json_color;
 use crate::commands::{export::JSONDestinationOptions, ApplicationArguments, UseColor};
 use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
 use crate::utils::fileorstdout::FileOrStdout;
-use crate::utils::{escape_binary_data, truncate_text_with_note};
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::truncate_text_with_note;
 
 pub struct JSONDestination {
     writer: FileOrStdout,
     truncate: Option<u64>,
+    formatter: ValueFormatter,
     indent: u16,
     compact: bool,
     first_row: bool,
@@ -24,6 +26,7 @@ impl JSONDestination {
     pub fn init(
         args: &ApplicationArguments,
         json_options: &JSONDestinationOptions,
+        formatter: &ValueFormatter,
     ) -> JSONDestination {
         let use_color = match args.color {
             UseColor::Yes => true,
@@ -45,6 +48,7 @@ impl JSONDestination {
             first_row: true,
             indent: json_options.indent,
             truncate: json_options.truncate,
+            formatter: formatter.clone(),
             writer,
             json_colorizer: json_color::Colorizer::arbitrary(),
         }
@@ -62,28 +66,34 @@ impl JSONDestination {
                 Value::I16(value) => json::JsonValue::Number(json::number::Number::from(*value)),
                 Value::U8(value) => json::JsonValue::Number(json::number::Number::from(*value)),
                 Value::I8(value) => json::JsonValue::Number(json::number::Number::from(*value)),
-                Value::F64(value) => json::JsonValue::Number(json::number::Number::from(*value)),
-                Value::F32(value) => json::JsonValue::Number(json::number::Number::from(*value)),
+                Value::F64(value) => json::JsonValue::Number(json::number::Number::from(
+                    self.formatter.round(*value),
+                )),
+                Value::F32(value) => json::JsonValue::Number(json::number::Number::from(
+                    self.formatter.round(*value),
+                )),
                 Value::String(value) => json::JsonValue::String(truncate_text_with_note(
                     value.to_string(),
                     self.truncate,
                 )),
                 Value::Bool(value) => json::JsonValue::Boolean(*value),
-                Value::Bytes(value) => json::JsonValue::String(escape_binary_data(value)),
+                Value::Bytes(value) => json::JsonValue::String(self.formatter.bytes(value)),
                 Value::None => json::JsonValue::Null,
                 Value::Timestamp(value) => {
                     json::JsonValue::Number(json::number::Number::from(*value))
                 }
-                Value::Date(date) => {
-                    json::JsonValue::String(format!("{}", date.format("%Y-%m-%d")))
-                }
-                Value::Time(time) => {
-                    json::JsonValue::String(format!("{}", time.format("%H:%M:%S")))
-                }
+                Value::Date(date) => json::JsonValue::String(self.formatter.date(date)),
+                Value::Time(time) => json::JsonValue::String(self.formatter.time(time)),
                 Value::DateTime(datetime) => {
-                    json::JsonValue::String(format!("{}", datetime.format("%Y-%m-%d %H:%M:%S")))
+                    json::JsonValue::String(self.formatter.datetime(datetime))
                 }
-                _ => panic!("json: unsupported type: {:?}", v),
+                //embedded as json, unless it isn't valid
+                Value::JSON(value) => json::parse(value)
+                    .unwrap_or_else(|_| json::JsonValue::String(value.to_string())),
+                Value::Custom(value) => json::JsonValue::String(truncate_text_with_note(
+                    value.to_string(),
+                    self.truncate,
+                )),
             };
             json_row.insert(&self.column_names[idx], value);
         });
@@ -141,3 +151,45 @@ impl DataDestination for JSONDestination {
         self.writer.flush().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::ColumnType;
+    use crate::utils::testing::{export, temp_path};
+
+    #[test]
+    fn test_json_values() {
+        let filename = temp_path("values.json");
+        let mut destination = JSONDestination {
+            writer: FileOrStdout::File(std::fs::File::create(&filename).unwrap()),
+            truncate: None,
+            formatter: ValueFormatter::default(),
+            indent: 2,
+            compact: true,
+            first_row: true,
+            column_names: vec![],
+            json_colorizer: json_color::Colorizer::arbitrary(),
+            use_color: false,
+        };
+        export(
+            &mut destination,
+            &[
+                ("j", ColumnType::JSON),
+                ("c", ColumnType::Custom("point".to_string())),
+            ],
+            vec![vec![
+                vec![
+                    Value::JSON(r#"{"a": [1, null]}"#.to_string()),
+                    Value::Custom("(1,2)".to_string()),
+                ],
+                vec![Value::JSON("not json".to_string()), Value::None],
+            ]],
+        );
+        assert_eq!(
+            std::fs::read_to_string(&filename).unwrap(),
+            r#"[{"j":{"a":[1,null]},"c":"(1,2)"},{"j":"not json","c":null}]"#
+        );
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::Write;
+
+use crate::commands::export::{LatexDestinationOptions, LatexEnvironment};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::formatter::ValueFormatter;
+
+pub fn latex_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '~' => result.push_str("\\textasciitilde{}"),
+            '^' => result.push_str("\\textasciicircum{}"),
+            '\\' => result.push_str("\\textbackslash{}"),
+            //line breaks are not allowed in l/r columns
+            '\n' | '\r' => result.push(' '),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+pub struct LatexDestination {
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    column_types: Vec<ColumnType>,
+    writer: FileOrStdout,
+    environment: LatexEnvironment,
+    caption: Option<String>,
+}
+
+impl LatexDestination {
+    pub fn init(options: &LatexDestinationOptions, formatter: &ValueFormatter) -> LatexDestination {
+        LatexDestination {
+            truncate: options.truncate,
+            formatter: formatter.clone(),
+            column_types: vec![],
+            writer: match options.filename.as_ref() {
+                "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                    termcolor::ColorChoice::Never,
+                )),
+                _ => FileOrStdout::File(std::fs::File::create(options.filename.clone()).unwrap()),
+            },
+            environment: options.environment,
+            caption: options.caption.clone(),
+        }
+    }
+
+    fn environment_name(&self) -> &'static str {
+        match self.environment {
+            LatexEnvironment::Tabular => "tabular",
+            LatexEnvironment::Longtable => "longtable",
+        }
+    }
+}
+
+impl DataDestination for LatexDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
+        let alignment: String = column_info
+            .iter()
+            .map(|col| if col.data_type.is_numeric() { 'r' } else { 'l' })
+            .collect();
+        let header = column_info
+            .iter()
+            .map(|col| latex_escape(&col.name))
+            .collect::<Vec<String>>()
+            .join(" & ");
+        let mut prefix = String::new();
+        //tabular can't have caption on its own, it needs to be put in a float
+        if let (LatexEnvironment::Tabular, Some(caption)) = (self.environment, &self.caption) {
+            prefix.push_str(&format!(
+                "\\begin{{table}}\n\\centering\n\\caption{{{}}}\n",
+                latex_escape(caption)
+            ));
+        }
+        prefix.push_str(&format!(
+            "\\begin{{{}}}{{{}}}\n",
+            self.environment_name(),
+            alignment
+        ));
+        match self.environment {
+            LatexEnvironment::Tabular => {
+                prefix.push_str(&format!("\\hline\n{} \\\\\n\\hline\n", header));
+            }
+            LatexEnvironment::Longtable => {
+                let table_header = format!("\\hline\n{} \\\\\n\\hline\n", header);
+                if let Some(caption) = &self.caption {
+                    //caption only on first page, header repeated on every page
+                    prefix.push_str(&format!(
+                        "\\caption{{{}}} \\\\\n{}\\endfirsthead\n",
+                        latex_escape(caption),
+                        table_header
+                    ));
+                }
+                prefix.push_str(&format!("{}\\endhead\n\\hline\n\\endfoot\n", table_header));
+            }
+        }
+        self.writer.write_all(prefix.as_bytes()).unwrap();
+        self.column_types = column_info.into_iter().map(|col| col.data_type).collect();
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let mut batch = String::new();
+        for row in rows {
+            batch.push_str(
+                &row.iter()
+                    .zip(&self.column_types)
+                    .map(|(value, data_type)| {
+                        latex_escape(&self.formatter.format_or_null(
+                            value,
+                            data_type,
+                            self.truncate,
+                            "",
+                        ))
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" & "),
+            );
+            batch.push_str(" \\\\\n");
+        }
+        self.writer.write_all(batch.as_bytes()).unwrap();
+    }
+
+    fn close(&mut self) {
+        let suffix = match (self.environment, &self.caption) {
+            (LatexEnvironment::Tabular, None) => "\\hline\n\\end{tabular}\n",
+            (LatexEnvironment::Tabular, Some(_)) => "\\hline\n\\end{tabular}\n\\end{table}\n",
+            (LatexEnvironment::Longtable, _) => "\\end{longtable}\n",
+        };
+        self.writer.write_all(suffix.as_bytes()).unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{ColumnType, Value};
+    use crate::utils::testing::{export, temp_path};
+    use clap::Parser;
+
+    fn latex(args: &[&str]) -> String {
+        let filename = temp_path("export.tex");
+        let options = LatexDestinationOptions::try_parse_from(
+            ["latex", filename.as_str()].iter().chain(args),
+        )
+        .unwrap();
+        export(
+            &mut LatexDestination::init(&options, &ValueFormatter::default()),
+            &[("id", ColumnType::I64), ("a_b", ColumnType::String)],
+            vec![vec![
+                vec![Value::I64(1), Value::String("50% & $".to_string())],
+                vec![Value::I64(2), Value::None],
+            ]],
+        );
+        let output = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_tabular() {
+        let rows = "1 & 50\\% \\& \\$ \\\\\n2 &  \\\\\n";
+        assert_eq!(
+            latex(&[]),
+            format!(
+                "\\begin{{tabular}}{{rl}}\n\\hline\nid & a\\_b \\\\\n\\hline\n{}\\hline\n\\end{{tabular}}\n",
+                rows
+            )
+        );
+        assert_eq!(
+            latex(&["--caption", "Users_1"]),
+            format!(
+                "\\begin{{table}}\n\\centering\n\\caption{{Users\\_1}}\n\
+                 \\begin{{tabular}}{{rl}}\n\\hline\nid & a\\_b \\\\\n\\hline\n{}\
+                 \\hline\n\\end{{tabular}}\n\\end{{table}}\n",
+                rows
+            )
+        );
+    }
+
+    #[test]
+    fn test_longtable() {
+        let output = latex(&["-e", "longtable", "--caption", "Users"]);
+        assert!(output.starts_with(
+            "\\begin{longtable}{rl}\n\\caption{Users} \\\\\n\\hline\nid & a\\_b \\\\\n\\hline\n\\endfirsthead\n"
+        ));
+        assert!(output.ends_with("2 &  \\\\\n\\end{longtable}\n"));
+    }
+}
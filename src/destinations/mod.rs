@@ -1,26 +1,52 @@
 use crate::definitions::{DataDestination, DataSourceBatchIterator, Row};
 
+#[cfg(feature = "use_arrow")]
+pub mod arrow;
+#[cfg(feature = "use_avro")]
+pub mod avro;
 #[cfg(feature = "use_csv")]
 pub mod csv;
 pub mod debug;
+#[cfg(feature = "use_text")]
+pub mod fixed_width;
 #[cfg(feature = "use_html")]
 pub mod html;
 #[cfg(feature = "use_json")]
 pub mod json;
+#[cfg(feature = "use_text")]
+pub mod latex;
 #[cfg(feature = "use_ods")]
 pub mod ods;
+#[cfg(feature = "use_ods")]
+pub mod ods_stream;
+#[cfg(feature = "use_pager")]
+pub mod pager;
 #[cfg(feature = "use_parquet")]
 pub mod parquet;
+#[cfg(feature = "use_text")]
+pub mod rst;
+#[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+pub mod spreadsheet;
 #[cfg(feature = "use_sqlite")]
 pub mod sqlite;
+#[cfg(feature = "use_template")]
+pub mod template;
 #[cfg(feature = "use_text")]
 pub mod text;
 #[cfg(feature = "use_text")]
 pub mod text_vertical;
+#[cfg(feature = "use_toml")]
+pub mod toml;
 #[cfg(feature = "use_xlsx")]
 pub mod xlsx;
+#[cfg(feature = "use_xml")]
+pub mod xml;
+#[cfg(feature = "use_yaml")]
+pub mod yaml;
 
 pub enum Destination {
+    #[cfg(feature = "use_arrow")]
+    Arrow(arrow::ArrowDestination),
     #[cfg(feature = "use_csv")]
     CSV(Box<csv::CSVDestination>),
     Debug(debug::DebugDestination),
@@ -28,6 +54,8 @@ pub enum Destination {
     HTML(html::HTMLDestination),
     #[cfg(feature = "use_json")]
     JSON(json::JSONDestination),
+    #[cfg(feature = "use_avro")]
+    Avro(avro::AvroDestination),
     #[cfg(feature = "use_parquet")]
     Parquet(parquet::ParquetDestination),
     #[cfg(feature = "use_sqlite")]
@@ -37,14 +65,48 @@ pub enum Destination {
     #[cfg(feature = "use_xlsx")]
     SpreadSheetXLSX(Box<xlsx::SpreadSheetXLSXDestination>),
     #[cfg(feature = "use_text")]
-    Text(text::TextDestination),
+    Text(Box<text::TextDestination>),
     #[cfg(feature = "use_text")]
     TextVertical(text_vertical::TextVerticalDestination),
+    #[cfg(feature = "use_template")]
+    Template(template::TemplateDestination),
+    #[cfg(feature = "use_text")]
+    FixedWidth(fixed_width::FixedWidthDestination),
+    #[cfg(feature = "use_text")]
+    Latex(latex::LatexDestination),
+    #[cfg(feature = "use_text")]
+    Rst(rst::RstDestination),
+    #[cfg(feature = "use_toml")]
+    TOML(toml::TOMLDestination),
+    #[cfg(feature = "use_yaml")]
+    YAML(yaml::YAMLDestination),
+    #[cfg(feature = "use_xml")]
+    XML(xml::XMLDestination),
+}
+
+#[cfg(any(feature = "use_ods", feature = "use_xlsx"))]
+impl Destination {
+    /// next result goes to its own worksheet with given name (--sheet)
+    pub fn set_sheet_title(&mut self, title: &str) {
+        match self {
+            #[cfg(feature = "use_ods")]
+            Destination::SpreadSheetODS(spreadsheet_destination) => {
+                spreadsheet_destination.set_sheet_title(title)
+            }
+            #[cfg(feature = "use_xlsx")]
+            Destination::SpreadSheetXLSX(spreadsheet_destination) => {
+                spreadsheet_destination.set_sheet_title(title)
+            }
+            _ => unreachable!("only spreadsheet destinations have named sheets"),
+        }
+    }
 }
 
 impl DataDestination for Destination {
     fn prepare(&mut self) {
         match self {
+            #[cfg(feature = "use_arrow")]
+            Destination::Arrow(arrow_destination) => arrow_destination.prepare(),
             #[cfg(feature = "use_csv")]
             Destination::CSV(csv_destination) => csv_destination.prepare(),
             Destination::Debug(debug_destination) => debug_destination.prepare(),
@@ -60,6 +122,8 @@ impl DataDestination for Destination {
             Destination::SpreadSheetXLSX(spreadsheet_destination) => {
                 spreadsheet_destination.prepare()
             }
+            #[cfg(feature = "use_avro")]
+            Destination::Avro(avro_destination) => avro_destination.prepare(),
             #[cfg(feature = "use_parquet")]
             Destination::Parquet(parquet_destination) => parquet_destination.prepare(),
             #[cfg(feature = "use_sqlite")]
@@ -70,11 +134,29 @@ impl DataDestination for Destination {
             Destination::TextVertical(text_vertical_destination) => {
                 text_vertical_destination.prepare()
             }
+            #[cfg(feature = "use_template")]
+            Destination::Template(template_destination) => template_destination.prepare(),
+            #[cfg(feature = "use_text")]
+            Destination::FixedWidth(fixed_width_destination) => fixed_width_destination.prepare(),
+            #[cfg(feature = "use_text")]
+            Destination::Latex(latex_destination) => latex_destination.prepare(),
+            #[cfg(feature = "use_text")]
+            Destination::Rst(rst_destination) => rst_destination.prepare(),
+            #[cfg(feature = "use_toml")]
+            Destination::TOML(toml_destination) => toml_destination.prepare(),
+            #[cfg(feature = "use_yaml")]
+            Destination::YAML(yaml_destination) => yaml_destination.prepare(),
+            #[cfg(feature = "use_xml")]
+            Destination::XML(xml_destination) => xml_destination.prepare(),
         }
     }
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
         match self {
+            #[cfg(feature = "use_arrow")]
+            Destination::Arrow(arrow_destination) => {
+                arrow_destination.prepare_for_results(result_iterator)
+            }
             #[cfg(feature = "use_csv")]
             Destination::CSV(csv_destination) => {
                 csv_destination.prepare_for_results(result_iterator)
@@ -98,6 +180,10 @@ impl DataDestination for Destination {
             Destination::SpreadSheetXLSX(spreadsheet_destination) => {
                 spreadsheet_destination.prepare_for_results(result_iterator)
             }
+            #[cfg(feature = "use_avro")]
+            Destination::Avro(avro_destination) => {
+                avro_destination.prepare_for_results(result_iterator)
+            }
             #[cfg(feature = "use_parquet")]
             Destination::Parquet(parquet_destination) => {
                 parquet_destination.prepare_for_results(result_iterator)
@@ -114,11 +200,41 @@ impl DataDestination for Destination {
             Destination::TextVertical(text_vertical_destination) => {
                 text_vertical_destination.prepare_for_results(result_iterator)
             }
+            #[cfg(feature = "use_template")]
+            Destination::Template(template_destination) => {
+                template_destination.prepare_for_results(result_iterator)
+            }
+            #[cfg(feature = "use_text")]
+            Destination::FixedWidth(fixed_width_destination) => {
+                fixed_width_destination.prepare_for_results(result_iterator)
+            }
+            #[cfg(feature = "use_text")]
+            Destination::Latex(latex_destination) => {
+                latex_destination.prepare_for_results(result_iterator)
+            }
+            #[cfg(feature = "use_text")]
+            Destination::Rst(rst_destination) => {
+                rst_destination.prepare_for_results(result_iterator)
+            }
+            #[cfg(feature = "use_toml")]
+            Destination::TOML(toml_destination) => {
+                toml_destination.prepare_for_results(result_iterator)
+            }
+            #[cfg(feature = "use_yaml")]
+            Destination::YAML(yaml_destination) => {
+                yaml_destination.prepare_for_results(result_iterator)
+            }
+            #[cfg(feature = "use_xml")]
+            Destination::XML(xml_destination) => {
+                xml_destination.prepare_for_results(result_iterator)
+            }
         }
     }
 
     fn add_rows(&mut self, rows: &[Row]) {
         match self {
+            #[cfg(feature = "use_arrow")]
+            Destination::Arrow(arrow_destination) => arrow_destination.add_rows(rows),
             #[cfg(feature = "use_csv")]
             Destination::CSV(csv_destination) => csv_destination.add_rows(rows),
             Destination::Debug(debug_destination) => debug_destination.add_rows(rows),
@@ -134,6 +250,8 @@ impl DataDestination for Destination {
             Destination::SpreadSheetXLSX(spreadsheet_destination) => {
                 spreadsheet_destination.add_rows(rows)
             }
+            #[cfg(feature = "use_avro")]
+            Destination::Avro(avro_destination) => avro_destination.add_rows(rows),
             #[cfg(feature = "use_parquet")]
             Destination::Parquet(parquet_destination) => parquet_destination.add_rows(rows),
             #[cfg(feature = "use_sqlite")]
@@ -144,11 +262,29 @@ impl DataDestination for Destination {
             Destination::TextVertical(text_vertical_destination) => {
                 text_vertical_destination.add_rows(rows)
             }
+            #[cfg(feature = "use_template")]
+            Destination::Template(template_destination) => template_destination.add_rows(rows),
+            #[cfg(feature = "use_text")]
+            Destination::FixedWidth(fixed_width_destination) => {
+                fixed_width_destination.add_rows(rows)
+            }
+            #[cfg(feature = "use_text")]
+            Destination::Latex(latex_destination) => latex_destination.add_rows(rows),
+            #[cfg(feature = "use_text")]
+            Destination::Rst(rst_destination) => rst_destination.add_rows(rows),
+            #[cfg(feature = "use_toml")]
+            Destination::TOML(toml_destination) => toml_destination.add_rows(rows),
+            #[cfg(feature = "use_yaml")]
+            Destination::YAML(yaml_destination) => yaml_destination.add_rows(rows),
+            #[cfg(feature = "use_xml")]
+            Destination::XML(xml_destination) => xml_destination.add_rows(rows),
         }
     }
 
     fn close(&mut self) {
         match self {
+            #[cfg(feature = "use_arrow")]
+            Destination::Arrow(arrow_destination) => arrow_destination.close(),
             #[cfg(feature = "use_csv")]
             Destination::CSV(csv_destination) => csv_destination.close(),
             Destination::Debug(debug_destination) => debug_destination.close(),
@@ -162,6 +298,8 @@ impl DataDestination for Destination {
             Destination::SpreadSheetXLSX(spreadsheet_destination) => {
                 spreadsheet_destination.close()
             }
+            #[cfg(feature = "use_avro")]
+            Destination::Avro(avro_destination) => avro_destination.close(),
             #[cfg(feature = "use_parquet")]
             Destination::Parquet(parquet_destination) => parquet_destination.close(),
             #[cfg(feature = "use_sqlite")]
@@ -172,6 +310,20 @@ impl DataDestination for Destination {
             Destination::TextVertical(text_vertical_destination) => {
                 text_vertical_destination.close()
             }
+            #[cfg(feature = "use_template")]
+            Destination::Template(template_destination) => template_destination.close(),
+            #[cfg(feature = "use_text")]
+            Destination::FixedWidth(fixed_width_destination) => fixed_width_destination.close(),
+            #[cfg(feature = "use_text")]
+            Destination::Latex(latex_destination) => latex_destination.close(),
+            #[cfg(feature = "use_text")]
+            Destination::Rst(rst_destination) => rst_destination.close(),
+            #[cfg(feature = "use_toml")]
+            Destination::TOML(toml_destination) => toml_destination.close(),
+            #[cfg(feature = "use_yaml")]
+            Destination::YAML(yaml_destination) => yaml_destination.close(),
+            #[cfg(feature = "use_xml")]
+            Destination::XML(xml_destination) => xml_destination.close(),
         }
     }
 }
@@ -1,19 +1,65 @@
-use icu_locid::locale;
+use icu_locid::{locale, Locale};
 use spreadsheet_ods;
+use spreadsheet_ods::format::FormatNumberStyle;
+use spreadsheet_ods::{
+    CellStyle, CellStyleRef, ValueFormatBoolean, ValueFormatDateTime, ValueFormatTimeDuration,
+};
 
-use crate::commands::export::SpreadSheetDestinationOptions;
+use crate::commands::export::{SheetOverflow, SpreadSheetDestinationOptions};
 use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::destinations::ods_stream::OdsStreamWriter;
+use crate::destinations::spreadsheet::{
+    max_sheet_rows, numbered_filename, sheet_name, use_low_memory,
+};
 use crate::utils::{escape_binary_data, truncate_text_with_note};
 
+/// cell styles used by both in-memory and streaming writer
+#[derive(Clone, Copy, Debug)]
+pub enum CellStyleKind {
+    Header,
+    Boolean,
+    Date,
+    DateTime,
+    Time,
+}
+
+impl CellStyleKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CellStyleKind::Header => "ce_header",
+            CellStyleKind::Boolean => "ce_boolean",
+            CellStyleKind::Date => "ce_date",
+            CellStyleKind::DateTime => "ce_datetime",
+            CellStyleKind::Time => "ce_time",
+        }
+    }
+}
+
+enum OdsOutput {
+    //whole workbook is kept in memory and written on close
+    WorkBook(Box<spreadsheet_ods::WorkBook>),
+    //rows are written to file as they arrive (--low-memory)
+    Stream(Option<Box<OdsStreamWriter>>),
+}
+
 pub struct SpreadSheetODSDestination {
     filename: String,
-    workbook: spreadsheet_ods::WorkBook,
+    output: OdsOutput,
+    locale: Locale,
     sheet_row_count: usize,
     truncate: Option<u64>,
+    on_row_limit: SheetOverflow,
+    max_sheet_rows: usize,
+    column_names: Vec<String>,
+    //sheet index within current workbook
+    sheet_index: usize,
+    //name of current result, set when exporting several queries (--sheet)
+    sheet_title: Option<String>,
+    sheet_number: usize,
+    file_number: usize,
+    low_memory_threshold: u64,
 }
 
-#[allow(deprecated)] //FIXME: spreadsheet_ods uses NaiveDateTime, hopefully it will be updated
-                     //someday
 pub fn value_to_ods_value(value: &Value, truncate: Option<u64>) -> spreadsheet_ods::Value {
     match value {
         Value::U64(value) => spreadsheet_ods::Value::Number(*value as f64),
@@ -26,36 +72,231 @@ pub fn value_to_ods_value(value: &Value, truncate: Option<u64>) -> spreadsheet_o
         Value::I8(value) => spreadsheet_ods::Value::Number(*value as f64),
         Value::F64(value) => spreadsheet_ods::Value::Number(*value),
         Value::F32(value) => spreadsheet_ods::Value::Number(*value as f64),
-        Value::String(value) => {
+        Value::String(value) | Value::JSON(value) | Value::Custom(value) => {
             spreadsheet_ods::Value::Text(truncate_text_with_note(value.to_string(), truncate))
         }
         Value::Bool(value) => spreadsheet_ods::Value::Boolean(*value),
-        Value::Bytes(value) => spreadsheet_ods::Value::Text(escape_binary_data(value)),
-        Value::None => spreadsheet_ods::Value::Text("".to_string()),
-        Value::Timestamp(value) => spreadsheet_ods::Value::DateTime(
-            chrono::NaiveDateTime::from_timestamp(*value as i64, 0),
-        ),
-        Value::Date(value) => spreadsheet_ods::Value::DateTime(chrono::NaiveDateTime::new(
-            *value,
-            chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        Value::Bytes(value) => spreadsheet_ods::Value::Text(truncate_text_with_note(
+            escape_binary_data(value),
+            truncate,
         )),
-        Value::Time(value) => spreadsheet_ods::Value::Text(format!("{:?}", value)),
+        Value::None => spreadsheet_ods::Value::Empty,
+        Value::Timestamp(value) => match chrono::DateTime::from_timestamp(*value as i64, 0) {
+            Some(datetime) => spreadsheet_ods::Value::DateTime(datetime.naive_utc()),
+            None => spreadsheet_ods::Value::Number(*value as f64),
+        },
+        Value::Date(value) => {
+            spreadsheet_ods::Value::DateTime(value.and_time(chrono::NaiveTime::MIN))
+        }
+        Value::Time(value) => spreadsheet_ods::Value::TimeDuration(
+            value.signed_duration_since(chrono::NaiveTime::MIN),
+        ),
         Value::DateTime(value) => spreadsheet_ods::Value::DateTime(*value),
-        _ => panic!("spsheet: unsupported type: {:?}", value),
     }
 }
 
+/// style for values that need a number format to be displayed properly
+pub fn value_style(value: &Value) -> Option<CellStyleKind> {
+    match value {
+        Value::Bool(_) => Some(CellStyleKind::Boolean),
+        Value::Date(_) => Some(CellStyleKind::Date),
+        Value::DateTime(_) | Value::Timestamp(_) => Some(CellStyleKind::DateTime),
+        Value::Time(_) => Some(CellStyleKind::Time),
+        _ => None,
+    }
+}
+
+/// --locale or system locale, en-US if neither can be used
+fn resolve_locale(name: Option<&str>) -> Locale {
+    match name {
+        Some(name) => name.replace('_', "-").parse().unwrap_or_else(|_| {
+            eprintln!("ods: invalid locale: {}", name);
+            std::process::exit(1);
+        }),
+        None => sys_locale::get_locale()
+            .and_then(|name| name.parse().ok())
+            .unwrap_or(locale!("en-US")),
+    }
+}
+
+/// empty workbook with styles matching ones written by OdsStreamWriter.
+/// Default styles of spreadsheet_ods are not used, since they show all dates without time
+/// and all numbers with two decimal places
+fn new_workbook(locale: &Locale) -> spreadsheet_ods::WorkBook {
+    let mut workbook = spreadsheet_ods::WorkBook::new_empty();
+
+    let mut boolean = ValueFormatBoolean::new_localized("N_boolean", locale.clone());
+    boolean.part_boolean().build();
+    let boolean = workbook.add_boolean_format(boolean);
+
+    let mut date = ValueFormatDateTime::new_localized("N_date", locale.clone());
+    date.part_year().style(FormatNumberStyle::Long).build();
+    date.part_text("-").build();
+    date.part_month().style(FormatNumberStyle::Long).build();
+    date.part_text("-").build();
+    date.part_day().style(FormatNumberStyle::Long).build();
+    let date = workbook.add_datetime_format(date);
+
+    let mut datetime = ValueFormatDateTime::new_localized("N_datetime", locale.clone());
+    datetime.part_year().style(FormatNumberStyle::Long).build();
+    datetime.part_text("-").build();
+    datetime.part_month().style(FormatNumberStyle::Long).build();
+    datetime.part_text("-").build();
+    datetime.part_day().style(FormatNumberStyle::Long).build();
+    datetime.part_text(" ").build();
+    datetime.part_hours().style(FormatNumberStyle::Long).build();
+    datetime.part_text(":").build();
+    datetime
+        .part_minutes()
+        .style(FormatNumberStyle::Long)
+        .build();
+    datetime.part_text(":").build();
+    datetime
+        .part_seconds()
+        .style(FormatNumberStyle::Long)
+        .build();
+    let datetime = workbook.add_datetime_format(datetime);
+
+    let mut time = ValueFormatTimeDuration::new_localized("N_time", locale.clone());
+    time.set_truncate_on_overflow(false);
+    time.part_hours().style(FormatNumberStyle::Long).build();
+    time.part_text(":").build();
+    time.part_minutes().style(FormatNumberStyle::Long).build();
+    time.part_text(":").build();
+    time.part_seconds().style(FormatNumberStyle::Long).build();
+    let time = workbook.add_timeduration_format(time);
+
+    for (kind, format) in [
+        (CellStyleKind::Boolean, &boolean),
+        (CellStyleKind::Date, &date),
+        (CellStyleKind::DateTime, &datetime),
+        (CellStyleKind::Time, &time),
+    ] {
+        workbook.add_cellstyle(CellStyle::new(kind.name(), format));
+    }
+    let mut header = CellStyle::new_empty();
+    header.set_name(CellStyleKind::Header.name());
+    header.set_font_bold();
+    workbook.add_cellstyle(header);
+    workbook
+}
+
 impl SpreadSheetODSDestination {
     pub fn init(spreadsheet_options: &SpreadSheetDestinationOptions) -> SpreadSheetODSDestination {
-        let mut workbook = spreadsheet_ods::WorkBook::new(locale!("en_US")); //todo!("Support any locales using system settings");
-        let sheet = spreadsheet_ods::Sheet::new("Sheet 1");
-        workbook.push_sheet(sheet);
-
+        let locale = resolve_locale(spreadsheet_options.locale.as_deref());
         SpreadSheetODSDestination {
             filename: spreadsheet_options.filename.clone(),
-            workbook: spreadsheet_ods::WorkBook::new(locale!("en_US")),
+            output: if spreadsheet_options.low_memory {
+                OdsOutput::Stream(None)
+            } else {
+                OdsOutput::WorkBook(Box::new(new_workbook(&locale)))
+            },
+            locale,
             sheet_row_count: 0,
             truncate: spreadsheet_options.truncate,
+            on_row_limit: spreadsheet_options.on_row_limit,
+            max_sheet_rows: max_sheet_rows(spreadsheet_options),
+            column_names: vec![],
+            sheet_index: 0,
+            sheet_title: None,
+            sheet_number: 0,
+            file_number: 1,
+            low_memory_threshold: spreadsheet_options.low_memory_threshold,
+        }
+    }
+
+    /// next result goes to its own sheet with given name
+    pub fn set_sheet_title(&mut self, title: &str) {
+        self.sheet_title = Some(title.to_string());
+        self.sheet_number = 0;
+    }
+
+    /// add sheet with header
+    fn start_sheet(&mut self) {
+        self.sheet_number += 1;
+        let name = sheet_name(self.sheet_title.as_deref(), self.sheet_number);
+        let header_style = CellStyleRef::from(CellStyleKind::Header.name());
+        match &mut self.output {
+            OdsOutput::WorkBook(workbook) => {
+                self.sheet_index = workbook.num_sheets();
+                let mut sheet = spreadsheet_ods::Sheet::new(name);
+                for (idx, column_name) in self.column_names.iter().enumerate() {
+                    sheet.set_styled_value(0, idx as u32, column_name.clone(), &header_style);
+                }
+                workbook.push_sheet(sheet);
+            }
+            OdsOutput::Stream(writer) => {
+                if writer.is_none() {
+                    let filename = numbered_filename(&self.filename, self.file_number);
+                    *writer = Some(Box::new(
+                        OdsStreamWriter::create(&filename, &self.locale).unwrap_or_else(|e| {
+                            eprintln!("ods: could not create {}: {}", filename, e);
+                            std::process::exit(1);
+                        }),
+                    ));
+                }
+                let writer = writer.as_mut().unwrap();
+                let header: Vec<(spreadsheet_ods::Value, Option<CellStyleKind>)> = self
+                    .column_names
+                    .iter()
+                    .map(|column_name| {
+                        (
+                            spreadsheet_ods::Value::Text(column_name.clone()),
+                            Some(CellStyleKind::Header),
+                        )
+                    })
+                    .collect();
+                writer
+                    .start_table(&name)
+                    .and_then(|_| writer.write_row(&header))
+                    .unwrap_or_else(|e| {
+                        eprintln!("ods: write error: {}", e);
+                        std::process::exit(1);
+                    });
+            }
+        }
+        self.sheet_row_count = 1;
+    }
+
+    fn save(&mut self) {
+        let filename = numbered_filename(&self.filename, self.file_number);
+        match &mut self.output {
+            OdsOutput::WorkBook(workbook) => spreadsheet_ods::write_ods(workbook, &filename)
+                .unwrap_or_else(|e| {
+                    eprintln!("ods: could not save {}: {}", filename, e);
+                    std::process::exit(1);
+                }),
+            OdsOutput::Stream(writer) => {
+                if let Some(writer) = writer.take() {
+                    writer.finish().unwrap_or_else(|e| {
+                        eprintln!("ods: could not save {}: {}", filename, e);
+                        std::process::exit(1);
+                    });
+                }
+            }
+        }
+    }
+
+    /// current sheet is full, continue according to --on-row-limit
+    fn overflow(&mut self) {
+        match self.on_row_limit {
+            SheetOverflow::Error => {
+                eprintln!(
+                    "ods: result has more than {} rows, which don't fit in one sheet (see --on-row-limit)",
+                    self.max_sheet_rows - 1
+                );
+                std::process::exit(1);
+            }
+            SheetOverflow::NewSheet => self.start_sheet(),
+            SheetOverflow::NewFile => {
+                self.save();
+                if let OdsOutput::WorkBook(workbook) = &mut self.output {
+                    **workbook = new_workbook(&self.locale);
+                }
+                self.file_number += 1;
+                self.sheet_number = 0;
+                self.start_sheet();
+            }
         }
     }
 }
@@ -64,30 +305,234 @@ impl DataDestination for SpreadSheetODSDestination {
     fn prepare(&mut self) {}
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
-        let sheet = self.workbook.sheet_mut(0);
-        for (idx, column) in result_iterator.get_column_info().iter().enumerate() {
-            //self.sheet.add_cell(Cell::str(column.name.clone()), 0, idx);
-            sheet.set_value(0, idx as u32, column.name.clone());
+        //workbook can be replaced with streaming writer only before anything was added to it
+        if let OdsOutput::WorkBook(workbook) = &self.output {
+            if workbook.num_sheets() == 0
+                && use_low_memory(
+                    false,
+                    self.low_memory_threshold,
+                    result_iterator.get_count(),
+                )
+            {
+                self.output = OdsOutput::Stream(None);
+            }
         }
-        self.sheet_row_count += 1;
+        self.column_names = result_iterator
+            .get_column_info()
+            .iter()
+            .map(|column| column.name.clone())
+            .collect();
+        self.start_sheet();
     }
 
     fn add_rows(&mut self, rows: &[Row]) {
-        let sheet = self.workbook.sheet_mut(0);
         for row in rows {
-            for (idx, val) in row.iter().enumerate() {
-                sheet.set_value(
-                    self.sheet_row_count as u32,
-                    idx as u32,
-                    value_to_ods_value(val, self.truncate),
-                );
+            if self.sheet_row_count == self.max_sheet_rows {
+                self.overflow();
+            }
+            match &mut self.output {
+                OdsOutput::WorkBook(workbook) => {
+                    let sheet = workbook.sheet_mut(self.sheet_index);
+                    for (idx, val) in row.iter().enumerate() {
+                        let value = value_to_ods_value(val, self.truncate);
+                        match value_style(val) {
+                            Some(kind) => sheet.set_styled_value(
+                                self.sheet_row_count as u32,
+                                idx as u32,
+                                value,
+                                &CellStyleRef::from(kind.name()),
+                            ),
+                            None => sheet.set_value(self.sheet_row_count as u32, idx as u32, value),
+                        }
+                    }
+                }
+                OdsOutput::Stream(writer) => {
+                    let cells: Vec<(spreadsheet_ods::Value, Option<CellStyleKind>)> = row
+                        .iter()
+                        .map(|val| (value_to_ods_value(val, self.truncate), value_style(val)))
+                        .collect();
+                    writer
+                        .as_mut()
+                        .unwrap()
+                        .write_row(&cells)
+                        .unwrap_or_else(|e| {
+                            eprintln!("ods: write error: {}", e);
+                            std::process::exit(1);
+                        });
+                }
             }
             self.sheet_row_count += 1;
         }
     }
 
     fn close(&mut self) {
-        spreadsheet_ods::write_ods(&mut self.workbook, self.filename.clone())
-            .unwrap_or_else(|_| panic!("write_ods: {}", self.filename))
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::ColumnType;
+    use crate::utils::testing::{exits_with, export, temp_path, zip_entry};
+    use clap::Parser;
+
+    fn export_ods(args: &[&str], rows: Vec<Row>) -> String {
+        let filename = temp_path("export.ods");
+        let options = SpreadSheetDestinationOptions::try_parse_from(
+            ["ods", filename.as_str(), "--locale", "en-US"]
+                .iter()
+                .chain(args),
+        )
+        .unwrap();
+        export(
+            &mut SpreadSheetODSDestination::init(&options),
+            &[("id", ColumnType::I64)],
+            vec![rows],
+        );
+        filename
+    }
+
+    fn numbered_rows(count: i64) -> Vec<Row> {
+        (1..=count).map(|id| vec![Value::I64(id)]).collect()
+    }
+
+    /// sheet names with their first column
+    fn read_sheets(filename: &str) -> Vec<(String, Vec<spreadsheet_ods::Value>)> {
+        let workbook = spreadsheet_ods::read_ods(filename).unwrap();
+        (0..workbook.num_sheets())
+            .map(|idx| {
+                let sheet = workbook.sheet(idx);
+                let (rows, _) = sheet.used_grid_size();
+                let values = (0..rows).map(|row| sheet.value(row, 0).clone()).collect();
+                (sheet.name().to_string(), values)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_row_limit() {
+        let header = spreadsheet_ods::Value::Text("id".to_string());
+        let number = |value: f64| spreadsheet_ods::Value::Number(value);
+        for args in [&[][..], &["--low-memory"][..]] {
+            let filename = export_ods(
+                &[&["--max-sheet-rows", "3"], args].concat(),
+                numbered_rows(5),
+            );
+            assert_eq!(
+                read_sheets(&filename),
+                vec![
+                    (
+                        "Sheet 1".to_string(),
+                        vec![header.clone(), number(1.0), number(2.0)]
+                    ),
+                    (
+                        "Sheet 2".to_string(),
+                        vec![header.clone(), number(3.0), number(4.0)]
+                    ),
+                    ("Sheet 3".to_string(), vec![header.clone(), number(5.0)]),
+                ]
+            );
+            std::fs::remove_file(&filename).unwrap();
+
+            let filename = export_ods(
+                &[
+                    &["--max-sheet-rows", "3", "--on-row-limit", "new-file"],
+                    args,
+                ]
+                .concat(),
+                numbered_rows(3),
+            );
+            assert_eq!(
+                read_sheets(&filename),
+                vec![(
+                    "Sheet 1".to_string(),
+                    vec![header.clone(), number(1.0), number(2.0)]
+                )]
+            );
+            let second = numbered_filename(&filename, 2);
+            assert_eq!(
+                read_sheets(&second),
+                vec![("Sheet 1".to_string(), vec![header.clone(), number(3.0)])]
+            );
+            std::fs::remove_file(&filename).unwrap();
+            std::fs::remove_file(&second).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_cells() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        let time = chrono::NaiveTime::from_hms_opt(13, 5, 9).unwrap();
+        let row = vec![
+            Value::Time(time),
+            Value::Date(date),
+            Value::DateTime(date.and_time(time)),
+            Value::Bool(true),
+            Value::None,
+        ];
+        for low_memory in [false, true] {
+            let filename = temp_path("cells.ods");
+            let mut args = vec!["ods", filename.as_str(), "--locale", "de_DE"];
+            if low_memory {
+                args.push("--low-memory");
+            }
+            let options = SpreadSheetDestinationOptions::try_parse_from(args).unwrap();
+            export(
+                &mut SpreadSheetODSDestination::init(&options),
+                &[
+                    ("t", ColumnType::Time),
+                    ("d", ColumnType::Date),
+                    ("dt", ColumnType::DateTime),
+                    ("b", ColumnType::Bool),
+                    ("n", ColumnType::I64),
+                ],
+                vec![vec![row.clone()]],
+            );
+            let workbook = spreadsheet_ods::read_ods(&filename).unwrap();
+            let sheet = workbook.sheet(0);
+            assert_eq!(
+                sheet.value(1, 0),
+                &spreadsheet_ods::Value::TimeDuration(chrono::Duration::seconds(13 * 3600 + 309))
+            );
+            assert_eq!(
+                sheet.value(1, 1),
+                &spreadsheet_ods::Value::DateTime(date.and_time(chrono::NaiveTime::MIN))
+            );
+            assert_eq!(
+                sheet.value(1, 2),
+                &spreadsheet_ods::Value::DateTime(date.and_time(time))
+            );
+            assert_eq!(sheet.value(1, 3), &spreadsheet_ods::Value::Boolean(true));
+            assert_eq!(sheet.value(1, 4), &spreadsheet_ods::Value::Empty);
+            for (col, kind) in [
+                (0, CellStyleKind::Time),
+                (1, CellStyleKind::Date),
+                (2, CellStyleKind::DateTime),
+            ] {
+                assert_eq!(
+                    sheet.cellstyle(1, col),
+                    Some(&CellStyleRef::from(kind.name()))
+                );
+            }
+            let content = zip_entry(&filename, "content.xml");
+            assert!(
+                content.contains(r#"number:language="de" number:country="DE""#),
+                "{}",
+                content
+            );
+            std::fs::remove_file(&filename).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_invalid_locale() {
+        let stderr = exits_with(module_path!(), "test_invalid_locale", || {
+            let options =
+                SpreadSheetDestinationOptions::try_parse_from(["ods", "x.ods", "--locale", "x y"])
+                    .unwrap();
+            SpreadSheetODSDestination::init(&options);
+        });
+        assert!(stderr.contains("ods: invalid locale: x y"), "{}", stderr);
     }
 }
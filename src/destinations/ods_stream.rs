@@ -0,0 +1,197 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use icu_locid::Locale;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::destinations::ods::CellStyleKind;
+use crate::utils::xml_escape;
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.spreadsheet";
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.3">
+<manifest:file-entry manifest:full-path="/" manifest:version="1.3" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+<manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+const CONTENT_START: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:number="urn:oasis:names:tc:opendocument:xmlns:datastyle:1.0" xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" office:version="1.3">
+"#;
+
+/// number and cell styles, same as ones created by SpreadSheetODSDestination for in-memory workbook
+fn automatic_styles(locale: &Locale) -> String {
+    let mut language = format!(r#" number:language="{}""#, locale.id.language.as_str());
+    if let Some(region) = locale.id.region {
+        language.push_str(&format!(r#" number:country="{}""#, region.as_str()));
+    }
+    let date = r#"<number:year number:style="long"/><number:text>-</number:text><number:month number:style="long"/><number:text>-</number:text><number:day number:style="long"/>"#;
+    let time = r#"<number:hours number:style="long"/><number:text>:</number:text><number:minutes number:style="long"/><number:text>:</number:text><number:seconds number:style="long"/>"#;
+    let mut styles = String::from("<office:automatic-styles>\n");
+    styles.push_str(&format!(
+        "<number:boolean-style style:name=\"N_boolean\"{}><number:boolean/></number:boolean-style>\n",
+        language
+    ));
+    styles.push_str(&format!(
+        "<number:date-style style:name=\"N_date\"{}>{}</number:date-style>\n",
+        language, date
+    ));
+    styles.push_str(&format!(
+        "<number:date-style style:name=\"N_datetime\"{}>{}<number:text> </number:text>{}</number:date-style>\n",
+        language, date, time
+    ));
+    styles.push_str(&format!(
+        "<number:time-style style:name=\"N_time\"{} number:truncate-on-overflow=\"false\">{}</number:time-style>\n",
+        language, time
+    ));
+    for (kind, format) in [
+        (CellStyleKind::Boolean, "N_boolean"),
+        (CellStyleKind::Date, "N_date"),
+        (CellStyleKind::DateTime, "N_datetime"),
+        (CellStyleKind::Time, "N_time"),
+    ] {
+        styles.push_str(&format!(
+            "<style:style style:name=\"{}\" style:family=\"table-cell\" style:data-style-name=\"{}\"/>\n",
+            kind.name(),
+            format
+        ));
+    }
+    styles.push_str(&format!(
+        "<style:style style:name=\"{}\" style:family=\"table-cell\"><style:text-properties fo:font-weight=\"bold\"/></style:style>\n",
+        CellStyleKind::Header.name()
+    ));
+    styles.push_str("</office:automatic-styles>\n");
+    styles
+}
+
+/// xml for one cell, written the same way spreadsheet_ods does it
+fn cell_xml(value: &spreadsheet_ods::Value, style: Option<CellStyleKind>) -> String {
+    let style = style
+        .map(|kind| format!(r#" table:style-name="{}""#, kind.name()))
+        .unwrap_or_default();
+    match value {
+        spreadsheet_ods::Value::Empty => "<table:table-cell/>".to_string(),
+        spreadsheet_ods::Value::Text(text) => {
+            let mut cell = format!(r#"<table:table-cell{} office:value-type="string">"#, style);
+            for line in text.split('\n') {
+                cell.push_str("<text:p>");
+                cell.push_str(&xml_escape(line));
+                cell.push_str("</text:p>");
+            }
+            cell.push_str("</table:table-cell>");
+            cell
+        }
+        spreadsheet_ods::Value::Number(number) if number.is_finite() => format!(
+            r#"<table:table-cell{} office:value-type="float" office:value="{}"><text:p>{}</text:p></table:table-cell>"#,
+            style, number, number
+        ),
+        //NaN and infinity are not valid float cells
+        spreadsheet_ods::Value::Number(number) => format!(
+            r#"<table:table-cell{} office:value-type="string"><text:p>{}</text:p></table:table-cell>"#,
+            style, number
+        ),
+        spreadsheet_ods::Value::Boolean(value) => format!(
+            r#"<table:table-cell{} office:value-type="boolean" office:boolean-value="{}"><text:p>{}</text:p></table:table-cell>"#,
+            style, value, value
+        ),
+        spreadsheet_ods::Value::DateTime(datetime) => {
+            let value = datetime.format("%Y-%m-%dT%H:%M:%S%.f");
+            format!(
+                r#"<table:table-cell{} office:value-type="date" office:date-value="{}"><text:p>{}</text:p></table:table-cell>"#,
+                style, value, value
+            )
+        }
+        spreadsheet_ods::Value::TimeDuration(duration) => {
+            let seconds = duration.num_seconds();
+            let nanoseconds = (*duration - chrono::Duration::seconds(seconds))
+                .num_nanoseconds()
+                .unwrap_or_default();
+            let mut value = format!(
+                "PT{}H{}M{}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            );
+            if nanoseconds > 0 {
+                value.push_str(format!(".{:09}", nanoseconds).trim_end_matches('0'));
+            }
+            value.push('S');
+            format!(
+                r#"<table:table-cell{} office:value-type="time" office:time-value="{}"><text:p>{}</text:p></table:table-cell>"#,
+                style, value, value
+            )
+        }
+        _ => unreachable!("ods: unexpected cell value {:?}", value),
+    }
+}
+
+/// writes ods file row by row, without keeping workbook in memory
+pub struct OdsStreamWriter {
+    zip: ZipWriter<BufWriter<File>>,
+    in_table: bool,
+}
+
+impl OdsStreamWriter {
+    pub fn create(filename: &str, locale: &Locale) -> zip::result::ZipResult<OdsStreamWriter> {
+        let mut zip = ZipWriter::new(BufWriter::new(File::create(filename)?));
+        //mimetype must be the first, uncompressed entry
+        zip.start_file(
+            "mimetype",
+            SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        zip.write_all(MIMETYPE.as_bytes())?;
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file("META-INF/manifest.xml", options)?;
+        zip.write_all(MANIFEST.as_bytes())?;
+        //content.xml is the last entry, so it can grow until the file is finished
+        zip.start_file("content.xml", options)?;
+        zip.write_all(CONTENT_START.as_bytes())?;
+        zip.write_all(automatic_styles(locale).as_bytes())?;
+        zip.write_all(b"<office:body><office:spreadsheet>\n")?;
+        Ok(OdsStreamWriter {
+            zip,
+            in_table: false,
+        })
+    }
+
+    pub fn start_table(&mut self, name: &str) -> std::io::Result<()> {
+        self.end_table()?;
+        writeln!(
+            self.zip,
+            "<table:table table:name=\"{}\">",
+            xml_escape(name)
+        )?;
+        self.in_table = true;
+        Ok(())
+    }
+
+    pub fn write_row(
+        &mut self,
+        cells: &[(spreadsheet_ods::Value, Option<CellStyleKind>)],
+    ) -> std::io::Result<()> {
+        let mut row = String::from("<table:table-row>");
+        for (value, style) in cells {
+            row.push_str(&cell_xml(value, *style));
+        }
+        row.push_str("</table:table-row>\n");
+        self.zip.write_all(row.as_bytes())
+    }
+
+    fn end_table(&mut self) -> std::io::Result<()> {
+        if self.in_table {
+            self.zip.write_all(b"</table:table>\n")?;
+            self.in_table = false;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> zip::result::ZipResult<()> {
+        self.end_table()?;
+        self.zip
+            .write_all(b"</office:spreadsheet></office:body></office:document-content>\n")?;
+        self.zip.finish()?.flush()?;
+        Ok(())
+    }
+}
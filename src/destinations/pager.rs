@@ -0,0 +1,693 @@
+use std::cmp::Ordering;
+use std::io::Write;
+
+use base64::Engine;
+use chrono::{Datelike, Timelike};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::{cursor, queue, terminal};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::definitions::{ColumnType, DataSourceBatchIterator, Row, Value};
+use crate::destinations::text::TextCell;
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::theme::{Theme, ValueKind};
+
+//wider values are cut with ellipsis, whole value can still be copied
+const MAX_COLUMN_WIDTH: usize = 40;
+
+const HELP: &str = "q quit  / search  n/N next/prev  s/S sort  x hide  u unhide  c/C copy cell/row";
+
+fn text_width(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// text cut or padded to exactly width graphemes
+fn fit(text: &str, width: usize, align_right: bool) -> String {
+    let length = text_width(text);
+    if length > width {
+        if width == 0 {
+            return String::new();
+        }
+        let mut result: String = text.graphemes(true).take(width - 1).collect();
+        result.push('…');
+        return result;
+    }
+    let padding = " ".repeat(width - length);
+    if align_right {
+        padding + text
+    } else {
+        text.to_string() + &padding
+    }
+}
+
+struct PagerCell {
+    //full text, used for copying
+    text: String,
+    //single line version shown on screen
+    display: String,
+    kind: ValueKind,
+    //numbers and dates are sorted by value, not by formatted text
+    sort_key: Option<f64>,
+}
+
+fn sort_key(value: &Value) -> Option<f64> {
+    Some(match value {
+        Value::U64(value) => *value as f64,
+        Value::I64(value) => *value as f64,
+        Value::U32(value) => f64::from(*value),
+        Value::I32(value) => f64::from(*value),
+        Value::U16(value) => f64::from(*value),
+        Value::I16(value) => f64::from(*value),
+        Value::U8(value) => f64::from(*value),
+        Value::I8(value) => f64::from(*value),
+        Value::F64(value) => *value,
+        Value::F32(value) => f64::from(*value),
+        Value::Timestamp(value) => *value as f64,
+        Value::Date(date) => f64::from(date.num_days_from_ce()),
+        Value::Time(time) => {
+            f64::from(time.num_seconds_from_midnight()) + f64::from(time.nanosecond()) / 1e9
+        }
+        Value::DateTime(datetime) => {
+            datetime.and_utc().timestamp() as f64
+                + f64::from(datetime.and_utc().timestamp_subsec_nanos()) / 1e9
+        }
+        _ => return None,
+    })
+}
+
+impl PagerCell {
+    fn new(cell: TextCell, value: &Value) -> PagerCell {
+        let display = cell
+            .text
+            .replace("\r\n", "↵")
+            .replace(['\n', '\r'], "↵")
+            .replace('\t', " ");
+        PagerCell {
+            text: cell.text,
+            display,
+            kind: cell.kind,
+            sort_key: sort_key(value),
+        }
+    }
+}
+
+fn compare_cells(a: &PagerCell, b: &PagerCell) -> Ordering {
+    match (a.kind, b.kind) {
+        (ValueKind::Null, ValueKind::Null) => Ordering::Equal,
+        (ValueKind::Null, _) => Ordering::Less,
+        (_, ValueKind::Null) => Ordering::Greater,
+        _ => match (a.sort_key, b.sort_key) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => a.text.cmp(&b.text),
+        },
+    }
+}
+
+/// terminal is restored also when pager panics
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> std::io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        crossterm::execute!(
+            std::io::stdout(),
+            terminal::EnterAlternateScreen,
+            cursor::Hide
+        )?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(
+            std::io::stdout(),
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// interactive table viewer. Rows are fetched from iterator only when they are needed:
+/// scrolling down, searching, sorting or jumping to the end
+pub struct Pager {
+    column_names: Vec<String>,
+    column_types: Vec<ColumnType>,
+    widths: Vec<usize>,
+    rows: Vec<Vec<PagerCell>>,
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    null_marker: String,
+    theme: Option<Theme>,
+    exhausted: bool,
+    hidden: Vec<bool>,
+    //first displayed row
+    top: usize,
+    //first displayed column after frozen one, index into visible columns
+    left: usize,
+    cursor_row: usize,
+    //index into visible columns
+    cursor_column: usize,
+    search: Option<String>,
+    //search being typed
+    input: Option<String>,
+    message: String,
+    width: usize,
+    height: usize,
+}
+
+impl Pager {
+    pub fn new(
+        column_names: Vec<String>,
+        column_types: Vec<ColumnType>,
+        truncate: Option<u64>,
+        formatter: ValueFormatter,
+        theme: Option<Theme>,
+    ) -> Pager {
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        Pager {
+            widths: column_names
+                .iter()
+                .map(|name| text_width(name).clamp(1, MAX_COLUMN_WIDTH))
+                .collect(),
+            hidden: vec![false; column_names.len()],
+            column_names,
+            column_types,
+            rows: vec![],
+            truncate,
+            formatter,
+            null_marker: theme
+                .as_ref()
+                .map(|theme| theme.null_marker.clone())
+                .unwrap_or("NULL".to_string()),
+            theme,
+            exhausted: false,
+            top: 0,
+            left: 0,
+            cursor_row: 0,
+            cursor_column: 0,
+            search: None,
+            input: None,
+            message: String::new(),
+            width: width as usize,
+            height: height as usize,
+        }
+    }
+
+    pub fn add_rows(&mut self, rows: &[Row]) {
+        for row in rows {
+            let cells: Vec<PagerCell> = row
+                .iter()
+                .zip(&self.column_types)
+                .map(|(value, data_type)| {
+                    PagerCell::new(
+                        TextCell::from_value(
+                            value,
+                            data_type,
+                            self.truncate,
+                            &self.formatter,
+                            Some(&self.null_marker),
+                        ),
+                        value,
+                    )
+                })
+                .collect();
+            for (idx, cell) in cells.iter().enumerate() {
+                self.widths[idx] =
+                    self.widths[idx].max(text_width(&cell.display).min(MAX_COLUMN_WIDTH));
+            }
+            self.rows.push(cells);
+        }
+    }
+
+    /// fetch rows until there is at least count of them or iterator is exhausted
+    fn load(&mut self, iterator: &mut dyn DataSourceBatchIterator, count: usize) {
+        while !self.exhausted && self.rows.len() < count {
+            match iterator.next() {
+                Some(rows) => self.add_rows(&rows),
+                None => self.exhausted = true,
+            }
+        }
+    }
+
+    fn load_all(&mut self, iterator: &mut dyn DataSourceBatchIterator) {
+        self.load(iterator, usize::MAX);
+    }
+
+    fn page_size(&self) -> usize {
+        //header and status line
+        self.height.saturating_sub(2).max(1)
+    }
+
+    fn visible_columns(&self) -> Vec<usize> {
+        (0..self.column_names.len())
+            .filter(|idx| !self.hidden[*idx])
+            .collect()
+    }
+
+    fn is_right_aligned(&self, column: usize) -> bool {
+        self.rows
+            .iter()
+            .take(100)
+            .map(|row| row[column].kind)
+            .filter(|kind| *kind != ValueKind::Null)
+            .all(|kind| kind == ValueKind::Number)
+    }
+
+    /// keep cursor on screen
+    fn scroll_to_cursor(&mut self) {
+        let page_size = self.page_size();
+        if self.cursor_row < self.top {
+            self.top = self.cursor_row;
+        } else if self.cursor_row >= self.top + page_size {
+            self.top = self.cursor_row + 1 - page_size;
+        }
+        let visible = self.visible_columns();
+        self.cursor_column = self.cursor_column.min(visible.len().saturating_sub(1));
+        if self.cursor_column == 0 {
+            return;
+        }
+        let position = self.cursor_column - 1;
+        if position < self.left {
+            self.left = position;
+        }
+        let available = self.width.saturating_sub(self.widths[visible[0]] + 1);
+        let scrolled = &visible[1..];
+        while self.left < position
+            && scrolled[self.left..=position]
+                .iter()
+                .map(|idx| self.widths[*idx] + 1)
+                .sum::<usize>()
+                > available
+        {
+            self.left += 1;
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write) -> std::io::Result<()> {
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        let visible = self.visible_columns();
+        let mut columns = vec![visible[0]];
+        columns.extend(visible.iter().skip(1 + self.left));
+        let align: Vec<bool> = columns
+            .iter()
+            .map(|idx| self.is_right_aligned(*idx))
+            .collect();
+
+        //header
+        queue!(out, cursor::MoveTo(0, 0))?;
+        let mut used = 0;
+        for (position, idx) in columns.iter().enumerate() {
+            if used >= self.width {
+                break;
+            }
+            let width = self.widths[*idx].min(self.width - used);
+            let text = fit(&self.column_names[*idx], width, align[position]);
+            match &self.theme {
+                Some(theme) => queue!(out, Print(Theme::paint(&text, &theme.header)))?,
+                None => queue!(
+                    out,
+                    SetAttribute(Attribute::Bold),
+                    Print(text),
+                    SetAttribute(Attribute::Reset)
+                )?,
+            }
+            used += width;
+            if used < self.width {
+                queue!(out, Print(if position == 0 { "║" } else { "│" }))?;
+                used += 1;
+            }
+        }
+
+        //rows
+        let cursor_column = visible[self.cursor_column];
+        for (line, row_idx) in (self.top..self.rows.len())
+            .take(self.page_size())
+            .enumerate()
+        {
+            queue!(out, cursor::MoveTo(0, line as u16 + 1))?;
+            let is_cursor_row = row_idx == self.cursor_row;
+            if is_cursor_row {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            let mut used = 0;
+            for (position, idx) in columns.iter().enumerate() {
+                if used >= self.width {
+                    break;
+                }
+                let width = self.widths[*idx].min(self.width - used);
+                let cell = &self.rows[row_idx][*idx];
+                let text = fit(&cell.display, width, align[position]);
+                if is_cursor_row {
+                    if *idx == cursor_column {
+                        queue!(
+                            out,
+                            SetAttribute(Attribute::Bold),
+                            SetAttribute(Attribute::Underlined),
+                            Print(text),
+                            SetAttribute(Attribute::NormalIntensity),
+                            SetAttribute(Attribute::NoUnderline)
+                        )?;
+                    } else {
+                        queue!(out, Print(text))?;
+                    }
+                } else {
+                    match &self.theme {
+                        Some(theme) => {
+                            queue!(out, Print(Theme::paint(&text, theme.style_for(cell.kind))))?
+                        }
+                        None => queue!(out, Print(text))?,
+                    }
+                }
+                used += width;
+                if used < self.width {
+                    queue!(out, Print(if position == 0 { "║" } else { "│" }))?;
+                    used += 1;
+                }
+            }
+            if is_cursor_row {
+                queue!(out, SetAttribute(Attribute::Reset))?;
+            }
+        }
+
+        //status line
+        let status = match &self.input {
+            Some(input) => format!("/{}", input),
+            None => {
+                let position = format!(
+                    "row {}/{}{}  {}",
+                    if self.rows.is_empty() {
+                        0
+                    } else {
+                        self.cursor_row + 1
+                    },
+                    self.rows.len(),
+                    if self.exhausted { "" } else { "+" },
+                    self.column_names[cursor_column]
+                );
+                if self.message.is_empty() {
+                    format!("{}  | {}", position, HELP)
+                } else {
+                    format!("{}  | {}", position, self.message)
+                }
+            }
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, self.height.saturating_sub(1) as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(fit(&status, self.width, false)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()
+    }
+
+    /// copy text to clipboard with OSC 52 escape sequence, supported by most terminal emulators
+    fn copy(&mut self, text: &str, what: &str) {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "\x1b]52;c;{}\x07", encoded);
+        let _ = stdout.flush();
+        self.message = format!("copied {}", what);
+    }
+
+    fn matches(&self, row: usize, column: usize, search: &str) -> bool {
+        self.rows[row][column].text.to_lowercase().contains(search)
+    }
+
+    /// move cursor to next (or previous) cell containing searched text
+    fn find(&mut self, iterator: &mut dyn DataSourceBatchIterator, forward: bool) {
+        let Some(search) = self.search.as_ref().map(|search| search.to_lowercase()) else {
+            self.message = "nothing to search for".to_string();
+            return;
+        };
+        let visible = self.visible_columns();
+        let (mut row, mut column) = (self.cursor_row, self.cursor_column);
+        loop {
+            if forward {
+                column += 1;
+                if column == visible.len() {
+                    column = 0;
+                    row += 1;
+                }
+                self.load(iterator, row + 1);
+                if row >= self.rows.len() {
+                    break;
+                }
+            } else {
+                if column == 0 {
+                    if row == 0 {
+                        break;
+                    }
+                    row -= 1;
+                    column = visible.len();
+                }
+                column -= 1;
+            }
+            if self.matches(row, visible[column], &search) {
+                self.cursor_row = row;
+                self.cursor_column = column;
+                self.message.clear();
+                return;
+            }
+        }
+        self.message = format!("pattern not found: {}", search);
+    }
+
+    fn sort(&mut self, iterator: &mut dyn DataSourceBatchIterator, descending: bool) {
+        self.load_all(iterator);
+        let column = self.visible_columns()[self.cursor_column];
+        self.rows.sort_by(|a, b| {
+            let ordering = compare_cells(&a[column], &b[column]);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.message = format!(
+            "sorted by {} {}",
+            self.column_names[column],
+            if descending {
+                "descending"
+            } else {
+                "ascending"
+            }
+        );
+    }
+
+    fn move_rows(&mut self, iterator: &mut dyn DataSourceBatchIterator, delta: isize) {
+        let target = (self.cursor_row as isize + delta).max(0) as usize;
+        self.load(iterator, target + 1);
+        self.cursor_row = target.min(self.rows.len().saturating_sub(1));
+    }
+
+    /// returns false when pager should quit
+    fn handle_key(&mut self, iterator: &mut dyn DataSourceBatchIterator, key: KeyEvent) -> bool {
+        if let Some(input) = self.input.as_mut() {
+            match key.code {
+                KeyCode::Esc => self.input = None,
+                KeyCode::Enter => {
+                    self.search = self.input.take().filter(|input| !input.is_empty());
+                    self.find(iterator, true);
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        self.message.clear();
+        let page_size = self.page_size() as isize;
+        let visible_count = self.visible_columns().len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => self.move_rows(iterator, 1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_rows(iterator, -1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.move_rows(iterator, page_size),
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.move_rows(iterator, page_size)
+            }
+            KeyCode::PageUp | KeyCode::Char('b') => self.move_rows(iterator, -page_size),
+            KeyCode::Home | KeyCode::Char('g') => self.cursor_row = 0,
+            KeyCode::End | KeyCode::Char('G') => {
+                self.load_all(iterator);
+                self.cursor_row = self.rows.len().saturating_sub(1);
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.cursor_column = self.cursor_column.saturating_sub(1)
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.cursor_column = (self.cursor_column + 1).min(visible_count - 1)
+            }
+            KeyCode::Char('/') => self.input = Some(String::new()),
+            KeyCode::Char('n') => self.find(iterator, true),
+            KeyCode::Char('N') => self.find(iterator, false),
+            KeyCode::Char('s') => self.sort(iterator, false),
+            KeyCode::Char('S') => self.sort(iterator, true),
+            KeyCode::Char('x') => {
+                if visible_count == 1 {
+                    self.message = "can't hide last column".to_string();
+                } else {
+                    let column = self.visible_columns()[self.cursor_column];
+                    self.hidden[column] = true;
+                    self.left = self.left.min(visible_count.saturating_sub(2));
+                    self.message = format!("hidden {}", self.column_names[column]);
+                }
+            }
+            KeyCode::Char('u') => {
+                self.hidden.iter_mut().for_each(|hidden| *hidden = false);
+                self.message = "all columns shown".to_string();
+            }
+            KeyCode::Char('c') if !self.rows.is_empty() => {
+                let column = self.visible_columns()[self.cursor_column];
+                let text = self.rows[self.cursor_row][column].text.clone();
+                self.copy(&text, "cell");
+            }
+            KeyCode::Char('C') if !self.rows.is_empty() => {
+                let text = self
+                    .visible_columns()
+                    .iter()
+                    .map(|column| self.rows[self.cursor_row][*column].text.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\t");
+                self.copy(&text, "row");
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// show rows until user quits, returns number of rows fetched from iterator
+    pub fn run(&mut self, iterator: &mut dyn DataSourceBatchIterator) -> std::io::Result<usize> {
+        let _guard = TerminalGuard::enter()?;
+        let mut stdout = std::io::stdout();
+        loop {
+            self.load(iterator, self.top + self.page_size());
+            self.scroll_to_cursor();
+            self.draw(&mut stdout)?;
+            match event::read()? {
+                Event::Key(key)
+                    if key.kind == KeyEventKind::Press && !self.handle_key(iterator, key) =>
+                {
+                    return Ok(self.rows.len());
+                }
+                Event::Resize(width, height) => {
+                    self.width = width as usize;
+                    self.height = height as usize;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::RowsIterator;
+
+    fn pager(rows: Vec<Row>) -> (Pager, RowsIterator) {
+        let pager = Pager::new(
+            vec!["id".to_string(), "name".to_string()],
+            vec![ColumnType::I64, ColumnType::String],
+            None,
+            ValueFormatter {
+                thousands_separator: Some(",".to_string()),
+                ..ValueFormatter::default()
+            },
+            None,
+        );
+        let iterator = RowsIterator::new(
+            &[("id", ColumnType::I64), ("name", ColumnType::String)],
+            rows.chunks(2).map(|chunk| chunk.to_vec()).collect(),
+        );
+        (pager, iterator)
+    }
+
+    fn row(id: Option<i64>, name: &str) -> Row {
+        vec![
+            id.map(Value::I64).unwrap_or(Value::None),
+            Value::String(name.to_string()),
+        ]
+    }
+
+    fn key(pager: &mut Pager, iterator: &mut RowsIterator, code: KeyCode) -> bool {
+        pager.handle_key(iterator, KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn column(pager: &Pager, column: usize) -> Vec<&str> {
+        pager
+            .rows
+            .iter()
+            .map(|row| row[column].text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_cells() {
+        let (mut pager, _) = pager(vec![]);
+        pager.add_rows(&[row(Some(1000), "b"), row(Some(20), "a"), row(None, "")]);
+        let cells: Vec<&PagerCell> = pager.rows.iter().map(|row| &row[0]).collect();
+        //"1,000" sorts before "20" as text
+        assert_eq!(compare_cells(cells[0], cells[1]), Ordering::Greater);
+        assert_eq!(compare_cells(cells[2], cells[1]), Ordering::Less);
+        assert_eq!(compare_cells(cells[2], cells[2]), Ordering::Equal);
+        assert_eq!(
+            compare_cells(&pager.rows[0][1], &pager.rows[1][1]),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_sort() {
+        let (mut pager, mut iterator) = pager(vec![
+            row(Some(1000), "c"),
+            row(None, "d"),
+            row(Some(-5), "a"),
+            row(Some(20), "b"),
+        ]);
+        key(&mut pager, &mut iterator, KeyCode::Char('s'));
+        assert!(pager.exhausted);
+        assert_eq!(column(&pager, 0), vec!["NULL", "-5", "20", "1,000"]);
+        key(&mut pager, &mut iterator, KeyCode::Char('l'));
+        key(&mut pager, &mut iterator, KeyCode::Char('S'));
+        assert_eq!(column(&pager, 1), vec!["d", "c", "b", "a"]);
+        assert_eq!(pager.message, "sorted by name descending");
+    }
+
+    #[test]
+    fn test_search() {
+        let (mut pager, mut iterator) = pager(vec![
+            row(Some(1), "Apple"),
+            row(Some(2), "pear"),
+            row(Some(3), "pineapple"),
+        ]);
+        pager.search = Some("APPLE".to_string());
+        pager.find(&mut iterator, true);
+        assert_eq!((pager.cursor_row, pager.cursor_column), (0, 1));
+        pager.find(&mut iterator, true);
+        assert_eq!((pager.cursor_row, pager.cursor_column), (2, 1));
+        pager.find(&mut iterator, true);
+        assert_eq!(pager.message, "pattern not found: apple");
+        pager.find(&mut iterator, false);
+        assert_eq!((pager.cursor_row, pager.cursor_column), (0, 1));
+    }
+
+    #[test]
+    fn test_hide() {
+        let (mut pager, mut iterator) = pager(vec![row(Some(1), "a")]);
+        key(&mut pager, &mut iterator, KeyCode::Char('x'));
+        assert_eq!(pager.visible_columns(), vec![1]);
+        key(&mut pager, &mut iterator, KeyCode::Char('x'));
+        assert_eq!(pager.message, "can't hide last column");
+        key(&mut pager, &mut iterator, KeyCode::Char('u'));
+        assert_eq!(pager.visible_columns(), vec![0, 1]);
+        assert!(!key(&mut pager, &mut iterator, KeyCode::Char('q')));
+    }
+}
@@ -2,345 +2,495 @@ use std::fs::File;
 use std::path::Path;
 use std::sync::Arc;
 
-use arrow::array::temporal_conversions::time_to_time64ns;
-use arrow::array::types::Date32Type;
-use arrow::array::{
-    Array, BinaryBuilder, BooleanBuilder, Date32Builder, Float32Builder, Float64Builder,
-    Int16Builder, Int32Builder, Int64Builder, Int8Builder, StringBuilder, StructArray,
-    Time64NanosecondBuilder, TimestampSecondBuilder, UInt16Builder, UInt32Builder, UInt64Builder,
-    UInt8Builder,
-};
-use arrow::datatypes::DataType;
-use arrow::datatypes::{Field, Fields, Schema, SchemaBuilder, TimeUnit};
-use parquet::arrow::ArrowWriter as ParquetWriter;
-//use parquet::basic::Encoding;
-//use parquet::errors::Result;
-use parquet::file::properties::WriterProperties;
+use arrow::array::ArrayRef;
+use arrow::datatypes::Schema;
+use chrono::{SecondsFormat, Utc};
+use parquet::arrow::arrow_to_parquet_schema;
+use parquet::arrow::arrow_writer::{compute_leaves, get_column_writers, ArrowColumnWriter};
+use parquet::basic::{BrotliLevel, Compression, GzipLevel, LogicalType, ZstdLevel};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::{ColumnPath, SchemaDescriptor, Type};
 
-use crate::commands::export::ParquetDestinationOptions;
-use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row, Value};
-use crate::utils::truncate_text_with_note;
+use crate::commands::export::{ParquetCompression, ParquetDestinationOptions, ParquetStatistics};
+use crate::definitions::{ColumnInfo, ColumnType, DataDestination, DataSourceBatchIterator, Row};
+use crate::destinations::arrow::{column_info_to_schema, rows_to_arrays, ArrowConversion};
+
+/// file writer with column writers of current row group.
+/// ArrowWriter can't annotate columns with JSON logical type, so row groups are assembled here
+struct ParquetOutput {
+    writer: SerializedFileWriter<File>,
+    parquet_schema: SchemaDescriptor,
+    properties: Arc<WriterProperties>,
+    column_writers: Vec<ArrowColumnWriter>,
+    row_group_rows: usize,
+}
+
+impl ParquetOutput {
+    fn write(&mut self, schema: &Schema, arrays: &[ArrayRef]) {
+        for ((writer, field), array) in self
+            .column_writers
+            .iter_mut()
+            .zip(schema.fields().iter())
+            .zip(arrays.iter())
+        {
+            for leaf in compute_leaves(field, array).unwrap() {
+                writer.write(&leaf).unwrap();
+            }
+        }
+    }
+
+    fn flush_row_group(&mut self, schema: &Arc<Schema>) {
+        let column_writers = std::mem::replace(
+            &mut self.column_writers,
+            get_column_writers(&self.parquet_schema, &self.properties, schema).unwrap(),
+        );
+        let mut row_group = self.writer.next_row_group().unwrap();
+        for column_writer in column_writers {
+            column_writer
+                .close()
+                .unwrap()
+                .append_to_row_group(&mut row_group)
+                .unwrap();
+        }
+        row_group.close().unwrap();
+        self.row_group_rows = 0;
+    }
+}
+
+/// parquet schema for given arrow schema, with JSON columns annotated as such
+fn parquet_schema(schema: &Schema, columns: &[ColumnInfo]) -> SchemaDescriptor {
+    let root = arrow_to_parquet_schema(schema).unwrap().root_schema_ptr();
+    let fields = root
+        .get_fields()
+        .iter()
+        .zip(columns.iter())
+        .map(|(field, column)| match column.data_type {
+            ColumnType::JSON => Arc::new(
+                Type::primitive_type_builder(field.name(), field.get_physical_type())
+                    .with_repetition(field.get_basic_info().repetition())
+                    .with_logical_type(Some(LogicalType::Json))
+                    .build()
+                    .unwrap(),
+            ),
+            _ => field.clone(),
+        })
+        .collect();
+    SchemaDescriptor::new(Arc::new(
+        Type::group_type_builder(root.name())
+            .with_fields(fields)
+            .build()
+            .unwrap(),
+    ))
+}
 
 pub struct ParquetDestination {
-    truncate: Option<u64>,
+    conversion: ArrowConversion,
     filename: String,
-    writer: Option<ParquetWriter<File>>,
+    options: ParquetDestinationOptions,
+    query: Option<String>,
+    output: Option<ParquetOutput>,
     schema: Option<Arc<Schema>>,
 }
 
 impl ParquetDestination {
-    pub fn init(parquet_options: &ParquetDestinationOptions) -> ParquetDestination {
+    pub fn init(
+        parquet_options: &ParquetDestinationOptions,
+        query: Option<String>,
+    ) -> ParquetDestination {
+        //check options early, before anything is queried
+        compression(parquet_options);
+        if parquet_options.max_row_group_size == Some(0) {
+            eprintln!("parquet: --max-row-group-size must be greater than 0");
+            std::process::exit(1);
+        }
+        let conversion = ArrowConversion {
+            decimal_precision: parquet_options.decimal_precision,
+            decimal_scale: parquet_options.decimal_scale,
+            coerce: parquet_options.coerce,
+            strict: parquet_options.strict,
+            ..ArrowConversion::new("parquet", parquet_options.truncate)
+        };
+        conversion.validate();
         let path = Path::new(&parquet_options.filename);
         if path.exists() {
             std::fs::remove_file(path).unwrap();
         }
         ParquetDestination {
             filename: parquet_options.filename.clone(),
-            truncate: parquet_options.truncate,
+            conversion,
+            options: parquet_options.clone(),
+            query,
             schema: None,
-            writer: None,
+            output: None,
+        }
+    }
+
+    fn writer_properties(&self) -> WriterProperties {
+        let options = &self.options;
+        let mut builder = WriterProperties::builder()
+            .set_compression(compression(options))
+            .set_dictionary_enabled(!options.no_dictionary)
+            .set_statistics_enabled(match options.statistics {
+                ParquetStatistics::None => EnabledStatistics::None,
+                ParquetStatistics::Chunk => EnabledStatistics::Chunk,
+                ParquetStatistics::Page => EnabledStatistics::Page,
+            });
+        if let Some(size) = options.max_row_group_size {
+            builder = builder.set_max_row_group_size(size);
+        }
+        if let Some(size) = options.data_page_size {
+            builder = builder.set_data_page_size_limit(size);
+        }
+        for name in options.dictionary_columns.iter() {
+            builder = builder.set_column_dictionary_enabled(ColumnPath::from(name.as_str()), true);
+        }
+        for name in options.no_dictionary_columns.iter() {
+            builder = builder.set_column_dictionary_enabled(ColumnPath::from(name.as_str()), false);
         }
+        let mut metadata: Vec<KeyValue> = options
+            .metadata
+            .iter()
+            .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+            .collect();
+        if options.source_metadata {
+            if let Some(query) = &self.query {
+                metadata.push(KeyValue::new("dbfish.query".to_string(), query.clone()));
+            }
+            metadata.push(KeyValue::new(
+                "dbfish.exported_at".to_string(),
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ));
+        }
+        if !metadata.is_empty() {
+            builder = builder.set_key_value_metadata(Some(metadata));
+        }
+        builder.build()
     }
 }
 
+/// codec with optional level, exits on level not supported by the codec
+fn compression(options: &ParquetDestinationOptions) -> Compression {
+    let level = options.compression_level;
+    let result = match options.compression {
+        ParquetCompression::None | ParquetCompression::Snappy | ParquetCompression::Lz4
+            if level.is_some() =>
+        {
+            eprintln!("parquet: --compression-level is not supported by this codec");
+            std::process::exit(1);
+        }
+        ParquetCompression::None => Ok(Compression::UNCOMPRESSED),
+        ParquetCompression::Snappy => Ok(Compression::SNAPPY),
+        ParquetCompression::Lz4 => Ok(Compression::LZ4_RAW),
+        ParquetCompression::Gzip => {
+            GzipLevel::try_new(level.unwrap_or(GzipLevel::default().compression_level()))
+                .map(Compression::GZIP)
+        }
+        ParquetCompression::Zstd => ZstdLevel::try_new(
+            level.map_or(ZstdLevel::default().compression_level(), |level| {
+                level as i32
+            }),
+        )
+        .map(Compression::ZSTD),
+        ParquetCompression::Brotli => {
+            BrotliLevel::try_new(level.unwrap_or(BrotliLevel::default().compression_level()))
+                .map(Compression::BROTLI)
+        }
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("parquet: {}", e);
+        std::process::exit(1);
+    })
+}
+
 impl DataDestination for ParquetDestination {
     fn prepare(&mut self) {}
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
-        let mut schema_builder = SchemaBuilder::new();
-        for col in result_iterator.get_column_info().iter() {
-            match col.data_type {
-                ColumnType::U64 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::UInt64, true))
-                }
-                ColumnType::I64 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Int64, true))
-                }
-                ColumnType::U32 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::UInt32, true))
-                }
-                ColumnType::I32 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Int32, true))
-                }
-                ColumnType::U16 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::UInt16, true))
-                }
-                ColumnType::I16 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Int16, true))
-                }
-                ColumnType::U8 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::UInt8, true))
-                }
-                ColumnType::I8 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Int8, true))
-                }
-                //Utf8 is 32bit, LargeUtf8 64bit
-                ColumnType::String => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Utf8, true))
-                }
-                ColumnType::Bytes => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Binary, true))
-                }
-                ColumnType::F64 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Float64, true))
-                }
-                ColumnType::F32 => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Float32, true))
-                }
-                ColumnType::Bool => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Boolean, true))
-                }
-
-                ColumnType::DateTime => schema_builder.push(Field::new(
-                    col.name.clone(),
-                    DataType::Timestamp(TimeUnit::Second, None),
-                    true,
-                )),
-                ColumnType::Date => {
-                    schema_builder.push(Field::new(col.name.clone(), DataType::Date32, true))
-                }
-                ColumnType::Time => schema_builder.push(Field::new(
-                    col.name.clone(),
-                    DataType::Time32(TimeUnit::Second),
-                    true,
-                )),
-                //ColumnType::Decimal => self.schema.fields.push(DataType::Decimal123(u8,i8)),
-                _ => panic!("parquet: unsupported column type: {:?}", col.data_type),
+        let columns = result_iterator.get_column_info();
+        for name in self
+            .options
+            .dictionary_columns
+            .iter()
+            .chain(self.options.no_dictionary_columns.iter())
+        {
+            if !columns.iter().any(|column| &column.name == name) {
+                eprintln!("parquet: unknown dictionary column: {}", name);
+                std::process::exit(1);
             }
         }
-
-        let writer_properties_builder = WriterProperties::builder();
-        let writer_properties = writer_properties_builder.build();
-
-        let schema = Arc::new(schema_builder.finish());
-        self.schema = Some(schema.clone());
+        let properties = Arc::new(self.writer_properties());
+        let schema = Arc::new(column_info_to_schema(&columns, &self.conversion));
+        let parquet_schema = parquet_schema(&schema, &columns);
         let file = File::create(&self.filename).unwrap();
-        let writer = ParquetWriter::try_new(file, schema, Some(writer_properties)).unwrap();
-
-        self.writer = Some(writer);
+        let writer =
+            SerializedFileWriter::new(file, parquet_schema.root_schema_ptr(), properties.clone())
+                .unwrap();
+        self.output = Some(ParquetOutput {
+            writer,
+            column_writers: get_column_writers(&parquet_schema, &properties, &schema).unwrap(),
+            parquet_schema,
+            properties,
+            row_group_rows: 0,
+        });
+        self.schema = Some(schema);
     }
 
     fn add_rows(&mut self, rows: &[Row]) {
-        let mut fields = vec![];
-        let mut arrays: Vec<Arc<dyn Array>> = vec![];
+        let schema = self.schema.as_ref().unwrap();
+        let output = self.output.as_mut().unwrap();
+        let max_row_group_size = output.properties.max_row_group_size();
+        let mut rows = rows;
+        while !rows.is_empty() {
+            let (chunk, rest) =
+                rows.split_at(rows.len().min(max_row_group_size - output.row_group_rows));
+            output.write(schema, &rows_to_arrays(schema, chunk, &self.conversion));
+            output.row_group_rows += chunk.len();
+            if output.row_group_rows == max_row_group_size {
+                output.flush_row_group(schema);
+            }
+            rows = rest;
+        }
+    }
 
-        for (col_idx, field) in (&self.schema.as_ref().unwrap().fields)
-            .into_iter()
-            .enumerate()
-        {
-            match field.data_type() {
-                DataType::UInt64 => {
-                    let mut array = UInt64Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::U64(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Int64 => {
-                    let mut array = Int64Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::I64(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
+    fn close(&mut self) {
+        let mut output = self.output.take().unwrap();
+        if output.row_group_rows > 0 {
+            output.flush_row_group(self.schema.as_ref().unwrap());
+        }
+        output.writer.close().unwrap();
+    }
+}
 
-                DataType::UInt32 => {
-                    let mut array = UInt32Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::U32(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Int32 => {
-                    let mut array = Int32Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::I32(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::UInt16 => {
-                    let mut array = UInt16Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::U16(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Int16 => {
-                    let mut array = Int16Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::I16(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                }
-                DataType::UInt8 => {
-                    let mut array = UInt8Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::U8(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Int8 => {
-                    let mut array = Int8Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::I8(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Float64 => {
-                    let mut array = Float64Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::F64(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Float32 => {
-                    let mut array = Float32Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::F32(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Boolean => {
-                    let mut array = BooleanBuilder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::Bool(v) = row[col_idx] {
-                            array.append_value(v);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Utf8 => {
-                    let mut array = StringBuilder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::String(ref s) = row[col_idx] {
-                            array.append_value(truncate_text_with_note(s.clone(), self.truncate));
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::Value;
+    use crate::utils::testing::{exits_with, export, temp_path};
+    use arrow::array::{
+        Array, Decimal128Array, StringArray, TimestampMicrosecondArray, UInt64Array,
+    };
+    use arrow::record_batch::RecordBatch;
+    use clap::Parser;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 
-                DataType::Timestamp(_unit, _optional_timezone) => {
-                    //FIXME: handle unit and timezone. For now it's only seconds
-                    let mut array = TimestampSecondBuilder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::Timestamp(t) = row[col_idx] {
-                            array.append_value(t as i64);
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
+    const COLUMNS: [(&str, ColumnType); 6] = [
+        ("number", ColumnType::U64),
+        ("amount", ColumnType::Decimal),
+        ("ts", ColumnType::Timestamp),
+        ("doc", ColumnType::JSON),
+        ("nothing", ColumnType::None),
+        ("address", ColumnType::Custom(String::new())),
+    ];
 
-                DataType::Date32 => {
-                    let mut array = Date32Builder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::Date(ref d) = row[col_idx] {
-                            array.append_value(Date32Type::from_naive_date(*d));
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
+    fn export_parquet(filename: &str, args: &[&str], rows: Vec<Row>) {
+        let options = ParquetDestinationOptions::try_parse_from(
+            [
+                "parquet",
+                filename,
+                "--decimal-precision",
+                "10",
+                "--decimal-scale",
+                "2",
+            ]
+            .iter()
+            .chain(args.iter()),
+        )
+        .unwrap();
+        export(
+            &mut ParquetDestination::init(&options, None),
+            &COLUMNS,
+            vec![rows],
+        );
+    }
 
-                DataType::Time32(_unit) => {
-                    //FIXME: assuming unit is second for now,
-                    let mut array = Time64NanosecondBuilder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::Time(t) = row[col_idx] {
-                            array.append_value(time_to_time64ns(t));
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
-                DataType::Binary => {
-                    //FIXME: assuming unit is second for now,
-                    let mut array = BinaryBuilder::new();
-                    rows.iter().for_each(|row| {
-                        if let Value::Bytes(ref b) = row[col_idx] {
-                            array.append_value(b.clone());
-                        } else {
-                            array.append_null();
-                        }
-                    });
-                    fields.push(field.clone());
-                    arrays.push(Arc::new(array.finish()));
-                }
+    fn row(number: Value, amount: Value) -> Row {
+        vec![
+            number,
+            amount,
+            Value::Timestamp(1),
+            Value::JSON("{}".to_string()),
+            Value::None,
+            Value::Custom("::1".to_string()),
+        ]
+    }
 
-                _ => panic!("Parquet: unsupported data type{}", field.data_type()),
-            }
-        }
+    fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> &'a T {
+        batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<T>()
+            .unwrap()
+    }
 
-        self.writer
-            .as_mut()
+    #[test]
+    fn test_round_trip() {
+        let filename = temp_path("export.parquet");
+        export_parquet(
+            &filename,
+            &["--coerce", "--max-row-group-size", "2"],
+            vec![
+                row(Value::U64(5), Value::String("-1.5".to_string())),
+                //coerced, or NULL when value doesn't fit
+                row(Value::I64(6), Value::F64(0.25)),
+                row(Value::I64(-5), Value::F64(0.125)),
+                vec![Value::None; 6],
+            ],
+        );
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&filename).unwrap()).unwrap();
+        let metadata = builder.metadata().clone();
+        assert_eq!(metadata.num_row_groups(), 2);
+        assert_eq!(
+            metadata
+                .file_metadata()
+                .schema_descr()
+                .column(3)
+                .logical_type(),
+            Some(LogicalType::Json)
+        );
+        let batches = builder
+            .build()
             .unwrap()
-            .write(&StructArray::new(Fields::from(fields), arrays, None).into())
+            .collect::<Result<Vec<RecordBatch>, _>>()
             .unwrap();
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 4);
+
+        let numbers = column::<UInt64Array>(&batch, "number");
+        assert_eq!(
+            (0..4)
+                .map(|idx| numbers.is_valid(idx).then(|| numbers.value(idx)))
+                .collect::<Vec<_>>(),
+            [Some(5), Some(6), None, None]
+        );
+        let amounts = column::<Decimal128Array>(&batch, "amount");
+        assert_eq!(
+            (0..4)
+                .map(|idx| amounts.is_valid(idx).then(|| amounts.value(idx)))
+                .collect::<Vec<_>>(),
+            [Some(-150), Some(25), None, None]
+        );
+        let timestamps = column::<TimestampMicrosecondArray>(&batch, "ts");
+        assert_eq!(timestamps.value(0), 1_000_000);
+        assert!(timestamps.is_null(3));
+        assert_eq!(column::<StringArray>(&batch, "doc").value(0), "{}");
+        assert_eq!(column::<StringArray>(&batch, "address").value(0), "::1");
+        let nothing = batch.column_by_name("nothing").unwrap();
+        assert_eq!(nothing.data_type(), &arrow::datatypes::DataType::Null);
+        assert_eq!(nothing.len(), 4);
+        std::fs::remove_file(&filename).unwrap();
     }
 
-    fn close(&mut self) {
-        let mut writer = self.writer.take().unwrap();
-        writer.flush().unwrap();
-        writer.close().unwrap();
+    #[test]
+    fn test_without_coerce() {
+        let filename = temp_path("no-coerce.parquet");
+        export_parquet(
+            &filename,
+            &[],
+            vec![row(Value::I64(6), Value::String("1".to_string()))],
+        );
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&filename).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(column::<UInt64Array>(&batch, "number").is_null(0));
+        assert_eq!(column::<Decimal128Array>(&batch, "amount").value(0), 100);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_strict() {
+        let stderr = exits_with(module_path!(), "test_strict", || {
+            export_parquet(
+                &temp_path("strict.parquet"),
+                &["--coerce", "--strict"],
+                vec![row(Value::I64(-5), Value::None)],
+            );
+        });
+        assert!(
+            stderr.contains("parquet: can't convert value I64(-5) of column number to UInt64"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn test_writer_options() {
+        let filename = temp_path("options.parquet");
+        let options = ParquetDestinationOptions::try_parse_from([
+            "parquet",
+            filename.as_str(),
+            "--compression",
+            "zstd",
+            "--compression-level",
+            "5",
+            "--no-dictionary",
+            "--dictionary-column",
+            "address",
+            "--statistics",
+            "none",
+            "--metadata",
+            "team=data",
+            "--source-metadata",
+        ])
+        .unwrap();
+        export(
+            &mut ParquetDestination::init(&options, Some("select 1".to_string())),
+            &COLUMNS,
+            vec![vec![row(Value::U64(1), Value::None); 3]],
+        );
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&filename).unwrap()).unwrap();
+        let metadata = builder.metadata();
+        let key_value: Vec<(String, Option<String>)> = metadata
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.key != "ARROW:schema")
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect();
+        assert_eq!(key_value.len(), 3, "{:?}", key_value);
+        assert_eq!(key_value[0], ("team".to_string(), Some("data".to_string())));
+        assert_eq!(
+            key_value[1],
+            ("dbfish.query".to_string(), Some("select 1".to_string()))
+        );
+        assert_eq!(key_value[2].0, "dbfish.exported_at");
+        let row_group = metadata.row_group(0);
+        for column in row_group.columns() {
+            assert!(matches!(column.compression(), Compression::ZSTD(_)));
+            assert!(column.statistics().is_none());
+            //only address column is dictionary encoded
+            assert_eq!(
+                column.dictionary_page_offset().is_some(),
+                column.column_path().string() == "address",
+                "{}",
+                column.column_path()
+            );
+        }
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let stderr = exits_with(module_path!(), "test_invalid_options", || {
+            export_parquet(
+                &temp_path("invalid.parquet"),
+                &["--compression", "snappy", "--compression-level", "3"],
+                vec![],
+            );
+        });
+        assert!(
+            stderr.contains("parquet: --compression-level is not supported by this codec"),
+            "{}",
+            stderr
+        );
     }
 }
@@ -0,0 +1,267 @@
+use std::io::Write;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::commands::export::{RstDestinationOptions, RstStyle};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::formatter::ValueFormatter;
+
+fn text_width(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+fn pad(text: &str, width: usize, align_right: bool) -> String {
+    let padding = " ".repeat(width - text_width(text));
+    if align_right {
+        padding + text
+    } else {
+        text.to_string() + &padding
+    }
+}
+
+/// text with inline markup characters escaped, so cells are shown literally
+pub fn rst_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\*`|".contains(c) {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    //would start a comment, directive or list item. Bullet needs whitespace after it,
+    //so negative numbers are left alone
+    let bullet = result
+        .strip_prefix(['-', '+'])
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+    if result.starts_with("..") || bullet {
+        result.insert(0, '\\');
+    }
+    result
+}
+
+/// reStructuredText table. Column widths depend on all values, so rows are kept until close()
+pub struct RstDestination {
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    writer: FileOrStdout,
+    style: RstStyle,
+    column_names: Vec<String>,
+    column_types: Vec<ColumnType>,
+    align_right: Vec<bool>,
+    rows: Vec<Vec<String>>,
+}
+
+impl RstDestination {
+    pub fn init(options: &RstDestinationOptions, formatter: &ValueFormatter) -> RstDestination {
+        RstDestination {
+            truncate: options.truncate,
+            formatter: formatter.clone(),
+            writer: match options.filename.as_ref() {
+                "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                    termcolor::ColorChoice::Never,
+                )),
+                _ => FileOrStdout::File(std::fs::File::create(options.filename.clone()).unwrap()),
+            },
+            style: options.style,
+            column_names: vec![],
+            column_types: vec![],
+            align_right: vec![],
+            rows: vec![],
+        }
+    }
+
+    fn format_row(&self, row: &[String], widths: &[usize], align: bool) -> String {
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| pad(cell, widths[idx], align && self.align_right[idx]));
+        match self.style {
+            RstStyle::Grid => format!("| {} |\n", cells.collect::<Vec<String>>().join(" | ")),
+            RstStyle::Simple => {
+                format!("{}\n", cells.collect::<Vec<String>>().join("  ").trim_end())
+            }
+        }
+    }
+
+    fn separator(&self, widths: &[usize], c: &str) -> String {
+        match self.style {
+            RstStyle::Grid => format!(
+                "+{}+\n",
+                widths
+                    .iter()
+                    .map(|width| c.repeat(width + 2))
+                    .collect::<Vec<String>>()
+                    .join("+")
+            ),
+            RstStyle::Simple => format!(
+                "{}\n",
+                widths
+                    .iter()
+                    .map(|width| c.repeat(*width))
+                    .collect::<Vec<String>>()
+                    .join("  ")
+            ),
+        }
+    }
+}
+
+impl DataDestination for RstDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
+        self.column_names = column_info
+            .iter()
+            .map(|col| rst_escape(&col.name.replace(['\n', '\r'], " ")))
+            .collect();
+        self.align_right = column_info
+            .iter()
+            .map(|col| col.data_type.is_numeric())
+            .collect();
+        self.column_types = column_info.into_iter().map(|col| col.data_type).collect();
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        for row in rows {
+            self.rows.push(
+                row.iter()
+                    .zip(&self.column_types)
+                    .enumerate()
+                    .map(|(idx, (value, data_type))| {
+                        let text = rst_escape(
+                            &self
+                                .formatter
+                                .format_or_null(value, data_type, self.truncate, "")
+                                .replace(['\n', '\r'], " "),
+                        );
+                        //empty cell in first column of simple table means continuation line
+                        if idx == 0 && self.style == RstStyle::Simple && text.trim().is_empty() {
+                            "\\".to_string()
+                        } else {
+                            text
+                        }
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    fn close(&mut self) {
+        let widths: Vec<usize> = self
+            .column_names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                self.rows
+                    .iter()
+                    .map(|row| text_width(&row[idx]))
+                    .chain(std::iter::once(text_width(name)))
+                    .max()
+                    .unwrap()
+                    .max(1)
+            })
+            .collect();
+        let mut output = String::new();
+        match self.style {
+            RstStyle::Grid => {
+                output.push_str(&self.separator(&widths, "-"));
+                output.push_str(&self.format_row(&self.column_names, &widths, false));
+                //header separator can't be the last line of a table
+                output.push_str(
+                    &self.separator(&widths, if self.rows.is_empty() { "-" } else { "=" }),
+                );
+                for row in self.rows.iter() {
+                    output.push_str(&self.format_row(row, &widths, true));
+                    output.push_str(&self.separator(&widths, "-"));
+                }
+            }
+            RstStyle::Simple => {
+                output.push_str(&self.separator(&widths, "="));
+                output.push_str(&self.format_row(&self.column_names, &widths, false));
+                output.push_str(&self.separator(&widths, "="));
+                for row in self.rows.iter() {
+                    output.push_str(&self.format_row(row, &widths, true));
+                }
+                output.push_str(&self.separator(&widths, "="));
+            }
+        }
+        self.writer.write_all(output.as_bytes()).unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{ColumnType, Value};
+    use crate::utils::testing::{export, temp_path};
+    use clap::Parser;
+
+    fn rst(args: &[&str], rows: Vec<Row>) -> String {
+        let filename = temp_path("export.rst");
+        let options =
+            RstDestinationOptions::try_parse_from(["rst", filename.as_str()].iter().chain(args))
+                .unwrap();
+        export(
+            &mut RstDestination::init(&options, &ValueFormatter::default()),
+            &[("id", ColumnType::I64), ("note", ColumnType::String)],
+            vec![rows],
+        );
+        let output = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_rst_escape() {
+        assert_eq!(
+            rst_escape("*bold* `code` |sub|"),
+            "\\*bold\\* \\`code\\` \\|sub\\|"
+        );
+        assert_eq!(rst_escape("a\\b"), "a\\\\b");
+        assert_eq!(rst_escape(".. note::"), "\\.. note::");
+        assert_eq!(rst_escape("- item"), "\\- item");
+        assert_eq!(rst_escape("+ 1"), "\\+ 1");
+        assert_eq!(rst_escape("+1"), "+1");
+        assert_eq!(rst_escape("-20"), "-20");
+        assert_eq!(rst_escape("-"), "\\-");
+        assert_eq!(rst_escape("a-b"), "a-b");
+    }
+
+    #[test]
+    fn test_grid() {
+        let output = rst(
+            &[],
+            vec![
+                vec![Value::I64(1), Value::String("*x*".to_string())],
+                vec![Value::I64(-20), Value::None],
+            ],
+        );
+        assert_eq!(
+            output,
+            "+-----+-------+\n\
+             | id  | note  |\n\
+             +=====+=======+\n\
+             |   1 | \\*x\\* |\n\
+             +-----+-------+\n\
+             | -20 |       |\n\
+             +-----+-------+\n"
+        );
+    }
+
+    #[test]
+    fn test_simple() {
+        let output = rst(
+            &["--style", "simple"],
+            vec![
+                vec![Value::None, Value::String("a".to_string())],
+                vec![Value::I64(7), Value::String("b".to_string())],
+            ],
+        );
+        assert_eq!(
+            output,
+            "==  ====\nid  note\n==  ====\n \\  a\n 7  b\n==  ====\n"
+        );
+    }
+}
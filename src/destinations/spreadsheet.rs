@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use crate::commands::export::SpreadSheetDestinationOptions;
+
+/// rows in a sheet (header included) supported by both Excel and LibreOffice
+pub const SHEET_ROW_LIMIT: usize = 1_048_576;
+
+/// maximum number of rows (header included) written to one sheet
+pub fn max_sheet_rows(options: &SpreadSheetDestinationOptions) -> usize {
+    match options.max_sheet_rows {
+        None => SHEET_ROW_LIMIT,
+        Some(rows) if (2..=SHEET_ROW_LIMIT).contains(&rows) => rows,
+        Some(rows) => {
+            eprintln!(
+                "spreadsheet: --max-sheet-rows must be between 2 and {}, got {}",
+                SHEET_ROW_LIMIT, rows
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// sheet names are limited to 31 characters by Excel
+const SHEET_NAME_LIMIT: usize = 31;
+
+/// worksheet filled by its own query, given as --sheet name=query
+#[derive(Clone, Debug)]
+pub struct SheetQuery {
+    pub name: String,
+    pub query: String,
+}
+
+/// parse name=query or name=@file.sql
+pub fn parse_sheet_query(value: &str) -> Result<SheetQuery, String> {
+    let (name, query) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=query or name=@file.sql, got {}", value))?;
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > SHEET_NAME_LIMIT {
+        return Err(format!(
+            "sheet name must have between 1 and {} characters, got '{}'",
+            SHEET_NAME_LIMIT, name
+        ));
+    }
+    if let Some(c) = name.chars().find(|c| "[]:*?/\\".contains(*c)) {
+        return Err(format!("sheet name '{}' can't contain '{}'", name, c));
+    }
+    if name.starts_with('\'') || name.ends_with('\'') {
+        return Err(format!(
+            "sheet name '{}' can't start or end with apostrophe",
+            name
+        ));
+    }
+    let query = match query.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read query file {}: {}", path, e))?,
+        None => query.to_string(),
+    };
+    if query.trim().is_empty() {
+        return Err(format!("query for sheet '{}' is empty", name));
+    }
+    Ok(SheetQuery {
+        name: name.to_string(),
+        query,
+    })
+}
+
+/// --low-memory, or row count known in advance is above --low-memory-threshold
+pub fn use_low_memory(low_memory: bool, threshold: u64, count: Option<u64>) -> bool {
+    low_memory || count.is_some_and(|count| count > threshold)
+}
+
+/// name of n-th sheet holding given result: Sheet 1, Sheet 2... for unnamed results,
+/// Summary, Summary (2)... for named ones
+pub fn sheet_name(title: Option<&str>, number: usize) -> String {
+    match title {
+        None => format!("Sheet {}", number),
+        Some(title) if number == 1 => title.to_string(),
+        Some(title) => {
+            let suffix = format!(" ({})", number);
+            let title: String = title
+                .chars()
+                .take(SHEET_NAME_LIMIT - suffix.len())
+                .collect();
+            format!("{}{}", title, suffix)
+        }
+    }
+}
+
+/// filename of n-th workbook when result is split into several files:
+/// report.xlsx, report_2.xlsx, report_3.xlsx...
+pub fn numbered_filename(filename: &str, number: usize) -> String {
+    if number == 1 {
+        return filename.to_string();
+    }
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, number),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
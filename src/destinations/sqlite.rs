@@ -1,30 +1,321 @@
-use std::path::Path;
-
 use sqlite;
 
-use crate::commands::export::SqliteDestinationOptions;
+use crate::commands::export::{SqliteDestinationOptions, SqliteIfExists};
 use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row, Value};
 use crate::utils::truncate_text_with_note;
 
-pub struct SqliteDestination {
+/// default limit of bound parameters in a single statement (for sqlite >= 3.32.0)
+const SQLITE_MAX_VARIABLE_NUMBER: usize = 32766;
+
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub fn value_to_sqlite_value(value: &Value, truncate: Option<u64>) -> sqlite::Value {
+    match value {
+        //sqlite integers are signed, keep larger values exact as text
+        Value::U64(value) => match i64::try_from(*value) {
+            Ok(value) => sqlite::Value::Integer(value),
+            Err(_) => sqlite::Value::String(value.to_string()),
+        },
+        Value::I64(value) => sqlite::Value::Integer(*value),
+        Value::U32(value) => sqlite::Value::Integer(i64::from(*value)),
+        Value::I32(value) => sqlite::Value::Integer(i64::from(*value)),
+        Value::U16(value) => sqlite::Value::Integer(i64::from(*value)),
+        Value::I16(value) => sqlite::Value::Integer(i64::from(*value)),
+        Value::U8(value) => sqlite::Value::Integer(i64::from(*value)),
+        Value::I8(value) => sqlite::Value::Integer(i64::from(*value)),
+        Value::Bool(value) => sqlite::Value::Integer(i64::from(*value)),
+        Value::String(value) => {
+            sqlite::Value::String(truncate_text_with_note(value.to_string(), truncate))
+        }
+        Value::F64(value) => sqlite::Value::Float(*value),
+        Value::F32(value) => sqlite::Value::Float(f64::from(*value)),
+        Value::Bytes(value) => sqlite::Value::Binary(value.clone()),
+        Value::JSON(value) => sqlite::Value::String(value.clone()),
+        Value::None => sqlite::Value::Null,
+        Value::Timestamp(value) => match i64::try_from(*value) {
+            Ok(value) => sqlite::Value::Integer(value),
+            Err(_) => sqlite::Value::String(value.to_string()),
+        },
+        Value::Date(date) => sqlite::Value::String(format!("{}", date.format("%Y-%m-%d"))),
+        Value::Time(time) => sqlite::Value::String(format!("{}", time.format("%H:%M:%S%.f"))),
+        Value::DateTime(datetime) => {
+            sqlite::Value::String(format!("{}", datetime.format("%Y-%m-%d %H:%M:%S%.f")))
+        }
+        Value::Custom(value) => {
+            sqlite::Value::String(truncate_text_with_note(value.to_string(), truncate))
+        }
+    }
+}
+
+/// connection with insert statement for full chunks of rows, reused for entire export
+#[ouroboros::self_referencing]
+struct SqliteConnection {
     connection: sqlite::Connection,
+    #[borrows(connection)]
+    #[covariant]
+    //(rows per statement, statement)
+    insert_statement: Option<(usize, sqlite::Statement<'this>)>,
+}
+
+pub struct SqliteDestination {
+    connection: SqliteConnection,
+    filename: String,
     table: String,
     column_names: Vec<String>,
     truncate: Option<u64>,
+    if_exists: SqliteIfExists,
+    primary_key: Vec<String>,
+    indexes: Vec<String>,
+    commit_interval: u64,
+    uncommitted_rows: u64,
 }
 
 impl SqliteDestination {
     pub fn init(sqlite_options: &SqliteDestinationOptions) -> SqliteDestination {
-        let path = Path::new(&sqlite_options.filename);
-        if path.exists() {
-            std::fs::remove_file(path).unwrap();
+        if sqlite_options.if_exists == SqliteIfExists::Upsert
+            && sqlite_options.primary_key.is_empty()
+        {
+            eprintln!("sqlite: upsert requires at least one --primary-key column");
+            std::process::exit(1);
         }
         SqliteDestination {
-            connection: sqlite::Connection::open(&sqlite_options.filename).unwrap(),
+            connection: SqliteConnection::new(
+                sqlite::Connection::open(&sqlite_options.filename).unwrap(),
+                |_| None,
+            ),
+            filename: sqlite_options.filename.clone(),
             table: sqlite_options.table.clone(),
             column_names: vec![],
             truncate: sqlite_options.truncate,
+            if_exists: sqlite_options.if_exists,
+            primary_key: sqlite_options.primary_key.clone(),
+            indexes: sqlite_options.index.clone(),
+            commit_interval: sqlite_options.commit_interval,
+            uncommitted_rows: 0,
+        }
+    }
+
+    fn table_exists(&self) -> bool {
+        let mut statement = self
+            .connection
+            .borrow_connection()
+            .prepare("select count(*) from sqlite_master where type = 'table' and name = ?")
+            .unwrap();
+        statement.bind((1, self.table.as_str())).unwrap();
+        statement.next().unwrap();
+        statement.read::<i64, _>(0).unwrap() > 0
+    }
+
+    fn ensure_columns_exist(&self, columns: &[String], option_name: &str) {
+        for column in columns {
+            if !self.column_names.contains(column) {
+                eprintln!(
+                    "sqlite: {} column {} is not present in query results",
+                    option_name, column
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// apply --if-exists to existing table, returns whether table needs to be created
+    fn handle_existing_table(&self) -> Result<bool, String> {
+        if !self.table_exists() {
+            return Ok(true);
+        }
+        match self.if_exists {
+            SqliteIfExists::Fail => Err(format!(
+                "table {} already exists in {}",
+                self.table, self.filename
+            )),
+            SqliteIfExists::ReplaceTable => {
+                self.connection
+                    .borrow_connection()
+                    .execute(format!("drop table {}", quote_identifier(&self.table)))
+                    .map_err(|e| e.to_string())?;
+                Ok(true)
+            }
+            SqliteIfExists::Append | SqliteIfExists::Upsert => {
+                self.check_existing_table()?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// check that results fit into existing table: all columns are present and
+    /// primary key used for upsert matches table's primary key or unique index
+    fn check_existing_table(&self) -> Result<(), String> {
+        let error = |e: sqlite::Error| e.to_string();
+        let mut table_columns: Vec<String> = vec![];
+        //(position in key, column name)
+        let mut table_primary_key: Vec<(i64, String)> = vec![];
+        let mut statement = self
+            .connection
+            .borrow_connection()
+            .prepare(format!(
+                "pragma table_info({})",
+                quote_identifier(&self.table)
+            ))
+            .map_err(error)?;
+        while statement.next().map_err(error)? == sqlite::State::Row {
+            let name = statement.read::<String, _>(1).map_err(error)?;
+            let key_position = statement.read::<i64, _>(5).map_err(error)?;
+            if key_position > 0 {
+                table_primary_key.push((key_position, name.clone()));
+            }
+            table_columns.push(name);
+        }
+        for column in self.column_names.iter() {
+            if !table_columns.contains(column) {
+                return Err(format!(
+                    "table {} has no column {} (table columns: {})",
+                    self.table,
+                    column,
+                    table_columns.join(", ")
+                ));
+            }
+        }
+        if self.if_exists != SqliteIfExists::Upsert {
+            return Ok(());
+        }
+        table_primary_key.sort();
+        let mut keys: Vec<Vec<String>> = vec![];
+        if !table_primary_key.is_empty() {
+            keys.push(
+                table_primary_key
+                    .into_iter()
+                    .map(|(_, name)| name)
+                    .collect(),
+            );
+        }
+        let mut unique_indexes = vec![];
+        let mut statement = self
+            .connection
+            .borrow_connection()
+            .prepare(format!(
+                "pragma index_list({})",
+                quote_identifier(&self.table)
+            ))
+            .map_err(error)?;
+        while statement.next().map_err(error)? == sqlite::State::Row {
+            let unique = statement.read::<i64, _>(2).map_err(error)? == 1;
+            let partial = statement.read::<i64, _>(4).map_err(error)? == 1;
+            if unique && !partial {
+                unique_indexes.push(statement.read::<String, _>(1).map_err(error)?);
+            }
+        }
+        for index in unique_indexes {
+            let mut statement = self
+                .connection
+                .borrow_connection()
+                .prepare(format!("pragma index_info({})", quote_identifier(&index)))
+                .map_err(error)?;
+            let mut columns = vec![];
+            while statement.next().map_err(error)? == sqlite::State::Row {
+                columns.push(statement.read::<String, _>(2).map_err(error)?);
+            }
+            keys.push(columns);
+        }
+        //conflict target may list key columns in any order
+        let mut primary_key = self.primary_key.clone();
+        primary_key.sort();
+        let matches = keys.into_iter().any(|mut key| {
+            key.sort();
+            key == primary_key
+        });
+        if !matches {
+            return Err(format!(
+                "primary key ({}) does not match primary key or unique index of table {}",
+                self.primary_key.join(", "),
+                self.table
+            ));
+        }
+        Ok(())
+    }
+
+    fn insert_query(&self, rows_count: usize) -> String {
+        let values_part = self
+            .column_names
+            .iter()
+            .map(|_| "?".to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let mut sql = format!(
+            "insert into {} ({}) values ({})",
+            quote_identifier(&self.table),
+            self.column_names
+                .iter()
+                .map(|name| quote_identifier(name))
+                .collect::<Vec<String>>()
+                .join(", "),
+            values_part
+        );
+        for _v in 1..rows_count {
+            sql.push_str(&format!(",({})", values_part));
+        }
+        if self.if_exists == SqliteIfExists::Upsert {
+            let updates = self
+                .column_names
+                .iter()
+                .filter(|name| !self.primary_key.contains(name))
+                .map(|name| {
+                    format!(
+                        "{} = excluded.{}",
+                        quote_identifier(name),
+                        quote_identifier(name)
+                    )
+                })
+                .collect::<Vec<String>>();
+            sql.push_str(&format!(
+                " on conflict ({}) do {}",
+                self.primary_key
+                    .iter()
+                    .map(|name| quote_identifier(name))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                if updates.is_empty() {
+                    "nothing".to_string()
+                } else {
+                    format!("update set {}", updates.join(", "))
+                }
+            ));
+        }
+        sql
+    }
+
+    fn insert_rows(
+        statement: &mut sqlite::Statement,
+        rows: &[Row],
+        truncate: Option<u64>,
+    ) -> sqlite::Result<()> {
+        statement.reset()?;
+        let mut param_idx = 1;
+        for row in rows {
+            for col in row.iter() {
+                statement.bind((param_idx, &value_to_sqlite_value(col, truncate)))?;
+                param_idx += 1;
+            }
         }
+        statement.next()?;
+        Ok(())
+    }
+
+    fn insert_failed(&self, error: sqlite::Error) -> ! {
+        eprintln!(
+            "sqlite: could not insert rows into table {}: {}",
+            self.table, error
+        );
+        std::process::exit(1);
+    }
+
+    fn commit(&mut self) {
+        self.connection
+            .borrow_connection()
+            .execute("commit")
+            .unwrap();
+        self.uncommitted_rows = 0;
     }
 }
 
@@ -32,92 +323,309 @@ impl DataDestination for SqliteDestination {
     fn prepare(&mut self) {}
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
-        let columns = result_iterator
+        let mut columns = result_iterator
             .get_column_info()
             .iter()
             .map(|col| {
-                format!(
-                    "{} {}",
-                    col.name,
-                    match col.data_type {
-                        ColumnType::U64
-                        | ColumnType::I64
-                        | ColumnType::U32
-                        | ColumnType::I32
-                        | ColumnType::U16
-                        | ColumnType::I16
-                        | ColumnType::U8
-                        | ColumnType::I8 => "integer".to_string(),
-                        ColumnType::String => "text".to_string(),
-                        ColumnType::Bytes => "blob".to_string(),
-                        ColumnType::F64 | ColumnType::F32 => "float".to_string(),
-                        ColumnType::Bool => "bool".to_string(),
-                        ColumnType::DateTime => "datetime".to_string(),
-                        ColumnType::Date => "date".to_string(),
-                        ColumnType::Time => "time".to_string(),
-                        ColumnType::Decimal => "numeric".to_string(),
-                        _ => panic!("sqlite: unsupported column type: {:?}", col.data_type),
-                    }
-                )
+                let column_type = match col.data_type {
+                    ColumnType::U64
+                    | ColumnType::I64
+                    | ColumnType::U32
+                    | ColumnType::I32
+                    | ColumnType::U16
+                    | ColumnType::I16
+                    | ColumnType::U8
+                    | ColumnType::I8 => "integer",
+                    ColumnType::String => "text",
+                    ColumnType::Bytes => "blob",
+                    ColumnType::F64 | ColumnType::F32 => "float",
+                    ColumnType::Bool => "bool",
+                    ColumnType::DateTime => "datetime",
+                    ColumnType::Date => "date",
+                    ColumnType::Time => "time",
+                    ColumnType::Timestamp => "timestamp",
+                    ColumnType::Decimal => "numeric",
+                    ColumnType::JSON => "text",
+                    ColumnType::Custom(_) => "text",
+                    //no declared type, sqlite will store values as they are
+                    ColumnType::None => "",
+                };
+                format!("{} {}", quote_identifier(&col.name), column_type)
+                    .trim_end()
+                    .to_string()
             })
-            .collect::<Vec<String>>()
-            .join(", ");
+            .collect::<Vec<String>>();
         self.column_names = result_iterator
             .get_column_info()
             .iter()
             .map(|col| col.name.clone())
             .collect();
+        self.ensure_columns_exist(&self.primary_key, "primary key");
+        for index in self.indexes.iter() {
+            let index_columns: Vec<String> =
+                index.split(',').map(|c| c.trim().to_string()).collect();
+            self.ensure_columns_exist(&index_columns, "index");
+        }
+        if self.column_names.len() > SQLITE_MAX_VARIABLE_NUMBER {
+            eprintln!(
+                "sqlite: too many columns ({}), sqlite allows at most {} parameters per query",
+                self.column_names.len(),
+                SQLITE_MAX_VARIABLE_NUMBER
+            );
+            std::process::exit(1);
+        }
+        if !self.primary_key.is_empty() {
+            columns.push(format!(
+                "primary key ({})",
+                self.primary_key
+                    .iter()
+                    .map(|name| quote_identifier(name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
 
-        let create_table_query = format!("create table {} ({})", self.table, columns);
-        self.connection.execute(create_table_query).unwrap();
+        let create_table = self.handle_existing_table().unwrap_or_else(|e| {
+            eprintln!("sqlite: {}", e);
+            std::process::exit(1);
+        });
+
+        self.connection
+            .borrow_connection()
+            .execute("begin transaction")
+            .unwrap();
+        if create_table {
+            let create_table_query = format!(
+                "create table {} ({})",
+                quote_identifier(&self.table),
+                columns.join(", ")
+            );
+            self.connection
+                .borrow_connection()
+                .execute(create_table_query)
+                .unwrap();
+        }
     }
 
     fn add_rows(&mut self, rows: &[Row]) {
-        let values_part = self
-            .column_names
-            .iter()
-            .map(|_| "?".to_string())
-            .collect::<Vec<String>>()
-            .join(", ");
-        let mut sql = format!(
-            "insert into {} ({}) values ({})",
-            self.table,
-            self.column_names.join(", "),
-            values_part
+        if rows.is_empty() {
+            return;
+        }
+        //insert as many rows per statement as sqlite parameter limit allows,
+        //reusing prepared statement for all full chunks
+        let rows_per_statement = std::cmp::max(
+            1,
+            SQLITE_MAX_VARIABLE_NUMBER / std::cmp::max(1, self.column_names.len()),
         );
-        for _v in 1..rows.len() {
-            sql.push_str(&format!(",({})", values_part));
+        let chunk_size = std::cmp::min(rows.len(), rows_per_statement);
+        let cached_size = self
+            .connection
+            .borrow_insert_statement()
+            .as_ref()
+            .map(|(size, _)| *size);
+        if cached_size != Some(chunk_size) {
+            let query = self.insert_query(chunk_size);
+            let result = self.connection.with_mut(|fields| {
+                *fields.insert_statement = Some((chunk_size, fields.connection.prepare(query)?));
+                Ok(())
+            });
+            if let Err(e) = result {
+                self.insert_failed(e);
+            }
         }
-        let mut statement = self.connection.prepare(sql).unwrap();
-        //let mut cursor = statement.iter();
-        let mut data: Vec<sqlite::Value> = Vec::with_capacity(self.column_names.len());
-        for row in rows {
-            for col in row.iter() {
-                match col {
-                    Value::U64(value) => data.push(sqlite::Value::Integer(*value as i64)),
-                    Value::I64(value) => data.push(sqlite::Value::Integer(*value)),
-                    Value::U32(value) => data.push(sqlite::Value::Integer(i64::from(*value))),
-                    Value::I32(value) => data.push(sqlite::Value::Integer(i64::from(*value))),
-                    Value::U16(value) => data.push(sqlite::Value::Integer(i64::from(*value))),
-                    Value::I16(value) => data.push(sqlite::Value::Integer(i64::from(*value))),
-                    Value::U8(value) => data.push(sqlite::Value::Integer(i64::from(*value))),
-                    Value::I8(value) => data.push(sqlite::Value::Integer(i64::from(*value))),
-                    Value::Bool(value) => data.push(sqlite::Value::Integer(i64::from(*value))),
-                    Value::String(value) => data.push(sqlite::Value::String(
-                        truncate_text_with_note(value.to_string(), self.truncate),
-                    )),
-                    Value::F64(value) => data.push(sqlite::Value::Float(*value)),
-                    Value::F32(value) => data.push(sqlite::Value::Float(f64::from(*value))),
-                    Value::Bytes(value) => data.push(sqlite::Value::Binary(value.clone())),
-                    _ => panic!("sqlite: unsupported type: {:?}", col),
-                }
+        let truncate = self.truncate;
+        for chunk in rows.chunks(rows_per_statement) {
+            let result = if chunk.len() == chunk_size {
+                self.connection.with_insert_statement_mut(|statement| {
+                    let (_, statement) = statement.as_mut().unwrap();
+                    SqliteDestination::insert_rows(statement, chunk, truncate)
+                })
+            } else {
+                self.connection
+                    .borrow_connection()
+                    .prepare(self.insert_query(chunk.len()))
+                    .and_then(|mut last_statement| {
+                        SqliteDestination::insert_rows(&mut last_statement, chunk, truncate)
+                    })
+            };
+            if let Err(e) = result {
+                self.insert_failed(e);
             }
         }
-        //cursor.bind(&*data).unwrap();
-        statement.bind(&*data).unwrap();
-        //cursor.next().unwrap();
-        statement.next().unwrap();
+        self.uncommitted_rows += rows.len() as u64;
+        if self.commit_interval > 0 && self.uncommitted_rows >= self.commit_interval {
+            self.commit();
+            self.connection
+                .borrow_connection()
+                .execute("begin transaction")
+                .unwrap();
+        }
+    }
+
+    fn close(&mut self) {
+        self.commit();
+        for index in self.indexes.iter() {
+            let index_columns: Vec<&str> = index.split(',').map(|c| c.trim()).collect();
+            let index_query = format!(
+                "create index if not exists {} on {} ({})",
+                quote_identifier(&format!("{}_{}_idx", self.table, index_columns.join("_"))),
+                quote_identifier(&self.table),
+                index_columns
+                    .iter()
+                    .map(|name| quote_identifier(name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+            self.connection
+                .borrow_connection()
+                .execute(index_query)
+                .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{export, temp_path};
+    use clap::Parser;
+
+    const COLUMNS: [(&str, ColumnType); 2] =
+        [("id", ColumnType::I64), ("name", ColumnType::String)];
+
+    fn destination(filename: &str, args: &[&str]) -> SqliteDestination {
+        let options = SqliteDestinationOptions::try_parse_from(
+            ["sqlite", filename, "t1"].iter().chain(args.iter()),
+        )
+        .unwrap();
+        SqliteDestination::init(&options)
+    }
+
+    fn row(id: i64, name: &str) -> Row {
+        vec![Value::I64(id), Value::String(name.to_string())]
     }
 
-    fn close(&mut self) {}
+    fn table_rows(filename: &str) -> Vec<(i64, String)> {
+        let connection = sqlite::open(filename).unwrap();
+        let mut statement = connection
+            .prepare("select id, name from t1 order by id, name")
+            .unwrap();
+        let mut rows = vec![];
+        while statement.next().unwrap() == sqlite::State::Row {
+            rows.push((
+                statement.read::<i64, _>(0).unwrap(),
+                statement.read::<String, _>(1).unwrap(),
+            ));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_if_exists_modes() {
+        let filename = temp_path("sqlite-modes.db");
+        export(
+            &mut destination(&filename, &["--primary-key", "id"]),
+            &COLUMNS,
+            vec![vec![row(1, "a"), row(2, "b")]],
+        );
+        export(
+            &mut destination(&filename, &["--if-exists", "append"]),
+            &COLUMNS,
+            vec![vec![row(3, "c")]],
+        );
+        assert_eq!(table_rows(&filename).len(), 3);
+        export(
+            &mut destination(&filename, &["--if-exists", "upsert", "--primary-key", "id"]),
+            &COLUMNS,
+            vec![vec![row(1, "x"), row(4, "d")]],
+        );
+        assert_eq!(
+            table_rows(&filename),
+            vec![
+                (1, "x".to_string()),
+                (2, "b".to_string()),
+                (3, "c".to_string()),
+                (4, "d".to_string())
+            ]
+        );
+        export(
+            &mut destination(&filename, &[]),
+            &COLUMNS,
+            vec![vec![row(5, "e")]],
+        );
+        assert_eq!(table_rows(&filename), vec![(5, "e".to_string())]);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_value_to_sqlite_value() {
+        assert_eq!(
+            value_to_sqlite_value(&Value::U64(42), None),
+            sqlite::Value::Integer(42)
+        );
+        assert_eq!(
+            value_to_sqlite_value(&Value::U64(u64::MAX), None),
+            sqlite::Value::String(u64::MAX.to_string())
+        );
+        assert_eq!(
+            value_to_sqlite_value(&Value::Timestamp(u64::MAX), None),
+            sqlite::Value::String(u64::MAX.to_string())
+        );
+    }
+
+    #[test]
+    fn test_insert_chunks() {
+        let filename = temp_path("sqlite-chunks.db");
+        //more rows than fit in a single statement, then batches of varying size
+        let rows_per_statement = SQLITE_MAX_VARIABLE_NUMBER / COLUMNS.len();
+        let big_batch = (0..rows_per_statement as i64 * 2 + 5)
+            .map(|id| row(id, "a"))
+            .collect();
+        let offset = rows_per_statement as i64 * 3;
+        let small_batches = (0..3)
+            .map(|batch| {
+                (0..3 - batch)
+                    .map(|id| row(offset + batch * 10 + id, "b"))
+                    .collect()
+            })
+            .collect::<Vec<Vec<Row>>>();
+        let mut batches = vec![big_batch];
+        batches.extend(small_batches);
+        export(&mut destination(&filename, &[]), &COLUMNS, batches);
+        assert_eq!(table_rows(&filename).len(), rows_per_statement * 2 + 5 + 6);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_existing_table_checks() {
+        let filename = temp_path("sqlite-checks.db");
+        let connection = sqlite::open(&filename).unwrap();
+        connection
+            .execute("create table t1 (id integer, name text, code text unique)")
+            .unwrap();
+        let columns = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+
+        let mut append = destination(&filename, &["--if-exists", "append"]);
+        append.column_names = columns(&["id", "name"]);
+        assert!(append.check_existing_table().is_ok());
+        append.column_names = columns(&["id", "zz"]);
+        let error = append.check_existing_table().unwrap_err();
+        assert!(error.contains("no column zz"), "{}", error);
+
+        let mut upsert = destination(&filename, &["--if-exists", "upsert", "--primary-key", "id"]);
+        upsert.column_names = columns(&["id", "name"]);
+        let error = upsert.check_existing_table().unwrap_err();
+        assert!(error.contains("does not match"), "{}", error);
+        let mut upsert = destination(
+            &filename,
+            &["--if-exists", "upsert", "--primary-key", "code"],
+        );
+        upsert.column_names = columns(&["code", "name"]);
+        assert!(upsert.check_existing_table().is_ok());
+        let error = destination(&filename, &["--if-exists", "fail"])
+            .handle_existing_table()
+            .unwrap_err();
+        assert!(error.contains("already exists"), "{}", error);
+        std::fs::remove_file(&filename).unwrap();
+    }
 }
@@ -0,0 +1,415 @@
+use std::io::Write;
+
+use minijinja::value::{Value as TemplateValue, ValueKind};
+use minijinja::{context, Environment, Error, ErrorKind, UndefinedBehavior};
+use regex::Regex;
+
+use crate::commands::export::TemplateDestinationOptions;
+use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::truncate_text_with_note;
+
+/// template split into sections. Text outside of blocks (macros, set statements)
+/// is shared by all sections
+#[derive(Debug, Default)]
+struct Sections {
+    header: Option<String>,
+    row: Option<String>,
+    footer: Option<String>,
+}
+
+/// find top level {% block header %}, {% block row %} and {% block footer %}
+fn parse_sections(source: &str) -> Result<Sections, String> {
+    let tag = Regex::new(r"\{%(-?)\s*(block|endblock)\b\s*(\w*)\s*(-?)%\}").unwrap();
+    let mut sections = Sections::default();
+    let mut shared = String::new();
+    let mut depth = 0;
+    //name of current top level block and position where its content starts
+    let mut current: Option<(String, usize, bool)> = None;
+    let mut last_end = 0;
+    for captures in tag.captures_iter(source) {
+        let whole = captures.get(0).unwrap();
+        let trim_before = !captures[1].is_empty();
+        let trim_after = !captures[4].is_empty();
+        if &captures[2] == "block" {
+            depth += 1;
+            if depth == 1 {
+                shared.push_str(&source[last_end..whole.start()]);
+                current = Some((captures[3].to_string(), whole.end(), trim_after));
+            }
+        } else {
+            if depth == 0 {
+                return Err("endblock without block".to_string());
+            }
+            depth -= 1;
+            if depth == 0 {
+                let (name, start, trim_start) = current.take().unwrap();
+                let mut content = &source[start..whole.start()];
+                if trim_start {
+                    content = content.trim_start();
+                } else {
+                    //same as trim_blocks for other tags
+                    content = content
+                        .strip_prefix("\r\n")
+                        .or(content.strip_prefix('\n'))
+                        .unwrap_or(content);
+                }
+                if trim_before {
+                    content = content.trim_end();
+                }
+                let section = match name.as_str() {
+                    "header" => &mut sections.header,
+                    "row" => &mut sections.row,
+                    "footer" => &mut sections.footer,
+                    _ => {
+                        return Err(format!(
+                            "unknown section {}, expected header, row or footer",
+                            name
+                        ))
+                    }
+                };
+                if section.is_some() {
+                    return Err(format!("section {} is defined more than once", name));
+                }
+                *section = Some(content.to_string());
+                last_end = whole.end();
+            }
+        }
+    }
+    if depth > 0 {
+        return Err("block without endblock".to_string());
+    }
+    shared.push_str(&source[last_end..]);
+    let shared = shared.trim();
+    if sections.row.is_none() {
+        if sections.header.is_some() || sections.footer.is_some() {
+            return Err("row section is missing".to_string());
+        }
+        //template without sections is rendered for every row
+        sections.row = Some(source.to_string());
+        return Ok(sections);
+    }
+    for section in [
+        &mut sections.header,
+        &mut sections.row,
+        &mut sections.footer,
+    ] {
+        if let Some(content) = section.as_mut() {
+            content.insert_str(0, shared);
+        }
+    }
+    Ok(sections)
+}
+
+fn to_template_value(
+    value: &Value,
+    truncate: Option<u64>,
+    formatter: &ValueFormatter,
+) -> TemplateValue {
+    match value {
+        Value::U64(value) => TemplateValue::from(*value),
+        Value::I64(value) => TemplateValue::from(*value),
+        Value::U32(value) => TemplateValue::from(*value),
+        Value::I32(value) => TemplateValue::from(*value),
+        Value::U16(value) => TemplateValue::from(*value),
+        Value::I16(value) => TemplateValue::from(*value),
+        Value::U8(value) => TemplateValue::from(*value),
+        Value::I8(value) => TemplateValue::from(*value),
+        Value::F64(value) => TemplateValue::from(formatter.round(*value)),
+        Value::F32(value) => TemplateValue::from(formatter.round(*value)),
+        Value::String(value) => {
+            TemplateValue::from(truncate_text_with_note(value.to_string(), truncate))
+        }
+        Value::Bool(value) => TemplateValue::from(*value),
+        Value::Bytes(value) => TemplateValue::from(formatter.bytes(value)),
+        Value::None => TemplateValue::from(()),
+        Value::Timestamp(value) => TemplateValue::from(*value),
+        Value::Date(date) => TemplateValue::from(formatter.date(date)),
+        Value::Time(time) => TemplateValue::from(formatter.time(time)),
+        Value::DateTime(datetime) => TemplateValue::from(formatter.datetime(datetime)),
+        Value::JSON(value) => TemplateValue::from(value.to_string()),
+        Value::Custom(value) => {
+            TemplateValue::from(truncate_text_with_note(value.to_string(), truncate))
+        }
+    }
+}
+
+fn is_null(value: &TemplateValue) -> bool {
+    value.is_none() || value.is_undefined()
+}
+
+fn html_filter(value: &TemplateValue) -> String {
+    if is_null(value) {
+        return String::new();
+    }
+    let mut result = String::new();
+    for c in value.to_string().chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#x27;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// sql literal: NULL, number, boolean or quoted string
+fn sql_filter(value: &TemplateValue) -> String {
+    match value.kind() {
+        ValueKind::None | ValueKind::Undefined => "NULL".to_string(),
+        ValueKind::Number => value.to_string(),
+        ValueKind::Bool => if value.is_true() { "TRUE" } else { "FALSE" }.to_string(),
+        _ => format!("'{}'", value.to_string().replace('\'', "''")),
+    }
+}
+
+/// single quoted posix shell word
+fn shell_filter(value: &TemplateValue) -> String {
+    let text = if is_null(value) {
+        String::new()
+    } else {
+        value.to_string()
+    };
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn json_filter(value: &TemplateValue) -> Result<String, Error> {
+    serde_json::to_string(value).map_err(|e| {
+        Error::new(ErrorKind::InvalidOperation, "can't serialize value to json").with_source(e)
+    })
+}
+
+pub struct TemplateDestination {
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    writer: FileOrStdout,
+    environment: Environment<'static>,
+    sections: Sections,
+    columns: TemplateValue,
+    column_names: Vec<String>,
+    row_count: u64,
+}
+
+impl TemplateDestination {
+    pub fn init(
+        options: &TemplateDestinationOptions,
+        formatter: &ValueFormatter,
+    ) -> TemplateDestination {
+        let source = std::fs::read_to_string(&options.template).unwrap_or_else(|e| {
+            eprintln!(
+                "template: could not read template file {}: {}",
+                options.template, e
+            );
+            std::process::exit(1);
+        });
+        let sections = parse_sections(&source).unwrap_or_else(|e| {
+            eprintln!("template: {}: {}", options.template, e);
+            std::process::exit(1);
+        });
+        let mut environment = Environment::new();
+        //typo in column name should be an error, not an empty string
+        environment.set_undefined_behavior(UndefinedBehavior::Strict);
+        environment.set_trim_blocks(true);
+        environment.set_lstrip_blocks(true);
+        environment.set_keep_trailing_newline(true);
+        environment.add_filter("html", html_filter);
+        environment.add_filter("sql", sql_filter);
+        environment.add_filter("shell", shell_filter);
+        environment.add_filter("json", json_filter);
+        for (name, section) in [
+            ("header", &sections.header),
+            ("row", &sections.row),
+            ("footer", &sections.footer),
+        ] {
+            if let Some(content) = section {
+                environment
+                    .add_template_owned(name, content.clone())
+                    .unwrap_or_else(|e| {
+                        eprintln!("template: {}: {:#}", options.template, e);
+                        std::process::exit(1);
+                    });
+            }
+        }
+        TemplateDestination {
+            truncate: options.truncate,
+            formatter: formatter.clone(),
+            writer: match options.filename.as_ref() {
+                "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                    termcolor::ColorChoice::Never,
+                )),
+                _ => FileOrStdout::File(std::fs::File::create(options.filename.clone()).unwrap()),
+            },
+            environment,
+            sections,
+            columns: TemplateValue::from(()),
+            column_names: vec![],
+            row_count: 0,
+        }
+    }
+
+    fn render(&self, name: &str, context: TemplateValue) -> String {
+        self.environment
+            .get_template(name)
+            .and_then(|template| template.render(context))
+            .unwrap_or_else(|e| {
+                eprintln!("template: error in {} section: {:#}", name, e);
+                std::process::exit(1);
+            })
+    }
+}
+
+impl DataDestination for TemplateDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
+        self.column_names = column_info.iter().map(|col| col.name.clone()).collect();
+        self.columns = column_info
+            .iter()
+            .map(|col| {
+                [
+                    ("name", TemplateValue::from(col.name.clone())),
+                    ("type", TemplateValue::from(format!("{:?}", col.data_type))),
+                    ("numeric", TemplateValue::from(col.data_type.is_numeric())),
+                ]
+                .into_iter()
+                .collect::<TemplateValue>()
+            })
+            .collect();
+        if self.sections.header.is_some() {
+            let output = self.render("header", context! { columns => self.columns });
+            self.writer.write_all(output.as_bytes()).unwrap();
+        }
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let mut batch = String::new();
+        for row in rows {
+            let values: Vec<TemplateValue> = row
+                .iter()
+                .map(|value| to_template_value(value, self.truncate, &self.formatter))
+                .collect();
+            let row_map: TemplateValue = self
+                .column_names
+                .iter()
+                .cloned()
+                .zip(values.iter().cloned())
+                .collect();
+            batch.push_str(&self.render(
+                "row",
+                context! {
+                    columns => self.columns,
+                    row => row_map,
+                    values => values,
+                    index => self.row_count,
+                },
+            ));
+            self.row_count += 1;
+        }
+        self.writer.write_all(batch.as_bytes()).unwrap();
+    }
+
+    fn close(&mut self) {
+        if self.sections.footer.is_some() {
+            let output = self.render(
+                "footer",
+                context! { columns => self.columns, count => self.row_count },
+            );
+            self.writer.write_all(output.as_bytes()).unwrap();
+        }
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::ColumnType;
+    use crate::utils::testing::{exits_with, export, temp_path};
+    use clap::Parser;
+
+    fn render(template: &str) -> String {
+        let template_filename = temp_path("template.j2");
+        std::fs::write(&template_filename, template).unwrap();
+        let filename = temp_path("export.txt");
+        let options = TemplateDestinationOptions::try_parse_from([
+            "template",
+            filename.as_str(),
+            "--template",
+            template_filename.as_str(),
+        ])
+        .unwrap();
+        export(
+            &mut TemplateDestination::init(&options, &ValueFormatter::default()),
+            &[("id", ColumnType::I64), ("name", ColumnType::String)],
+            vec![vec![
+                vec![Value::I64(1), Value::String("O'Brien <x>".to_string())],
+                vec![Value::I64(2), Value::None],
+            ]],
+        );
+        let output = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        std::fs::remove_file(&template_filename).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_sections() {
+        let output = render(
+            "{% macro q(v) %}{{ v | sql }}{% endmacro %}\n\
+             {% block header %}\n\
+             {% for c in columns %}\n\
+             -- {{ c.name }}:{{ c.numeric }}\n\
+             {% endfor %}\n\
+             {% endblock %}\n\
+             {% block row %}\n\
+             insert into t values ({{ row.id }}, {{ q(row.name) }}); -- {{ index }}\n\
+             {% endblock %}\n\
+             {% block footer %}\n\
+             -- {{ count }} rows\n\
+             {% endblock %}\n",
+        );
+        assert_eq!(
+            output,
+            "-- id:True\n\
+             -- name:False\n\
+             insert into t values (1, 'O''Brien <x>'); -- 0\n\
+             insert into t values (2, NULL); -- 1\n\
+             -- 2 rows\n"
+        );
+    }
+
+    #[test]
+    fn test_filters_without_sections() {
+        assert_eq!(
+            render("{{ values[1] | html }}|{{ row.name | shell }}|{{ row.name | json }}\n"),
+            "O&#x27;Brien &lt;x&gt;|'O'\\''Brien <x>'|\"O'Brien <x>\"\n|''|null\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_sections() {
+        assert_eq!(
+            parse_sections("{% block header %}{% endblock %}").unwrap_err(),
+            "row section is missing"
+        );
+        assert_eq!(
+            parse_sections("{% block row %}{% endblock %}{% block row %}{% endblock %}")
+                .unwrap_err(),
+            "section row is defined more than once"
+        );
+        assert!(parse_sections("{% block body %}{% endblock %}").is_err());
+        let stderr = exits_with(module_path!(), "test_invalid_sections", || {
+            render("{{ row.missing }}");
+        });
+        assert!(
+            stderr.contains("template: error in row section"),
+            "{}",
+            stderr
+        );
+    }
+}
@@ -4,22 +4,349 @@ use std::io::Write;
 use is_terminal::IsTerminal;
 use prettytable::{self, Cell, Table};
 use termcolor;
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::commands::{export::TextDestinationOptions, ApplicationArguments, UseColor};
-use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::commands::{
+    export::{TextDestinationOptions, TextOverflow},
+    ApplicationArguments, UseColor,
+};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row, Value};
+#[cfg(feature = "use_pager")]
+use crate::destinations::pager::Pager;
 use crate::utils::fileorstdout::FileOrStdout;
-use crate::utils::{escape_binary_data, truncate_text_with_note};
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::theme::{Theme, ValueKind};
+use crate::utils::truncate_text_split;
+
+/// value prepared for text table: its text, kind (used for styling)
+/// and grapheme index where truncation note starts
+pub struct TextCell {
+    pub text: String,
+    pub kind: ValueKind,
+    pub note_start: Option<usize>,
+}
+
+impl TextCell {
+    pub fn plain(text: String) -> TextCell {
+        TextCell {
+            text,
+            kind: ValueKind::Text,
+            note_start: None,
+        }
+    }
+
+    /// NULL is rendered as formatter's null marker, null_marker if given, as empty string otherwise
+    pub fn from_value(
+        value: &Value,
+        data_type: &ColumnType,
+        truncate: Option<u64>,
+        formatter: &ValueFormatter,
+        null_marker: Option<&str>,
+    ) -> TextCell {
+        let decimal = matches!(data_type, ColumnType::Decimal);
+        let (text, note) = match value {
+            Value::String(value) | Value::Custom(value) if !decimal => {
+                truncate_text_split(value.to_string(), truncate)
+            }
+            _ => (
+                formatter.format_or_null(value, data_type, truncate, null_marker.unwrap_or("")),
+                None,
+            ),
+        };
+        let kind = match value {
+            Value::String(_) if decimal => ValueKind::Number,
+            _ => ValueKind::of(value),
+        };
+        match note {
+            None => TextCell {
+                text,
+                kind,
+                note_start: None,
+            },
+            Some(note) => TextCell {
+                note_start: Some(text.graphemes(true).count()),
+                text: text + &note,
+                kind,
+            },
+        }
+    }
+}
+
+/// table printed as rows arrive. Column widths are computed from first sample_rows rows
+/// (capped by max_width), longer values are wrapped or ellipsized
+pub struct StreamingTable {
+    sample_rows: usize,
+    max_width: Option<usize>,
+    page_size: usize,
+    overflow: TextOverflow,
+    theme: Option<Theme>,
+    row_separators: bool,
+    widths: Option<Vec<usize>>,
+    pending_rows: Vec<Vec<TextCell>>,
+    rows_on_page: usize,
+}
+
+impl StreamingTable {
+    pub fn new(options: &TextDestinationOptions, theme: Option<Theme>) -> StreamingTable {
+        if options.sample_rows == 0 && options.max_width.is_none() {
+            eprintln!("text: --sample-rows 0 requires --max-width");
+            std::process::exit(1);
+        }
+        StreamingTable {
+            sample_rows: options.sample_rows,
+            max_width: options.max_width.map(|w| w.max(1)),
+            page_size: options.page_size,
+            overflow: options.overflow,
+            theme,
+            row_separators: false,
+            widths: None,
+            pending_rows: vec![],
+            rows_on_page: 0,
+        }
+    }
+
+    /// whole result printed on close, looking like prettytable output
+    pub fn buffered(theme: Option<Theme>) -> StreamingTable {
+        StreamingTable {
+            sample_rows: usize::MAX,
+            max_width: None,
+            page_size: 0,
+            overflow: TextOverflow::Wrap,
+            theme,
+            row_separators: true,
+            widths: None,
+            pending_rows: vec![],
+            rows_on_page: 0,
+        }
+    }
+
+    fn width_of(text: &str) -> usize {
+        text.lines()
+            .map(|line| line.graphemes(true).count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn compute_widths(&mut self, column_names: &[String]) -> Vec<usize> {
+        column_names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let width = if self.sample_rows == 0 {
+                    self.max_width.unwrap()
+                } else {
+                    self.pending_rows
+                        .iter()
+                        .map(|row| StreamingTable::width_of(&row[idx].text))
+                        .chain(std::iter::once(StreamingTable::width_of(name)))
+                        .max()
+                        .unwrap()
+                };
+                match self.max_width {
+                    Some(max_width) => width.min(max_width),
+                    None => width,
+                }
+                .max(1)
+            })
+            .collect()
+    }
+
+    /// split cell into lines fitting given width.
+    /// Returns grapheme ranges and whether ellipsis should follow
+    fn cell_lines(&self, graphemes: &[&str], width: usize) -> Vec<(usize, usize, bool)> {
+        let mut ranges = vec![];
+        let mut start = 0;
+        for (idx, grapheme) in graphemes.iter().enumerate() {
+            if *grapheme == "\n" || *grapheme == "\r\n" {
+                ranges.push((start, idx));
+                start = idx + 1;
+            }
+        }
+        ranges.push((start, graphemes.len()));
+        let mut lines = vec![];
+        for (start, end) in ranges {
+            if end - start <= width {
+                lines.push((start, end, false));
+                continue;
+            }
+            match self.overflow {
+                TextOverflow::Wrap => {
+                    let mut position = start;
+                    while position < end {
+                        let line_end = (position + width).min(end);
+                        lines.push((position, line_end, false));
+                        position = line_end;
+                    }
+                }
+                TextOverflow::Ellipsis => lines.push((start, start + width - 1, true)),
+            }
+        }
+        if self.overflow == TextOverflow::Ellipsis && lines.len() > 1 {
+            //keep one line per row
+            let (start, end, _) = lines[0];
+            lines = vec![(start, end.min(start + width - 1), true)];
+        }
+        lines
+    }
+
+    fn render_line(
+        &self,
+        cell: &TextCell,
+        graphemes: &[&str],
+        line: (usize, usize, bool),
+        width: usize,
+        header: bool,
+    ) -> String {
+        let (start, end, ellipsis) = line;
+        let padding = " ".repeat(width - (end - start) - usize::from(ellipsis));
+        let Some(theme) = &self.theme else {
+            return graphemes[start..end].concat() + if ellipsis { "…" } else { "" } + &padding;
+        };
+        let style = if header {
+            &theme.header
+        } else {
+            theme.style_for(cell.kind)
+        };
+        let split = cell.note_start.map(|n| n.clamp(start, end)).unwrap_or(end);
+        let mut content = Theme::paint(&graphemes[start..split].concat(), style)
+            + &Theme::paint(&graphemes[split..end].concat(), &theme.truncated);
+        if ellipsis {
+            content.push_str(&Theme::paint("…", &theme.truncated));
+        }
+        if cell.kind == ValueKind::Number {
+            padding + &content
+        } else {
+            content + &padding
+        }
+    }
+
+    fn border(widths: &[usize], left: &str, middle: &str, right: &str) -> String {
+        format!(
+            "{}{}{}\n",
+            left,
+            widths
+                .iter()
+                .map(|width| "─".repeat(*width))
+                .collect::<Vec<String>>()
+                .join(middle),
+            right
+        )
+    }
+
+    fn format_row(&self, row: &[TextCell], widths: &[usize], header: bool) -> String {
+        let graphemes: Vec<Vec<&str>> = row
+            .iter()
+            .map(|cell| cell.text.graphemes(true).collect())
+            .collect();
+        let lines: Vec<Vec<(usize, usize, bool)>> = graphemes
+            .iter()
+            .zip(widths.iter())
+            .map(|(graphemes, width)| self.cell_lines(graphemes, *width))
+            .collect();
+        let height = lines.iter().map(|lines| lines.len()).max().unwrap_or(1);
+        let mut output = String::new();
+        for line_idx in 0..height {
+            output.push('│');
+            for (idx, width) in widths.iter().enumerate() {
+                match lines[idx].get(line_idx) {
+                    Some(line) => output.push_str(&self.render_line(
+                        &row[idx],
+                        &graphemes[idx],
+                        *line,
+                        *width,
+                        header,
+                    )),
+                    None => output.push_str(&" ".repeat(*width)),
+                }
+                output.push('│');
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    fn format_header(&self, column_names: &[String], widths: &[usize], first: bool) -> String {
+        let mut output = if first {
+            StreamingTable::border(widths, "┌", "┬", "┐")
+        } else {
+            StreamingTable::border(widths, "├", "┼", "┤")
+        };
+        let header: Vec<TextCell> = column_names
+            .iter()
+            .map(|name| TextCell::plain(name.clone()))
+            .collect();
+        output.push_str(&self.format_row(&header, widths, true));
+        output.push_str(&StreamingTable::border(widths, "├", "┼", "┤"));
+        output
+    }
+
+    /// format rows that can be printed already
+    pub fn add_rows(&mut self, column_names: &[String], rows: Vec<Vec<TextCell>>) -> String {
+        self.pending_rows.extend(rows);
+        if self.widths.is_none() && self.pending_rows.len() < self.sample_rows {
+            return String::new();
+        }
+        self.flush(column_names)
+    }
+
+    fn flush(&mut self, column_names: &[String]) -> String {
+        let mut output = String::new();
+        if self.widths.is_none() {
+            let widths = self.compute_widths(column_names);
+            output.push_str(&self.format_header(column_names, &widths, true));
+            self.widths = Some(widths);
+        }
+        let widths = self.widths.clone().unwrap();
+        for row in std::mem::take(&mut self.pending_rows) {
+            if self.page_size > 0 && self.rows_on_page == self.page_size {
+                output.push_str(&self.format_header(column_names, &widths, false));
+                self.rows_on_page = 0;
+            } else if self.row_separators && self.rows_on_page > 0 {
+                output.push_str(&StreamingTable::border(&widths, "├", "┼", "┤"));
+            }
+            output.push_str(&self.format_row(&row, &widths, false));
+            self.rows_on_page += 1;
+        }
+        output
+    }
+
+    pub fn close(&mut self, column_names: &[String]) -> String {
+        let mut output = self.flush(column_names);
+        output.push_str(&StreamingTable::border(
+            self.widths.as_ref().unwrap(),
+            "└",
+            "┴",
+            "┘",
+        ));
+        output
+    }
+}
 
 pub struct TextDestination {
     truncate: Option<u64>,
     column_names: Vec<String>,
+    column_types: Vec<ColumnType>,
     writer: FileOrStdout,
     table: Table,
-    _use_color: bool,
+    //also used for coloured output, which prettytable can't write to arbitrary writer
+    streaming_table: Option<StreamingTable>,
+    formatter: ValueFormatter,
+    null_marker: Option<String>,
+    #[cfg(feature = "use_pager")]
+    use_pager: bool,
+    #[cfg(feature = "use_pager")]
+    theme: Option<Theme>,
+    //results were shown in pager, nothing left to print
+    #[cfg(feature = "use_pager")]
+    paged: bool,
 }
 
 impl TextDestination {
-    pub fn init(args: &ApplicationArguments, options: &TextDestinationOptions) -> TextDestination {
+    pub fn init(
+        args: &ApplicationArguments,
+        options: &TextDestinationOptions,
+        formatter: &ValueFormatter,
+    ) -> TextDestination {
         let use_color = match args.color {
             UseColor::Yes => true,
             UseColor::No => false,
@@ -31,10 +358,27 @@ impl TextDestination {
         format.padding(0, 0);
         table.set_format(format);
 
+        let theme = if use_color {
+            Some(Theme::from_config())
+        } else {
+            None
+        };
+
         TextDestination {
             truncate: options.truncate,
             column_names: vec![],
-            _use_color: use_color,
+            column_types: vec![],
+            formatter: formatter.clone(),
+            null_marker: theme.as_ref().map(|theme| theme.null_marker.clone()),
+            #[cfg(feature = "use_pager")]
+            use_pager: options.filename == "-"
+                && std::io::stdout().is_terminal()
+                && !options.stream
+                && !options.no_pager,
+            #[cfg(feature = "use_pager")]
+            theme: theme.clone(),
+            #[cfg(feature = "use_pager")]
+            paged: false,
             writer: match options.filename.as_ref() {
                 "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(if use_color {
                     termcolor::ColorChoice::Always
@@ -44,19 +388,101 @@ impl TextDestination {
                 _ => FileOrStdout::File(std::fs::File::create(options.filename.clone()).unwrap()),
             },
             table,
+            streaming_table: if options.stream {
+                Some(StreamingTable::new(options, theme))
+            } else if theme.is_some() {
+                Some(StreamingTable::buffered(theme))
+            } else {
+                None
+            },
         }
     }
+
+    /// lines and columns taken by table with given rows
+    #[cfg(feature = "use_pager")]
+    fn table_size(&self, rows: &[Row]) -> (usize, usize) {
+        let mut widths: Vec<usize> = self
+            .column_names
+            .iter()
+            .map(|name| name.graphemes(true).count())
+            .collect();
+        //top and bottom border, header and its separator
+        let mut height = 3;
+        for row in rows {
+            let mut lines = 1;
+            for (idx, (value, data_type)) in row.iter().zip(&self.column_types).enumerate() {
+                let cell = TextCell::from_value(
+                    value,
+                    data_type,
+                    self.truncate,
+                    &self.formatter,
+                    self.null_marker.as_deref(),
+                );
+                for line in cell.text.lines() {
+                    widths[idx] = widths[idx].max(line.graphemes(true).count());
+                }
+                lines = lines.max(cell.text.lines().count());
+            }
+            //row separator
+            height += lines + 1;
+        }
+        let width = widths.iter().sum::<usize>() + widths.len() + 1;
+        (height, width)
+    }
+
+    /// show results in interactive pager if they don't fit on screen, results that fit are
+    /// added to this destination. Returns number of consumed rows, None when pager isn't used
+    /// and export continues as usual
+    #[cfg(feature = "use_pager")]
+    pub fn page(&mut self, iterator: &mut dyn DataSourceBatchIterator) -> Option<usize> {
+        if !self.use_pager || self.column_names.is_empty() {
+            return None;
+        }
+        let (screen_width, screen_height) = match crossterm::terminal::size() {
+            Ok((width, height)) => (width as usize, height as usize),
+            Err(_) => return None,
+        };
+        let mut rows: Vec<Row> = vec![];
+        let mut exhausted = false;
+        //leave a line for shell prompt
+        while !exhausted && self.table_size(&rows).0 < screen_height {
+            match iterator.next() {
+                Some(batch) => rows.extend(batch),
+                None => exhausted = true,
+            }
+        }
+        let (height, width) = self.table_size(&rows);
+        if exhausted && height < screen_height && width <= screen_width {
+            self.add_rows(&rows);
+            return Some(rows.len());
+        }
+        let mut pager = Pager::new(
+            self.column_names.clone(),
+            self.column_types.clone(),
+            self.truncate,
+            self.formatter.clone(),
+            self.theme.clone(),
+        );
+        pager.add_rows(&rows);
+        let count = match pager.run(iterator) {
+            Ok(count) => count,
+            Err(e) => {
+                eprintln!("pager failed: {}", e);
+                std::process::exit(1);
+            }
+        };
+        self.paged = true;
+        Some(count)
+    }
 }
 
 impl DataDestination for TextDestination {
     fn prepare(&mut self) {}
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
-        self.column_names = result_iterator
-            .get_column_info()
-            .iter()
-            .map(|col| col.name.clone())
-            .collect();
+        let column_info = result_iterator.get_column_info();
+        self.column_names = column_info.iter().map(|col| col.name.clone()).collect();
+        self.column_types = column_info.into_iter().map(|col| col.data_type).collect();
         self.table.add_row(prettytable::Row::new(
             self.column_names
                 .iter()
@@ -66,37 +492,37 @@ impl DataDestination for TextDestination {
     }
 
     fn add_rows(&mut self, rows: &[Row]) {
+        if let Some(streaming_table) = self.streaming_table.as_mut() {
+            let rows = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .zip(&self.column_types)
+                        .map(|(value, data_type)| {
+                            TextCell::from_value(
+                                value,
+                                data_type,
+                                self.truncate,
+                                &self.formatter,
+                                self.null_marker.as_deref(),
+                            )
+                        })
+                        .collect()
+                })
+                .collect();
+            let output = streaming_table.add_rows(&self.column_names, rows);
+            self.writer.write_all(output.as_bytes()).unwrap();
+            self.writer.flush().unwrap();
+            return;
+        }
         for row in rows {
             //<column index, value, original length, truncated>
             let mut row_data: Vec<String> = Vec::with_capacity(self.column_names.len());
-            for col in row.iter() {
-                let content = match col {
-                    Value::U64(value) => value.to_string(),
-                    Value::I64(value) => value.to_string(),
-                    Value::U32(value) => value.to_string(),
-                    Value::I32(value) => value.to_string(),
-                    Value::U16(value) => value.to_string(),
-                    Value::I16(value) => value.to_string(),
-                    Value::U8(value) => value.to_string(),
-                    Value::I8(value) => value.to_string(),
-                    Value::F64(value) => value.to_string(),
-                    Value::F32(value) => value.to_string(),
-                    Value::String(value) => {
-                        truncate_text_with_note(value.to_string(), self.truncate)
-                    }
-                    Value::Bool(value) => value.to_string(),
-                    Value::Bytes(value) => escape_binary_data(value),
-                    Value::None => "".to_string(),
-                    Value::Timestamp(value) => value.to_string(),
-                    Value::Date(date) => format!("{}", date.format("%Y-%m-%d")),
-                    Value::Time(time) => format!("{}", time.format("%H:%M:%S")),
-                    Value::DateTime(datetime) => {
-                        format!("{}", datetime.format("%Y-%m-%d %H:%M:%S"))
-                    }
-
-                    _ => panic!("text: unsupported type: {:?}", col),
-                };
-                row_data.push(content);
+            for (col, data_type) in row.iter().zip(&self.column_types) {
+                row_data.push(
+                    self.formatter
+                        .format_or_null(col, data_type, self.truncate, ""),
+                );
             }
 
             self.table.add_row(prettytable::Row::new(
@@ -106,7 +532,129 @@ impl DataDestination for TextDestination {
     }
 
     fn close(&mut self) {
+        #[cfg(feature = "use_pager")]
+        if self.paged {
+            return;
+        }
+        if let Some(streaming_table) = self.streaming_table.as_mut() {
+            let output = streaming_table.close(&self.column_names);
+            self.writer.write_all(output.as_bytes()).unwrap();
+            self.writer.flush().unwrap();
+            return;
+        }
         self.table.print(&mut self.writer).unwrap();
         self.writer.flush().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::theme::parse_style;
+    use clap::Parser;
+
+    fn streaming_table(args: &[&str]) -> StreamingTable {
+        let options =
+            TextDestinationOptions::try_parse_from(["text", "-", "--stream"].iter().chain(args))
+                .unwrap();
+        StreamingTable::new(&options, None)
+    }
+
+    fn cells(texts: &[&str]) -> Vec<TextCell> {
+        texts
+            .iter()
+            .map(|text| TextCell::plain(text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_streaming_pages() {
+        let names = vec!["id".to_string(), "name".to_string()];
+        let mut table =
+            streaming_table(&["--sample-rows", "2", "--max-width", "3", "--page-size", "2"]);
+        //widths are not known until sample is complete
+        assert_eq!(table.add_rows(&names, vec![cells(&["1", "abcdef"])]), "");
+        let mut output = table.add_rows(&names, vec![cells(&["22", "x"])]);
+        output.push_str(&table.add_rows(&names, vec![cells(&["3", "y"])]));
+        output.push_str(&table.close(&names));
+        assert_eq!(
+            output,
+            "┌──┬───┐\n│id│na…│\n├──┼───┤\n│1 │ab…│\n│22│x  │\n\
+             ├──┼───┤\n│id│na…│\n├──┼───┤\n│3 │y  │\n└──┴───┘\n"
+        );
+    }
+
+    #[test]
+    fn test_themed_cells() {
+        let theme = Theme {
+            number: parse_style("cyan").unwrap(),
+            truncated: parse_style("yellow").unwrap(),
+            ..Theme::default()
+        };
+        let formatter = ValueFormatter::default();
+        let cell = |value: &Value| {
+            TextCell::from_value(value, &ColumnType::None, Some(3), &formatter, Some("-"))
+        };
+        let table = StreamingTable::buffered(Some(theme.clone()));
+        let number = cell(&Value::I64(42));
+        assert_eq!(number.kind, ValueKind::Number);
+        //numbers are aligned right
+        assert_eq!(
+            table.render_line(&number, &["4", "2"], (0, 2, false), 4, false),
+            format!("  {}", Theme::paint("42", &theme.number))
+        );
+        let null = cell(&Value::None);
+        assert_eq!((null.text.as_str(), null.kind), ("-", ValueKind::Null));
+        //decimal text is a number: formatted, not truncated
+        let formatter = ValueFormatter {
+            thousands_separator: Some(",".to_string()),
+            ..ValueFormatter::default()
+        };
+        let decimal = TextCell::from_value(
+            &Value::String("1234.50".to_string()),
+            &ColumnType::Decimal,
+            Some(3),
+            &formatter,
+            None,
+        );
+        assert_eq!(
+            (decimal.text.as_str(), decimal.kind),
+            ("1,234.50", ValueKind::Number)
+        );
+        //truncation note is painted with its own style
+        let text = cell(&Value::String("abcdef".to_string()));
+        let note_start = text.note_start.unwrap();
+        assert_eq!(note_start, 3);
+        let graphemes: Vec<&str> = text.text.graphemes(true).collect();
+        assert_eq!(
+            table.render_line(
+                &text,
+                &graphemes,
+                (0, graphemes.len(), false),
+                graphemes.len(),
+                false
+            ),
+            Theme::paint("abc", &theme.text)
+                + &Theme::paint(&graphemes[3..].concat(), &theme.truncated)
+        );
+    }
+
+    #[test]
+    fn test_streaming_wrap() {
+        let names = vec!["text".to_string()];
+        let mut table = streaming_table(&[
+            "--sample-rows",
+            "0",
+            "--max-width",
+            "2",
+            "--overflow",
+            "wrap",
+        ]);
+        let mut output = table.add_rows(&names, vec![cells(&["abcde"]), cells(&["a\nb"])]);
+        output.push_str(&table.close(&names));
+        assert_eq!(
+            output,
+            "┌──┐\n│te│\n│xt│\n├──┤\n│ab│\n│cd│\n│e │\n│a │\n│b │\n└──┘\n"
+        );
+    }
+}
@@ -3,17 +3,21 @@ use std::io::Write;
 
 use is_terminal::IsTerminal;
 use termcolor;
-use termcolor::WriteColor;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::commands::{export::TextVerticalDestinationOptions, ApplicationArguments, UseColor};
-use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row};
+use crate::destinations::text::TextCell;
 use crate::utils::fileorstdout::FileOrStdout;
-use crate::utils::{escape_binary_data, truncate_text_with_note};
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::theme::Theme;
 
 pub struct TextVerticalDestination {
     truncate: Option<u64>,
+    formatter: ValueFormatter,
     column_names: Vec<String>,
-    use_color: bool,
+    column_types: Vec<ColumnType>,
+    theme: Option<Theme>,
     writer: FileOrStdout,
     sort_columns: bool,
 }
@@ -22,6 +26,7 @@ impl TextVerticalDestination {
     pub fn init(
         args: &ApplicationArguments,
         options: &TextVerticalDestinationOptions,
+        formatter: &ValueFormatter,
     ) -> TextVerticalDestination {
         let use_color = match args.color {
             UseColor::Yes => true,
@@ -39,9 +44,15 @@ impl TextVerticalDestination {
 
         TextVerticalDestination {
             truncate: options.truncate,
+            formatter: formatter.clone(),
             sort_columns: options.sort_columns,
             column_names: vec![],
-            use_color,
+            column_types: vec![],
+            theme: if use_color {
+                Some(Theme::from_config())
+            } else {
+                None
+            },
             writer,
         }
     }
@@ -51,11 +62,9 @@ impl DataDestination for TextVerticalDestination {
     fn prepare(&mut self) {}
 
     fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
-        self.column_names = result_iterator
-            .get_column_info()
-            .iter()
-            .map(|col| col.name.clone())
-            .collect();
+        let column_info = result_iterator.get_column_info();
+        self.column_names = column_info.iter().map(|col| col.name.clone()).collect();
+        self.column_types = column_info.into_iter().map(|col| col.data_type).collect();
     }
     fn add_rows(&mut self, rows: &[Row]) {
         for row in rows {
@@ -64,55 +73,39 @@ impl DataDestination for TextVerticalDestination {
             self.writer
                 .write_all(&"──────────\n".to_string().into_bytes())
                 .unwrap();
-            for (idx, col) in row.iter().enumerate() {
-                let content = match col {
-                    Value::U64(value) => value.to_string(),
-                    Value::I64(value) => value.to_string(),
-                    Value::U32(value) => value.to_string(),
-                    Value::I32(value) => value.to_string(),
-                    Value::U16(value) => value.to_string(),
-                    Value::I16(value) => value.to_string(),
-                    Value::U8(value) => value.to_string(),
-                    Value::I8(value) => value.to_string(),
-                    Value::F64(value) => value.to_string(),
-                    Value::F32(value) => value.to_string(),
-                    Value::String(value) => {
-                        truncate_text_with_note(value.to_string(), self.truncate)
-                    }
-                    Value::Bool(value) => value.to_string(),
-                    Value::Bytes(value) => escape_binary_data(value),
-                    Value::None => "".to_string(),
-                    Value::Timestamp(value) => value.to_string(),
-                    Value::Date(date) => format!("{}", date.format("%Y-%m-%d")),
-                    Value::Time(time) => format!("{}", time.format("%H:%M:%S")),
-                    Value::DateTime(datetime) => {
-                        format!("{}", datetime.format("%Y-%m-%d %H:%M:%S"))
+            for (idx, (col, data_type)) in row.iter().zip(&self.column_types).enumerate() {
+                let content = match &self.theme {
+                    None => self
+                        .formatter
+                        .format_or_null(col, data_type, self.truncate, ""),
+                    Some(theme) => {
+                        let cell = TextCell::from_value(
+                            col,
+                            data_type,
+                            self.truncate,
+                            &self.formatter,
+                            Some(&theme.null_marker),
+                        );
+                        let graphemes: Vec<&str> = cell.text.graphemes(true).collect();
+                        let split = cell.note_start.unwrap_or(graphemes.len());
+                        Theme::paint(&graphemes[..split].concat(), theme.style_for(cell.kind))
+                            + &Theme::paint(&graphemes[split..].concat(), &theme.truncated)
                     }
-
-                    _ => panic!("text-vertical: unsupported type: {:?}", col),
                 };
                 row_data.push((idx, content));
             }
             if self.sort_columns {
                 row_data.sort_by(|a, b| self.column_names[a.0].cmp(&self.column_names[b.0]));
             }
+            let mut output = String::new();
             for (idx, content) in row_data {
-                if self.use_color {
-                    if let FileOrStdout::ColorStdout(ref mut s) = self.writer {
-                        s.set_color(termcolor::ColorSpec::new().set_bold(true))
-                            .unwrap();
-                        write!(s, "{}", self.column_names[idx]).unwrap();
-                        s.set_color(&termcolor::ColorSpec::new()).unwrap();
-                        writeln!(s, ": {}", content).unwrap();
-                    }
-                } else {
-                    self.writer
-                        .write_all(
-                            &format!("{}: {}\n", self.column_names[idx], content).into_bytes(),
-                        )
-                        .unwrap();
-                }
+                let name = match &self.theme {
+                    None => self.column_names[idx].clone(),
+                    Some(theme) => Theme::paint(&self.column_names[idx], &theme.header),
+                };
+                output.push_str(&format!("{}: {}\n", name, content));
             }
+            self.writer.write_all(output.as_bytes()).unwrap();
         }
     }
 
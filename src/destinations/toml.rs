@@ -0,0 +1,198 @@
+use std::io::Write;
+
+use chrono::{Datelike, Timelike};
+use toml::value::{Date, Datetime, Time};
+
+use crate::commands::export::TOMLDestinationOptions;
+use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::truncate_text_with_note;
+
+/// bare key if possible, quoted otherwise
+pub fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+fn toml_date(date: &chrono::NaiveDate) -> Date {
+    Date {
+        year: date.year() as u16,
+        month: date.month() as u8,
+        day: date.day() as u8,
+    }
+}
+
+fn toml_time(time: &chrono::NaiveTime) -> Time {
+    Time {
+        hour: time.hour() as u8,
+        minute: time.minute() as u8,
+        second: time.second() as u8,
+        nanosecond: time.nanosecond(),
+    }
+}
+
+pub struct TOMLDestination {
+    writer: FileOrStdout,
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    table: String,
+    column_names: Vec<String>,
+}
+
+impl TOMLDestination {
+    pub fn init(options: &TOMLDestinationOptions, formatter: &ValueFormatter) -> TOMLDestination {
+        TOMLDestination {
+            writer: match options.filename.as_str() {
+                "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                    termcolor::ColorChoice::Never,
+                )),
+                _ => FileOrStdout::File(std::fs::File::create(&options.filename).unwrap()),
+            },
+            truncate: options.truncate,
+            formatter: formatter.clone(),
+            table: toml_key(&options.table),
+            column_names: vec![],
+        }
+    }
+
+    /// NULL has no representation in toml, None means value should be omitted
+    pub fn value_to_toml(&self, value: &Value) -> Option<toml::Value> {
+        Some(match value {
+            Value::U64(value) => match i64::try_from(*value) {
+                Ok(value) => toml::Value::Integer(value),
+                //toml integers are 64 bit signed
+                Err(_) => toml::Value::String(value.to_string()),
+            },
+            Value::I64(value) => toml::Value::Integer(*value),
+            Value::U32(value) => toml::Value::Integer(i64::from(*value)),
+            Value::I32(value) => toml::Value::Integer(i64::from(*value)),
+            Value::U16(value) => toml::Value::Integer(i64::from(*value)),
+            Value::I16(value) => toml::Value::Integer(i64::from(*value)),
+            Value::U8(value) => toml::Value::Integer(i64::from(*value)),
+            Value::I8(value) => toml::Value::Integer(i64::from(*value)),
+            Value::F64(value) => toml::Value::Float(self.formatter.round(*value)),
+            Value::F32(value) => toml::Value::Float(f64::from(self.formatter.round(*value))),
+            Value::String(value) => {
+                toml::Value::String(truncate_text_with_note(value.to_string(), self.truncate))
+            }
+            Value::Bool(value) => toml::Value::Boolean(*value),
+            Value::Bytes(value) => toml::Value::String(self.formatter.bytes(value)),
+            Value::None => return None,
+            Value::Timestamp(value) => match i64::try_from(*value) {
+                Ok(value) => toml::Value::Integer(value),
+                Err(_) => toml::Value::String(value.to_string()),
+            },
+            Value::Date(date) => toml::Value::Datetime(Datetime {
+                date: Some(toml_date(date)),
+                time: None,
+                offset: None,
+            }),
+            Value::Time(time) => toml::Value::Datetime(Datetime {
+                date: None,
+                time: Some(toml_time(time)),
+                offset: None,
+            }),
+            Value::DateTime(datetime) => toml::Value::Datetime(Datetime {
+                date: Some(toml_date(&datetime.date())),
+                time: Some(toml_time(&datetime.time())),
+                offset: None,
+            }),
+            Value::JSON(value) => toml::Value::String(value.to_string()),
+            Value::Custom(value) => {
+                toml::Value::String(truncate_text_with_note(value.to_string(), self.truncate))
+            }
+        })
+    }
+}
+
+impl DataDestination for TOMLDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        self.column_names = result_iterator
+            .get_column_info()
+            .iter()
+            .map(|c| toml_key(&c.name))
+            .collect();
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let mut batch = String::new();
+        for row in rows {
+            batch.push_str(&format!("[[{}]]\n", self.table));
+            for (name, value) in self.column_names.iter().zip(row.iter()) {
+                if let Some(value) = self.value_to_toml(value) {
+                    batch.push_str(&format!("{} = {}\n", name, value));
+                }
+            }
+            batch.push('\n');
+        }
+        self.writer.write_all(batch.as_bytes()).unwrap();
+    }
+
+    fn close(&mut self) {
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::ColumnType;
+    use crate::utils::testing::{export, temp_path};
+    use clap::Parser;
+
+    #[test]
+    fn test_round_trip() {
+        let filename = temp_path("export.toml");
+        let options = TOMLDestinationOptions::try_parse_from([
+            "toml",
+            filename.as_str(),
+            "--table",
+            "user rows",
+        ])
+        .unwrap();
+        let datetime = chrono::NaiveDate::from_ymd_opt(2024, 3, 5)
+            .unwrap()
+            .and_hms_micro_opt(10, 20, 30, 500)
+            .unwrap();
+        export(
+            &mut TOMLDestination::init(&options, &ValueFormatter::default()),
+            &[
+                ("id", ColumnType::U64),
+                ("first name", ColumnType::String),
+                ("seen", ColumnType::DateTime),
+            ],
+            vec![vec![
+                vec![
+                    Value::U64(u64::MAX),
+                    Value::String("a \"b\"\n".to_string()),
+                    Value::DateTime(datetime),
+                ],
+                vec![Value::U64(2), Value::None, Value::None],
+            ]],
+        );
+        let document: toml::Table =
+            toml::from_str(&std::fs::read_to_string(&filename).unwrap()).unwrap();
+        let rows = document["user rows"].as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["id"].as_str(), Some(u64::MAX.to_string().as_str()));
+        assert_eq!(rows[0]["first name"].as_str(), Some("a \"b\"\n"));
+        assert_eq!(
+            rows[0]["seen"].as_datetime().unwrap().to_string(),
+            "2024-03-05T10:20:30.0005"
+        );
+        //NULL values are omitted
+        assert_eq!(rows[1].as_table().unwrap().len(), 1);
+        assert_eq!(rows[1]["id"].as_integer(), Some(2));
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
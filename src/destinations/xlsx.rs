@@ -1,38 +1,224 @@
-use rust_xlsxwriter;
+use rust_xlsxwriter::{self, Color, Format, FormatBorder, FormatUnderline, TableStyle};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::commands::export::SpreadSheetDestinationOptions;
-use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::commands::export::{SheetOverflow, SpreadSheetDestinationOptions};
+use crate::definitions::{
+    ColumnInfo, ColumnType, DataDestination, DataSourceBatchIterator, Row, Value,
+};
+use crate::destinations::spreadsheet::{
+    max_sheet_rows, numbered_filename, sheet_name, use_low_memory,
+};
 use crate::utils::{escape_binary_data, truncate_text_with_note};
 
+//autofit never makes columns wider than this (in characters)
+const MAX_COLUMN_WIDTH: usize = 60;
+
+fn parse_color(name: &str) -> Result<Color, String> {
+    let rgb = match name {
+        "black" => 0x000000,
+        "white" => 0xFFFFFF,
+        "red" => 0xFF0000,
+        "green" => 0x008000,
+        "blue" => 0x0000FF,
+        "yellow" => 0xFFFF00,
+        "orange" => 0xFFA500,
+        "gray" | "grey" => 0x808080,
+        "lightgray" | "lightgrey" => 0xD9D9D9,
+        _ => match name.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 => {
+                u32::from_str_radix(hex, 16).map_err(|_| format!("invalid colour: {}", name))?
+            }
+            _ => return Err(format!("unknown colour: {} (use name or #RRGGBB)", name)),
+        },
+    };
+    Ok(Color::RGB(rgb))
+}
+
+/// space separated list of: bold, italic, underline, border, wrap, fg:<colour>, bg:<colour>
+pub fn parse_header_format(spec: &str) -> Result<Format, String> {
+    let mut format = Format::new();
+    for token in spec.split_whitespace() {
+        format = match token {
+            "bold" => format.set_bold(),
+            "italic" => format.set_italic(),
+            "underline" => format.set_underline(FormatUnderline::Single),
+            "border" => format.set_border(FormatBorder::Thin),
+            "wrap" => format.set_text_wrap(),
+            _ => {
+                if let Some(color) = token.strip_prefix("fg:") {
+                    format.set_font_color(parse_color(color)?)
+                } else if let Some(color) = token.strip_prefix("bg:") {
+                    format.set_background_color(parse_color(color)?)
+                } else {
+                    return Err(format!("unknown header format: {}", token));
+                }
+            }
+        };
+    }
+    Ok(format)
+}
+
+/// table style name like "Medium9", "light1" or "none"
+pub fn parse_table_style(name: &str) -> Result<TableStyle, String> {
+    use TableStyle::*;
+    let styles = [
+        None, Light1, Light2, Light3, Light4, Light5, Light6, Light7, Light8, Light9, Light10,
+        Light11, Light12, Light13, Light14, Light15, Light16, Light17, Light18, Light19, Light20,
+        Light21, Medium1, Medium2, Medium3, Medium4, Medium5, Medium6, Medium7, Medium8, Medium9,
+        Medium10, Medium11, Medium12, Medium13, Medium14, Medium15, Medium16, Medium17, Medium18,
+        Medium19, Medium20, Medium21, Medium22, Medium23, Medium24, Medium25, Medium26, Medium27,
+        Medium28, Dark1, Dark2, Dark3, Dark4, Dark5, Dark6, Dark7, Dark8, Dark9, Dark10, Dark11,
+    ];
+    if name.eq_ignore_ascii_case("none") {
+        return Ok(TableStyle::None);
+    }
+    styles
+        .into_iter()
+        .find(|style| {
+            style
+                .to_string()
+                .strip_prefix("TableStyle")
+                .is_some_and(|style_name| style_name.eq_ignore_ascii_case(name))
+        })
+        .ok_or(format!(
+            "unknown table style: {} (expected none, light1-21, medium1-28 or dark1-11)",
+            name
+        ))
+}
+
+/// width of value as displayed by spreadsheet, in characters
+fn value_width(value: &Value, truncate: Option<u64>) -> usize {
+    match value {
+        Value::String(value) | Value::Custom(value) | Value::JSON(value) => {
+            truncate_text_with_note(value.to_string(), truncate)
+                .lines()
+                .map(|line| line.graphemes(true).count())
+                .max()
+                .unwrap_or(0)
+        }
+        Value::Bytes(value) => escape_binary_data(value).len(),
+        Value::None => 0,
+        Value::Date(_) => 10,
+        Value::Time(_) => 8,
+        Value::DateTime(_) | Value::Timestamp(_) => 19,
+        Value::F64(value) => format!("{:.2}", value).len(),
+        Value::F32(value) => format!("{:.2}", value).len(),
+        Value::U64(value) => value.to_string().len(),
+        Value::I64(value) => value.to_string().len(),
+        Value::U32(value) => value.to_string().len(),
+        Value::I32(value) => value.to_string().len(),
+        Value::U16(value) => value.to_string().len(),
+        Value::I16(value) => value.to_string().len(),
+        Value::U8(value) => value.to_string().len(),
+        Value::I8(value) => value.to_string().len(),
+        Value::Bool(_) => 5,
+    }
+}
+
 pub struct SpreadSheetXLSXDestination {
     filename: String,
     workbook: rust_xlsxwriter::Workbook,
     sheet_row_count: usize,
     truncate: Option<u64>,
+    header_format: Format,
+    autofilter: bool,
+    freeze_header: bool,
+    freeze_columns: u16,
+    autofit: bool,
+    autofit_rows: usize,
+    table_style: Option<TableStyle>,
+    low_memory: bool,
+    low_memory_threshold: u64,
+    //current result is written in constant memory mode
+    sheet_low_memory: bool,
+    on_row_limit: SheetOverflow,
+    max_sheet_rows: usize,
+    column_info: Vec<ColumnInfo>,
+    column_widths: Vec<usize>,
+    //worksheet index within current workbook
+    sheet_index: usize,
+    //name of current result, set when exporting several queries (--sheet)
+    sheet_title: Option<String>,
+    sheet_number: usize,
+    file_number: usize,
+    row_count: usize,
 }
 
 impl SpreadSheetXLSXDestination {
     pub fn init(spreadsheet_options: &SpreadSheetDestinationOptions) -> SpreadSheetXLSXDestination {
+        let header_format =
+            parse_header_format(&spreadsheet_options.header_format).unwrap_or_else(|e| {
+                eprintln!("xlsx: {}", e);
+                std::process::exit(1);
+            });
+        let table_style = spreadsheet_options.table.as_ref().map(|name| {
+            parse_table_style(name).unwrap_or_else(|e| {
+                eprintln!("xlsx: {}", e);
+                std::process::exit(1);
+            })
+        });
+        if spreadsheet_options.low_memory && table_style.is_some() {
+            eprintln!("xlsx: --table is not available with --low-memory");
+            std::process::exit(1);
+        }
         SpreadSheetXLSXDestination {
             filename: spreadsheet_options.filename.clone(),
             workbook: rust_xlsxwriter::Workbook::new(),
             sheet_row_count: 0,
             truncate: spreadsheet_options.truncate,
+            header_format,
+            autofilter: !spreadsheet_options.no_autofilter,
+            freeze_header: !spreadsheet_options.no_freeze_header,
+            freeze_columns: spreadsheet_options.freeze_columns,
+            autofit: !spreadsheet_options.no_autofit,
+            autofit_rows: spreadsheet_options.autofit_rows,
+            table_style,
+            low_memory: spreadsheet_options.low_memory,
+            low_memory_threshold: spreadsheet_options.low_memory_threshold,
+            sheet_low_memory: false,
+            on_row_limit: spreadsheet_options.on_row_limit,
+            max_sheet_rows: max_sheet_rows(spreadsheet_options),
+            column_info: vec![],
+            column_widths: vec![],
+            sheet_index: 0,
+            sheet_title: None,
+            sheet_number: 0,
+            file_number: 1,
+            row_count: 0,
         }
     }
-}
 
-impl DataDestination for SpreadSheetXLSXDestination {
-    fn prepare(&mut self) {}
+    /// next result goes to its own worksheet with given name
+    pub fn set_sheet_title(&mut self, title: &str) {
+        if self.sheet_number > 0 {
+            self.finish_sheet();
+        }
+        self.sheet_title = Some(title.to_string());
+        self.sheet_number = 0;
+        self.row_count = 0;
+    }
 
-    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
-        let worksheet = self.workbook.add_worksheet();
+    /// add worksheet with header
+    fn start_sheet(&mut self) {
+        self.sheet_number += 1;
+        self.sheet_index = self.workbook.worksheets().len();
+        //constant memory worksheet writes each row to temporary file once next row starts
+        let worksheet = if self.sheet_low_memory {
+            self.workbook.add_worksheet_with_constant_memory()
+        } else {
+            self.workbook.add_worksheet()
+        };
+        worksheet
+            .set_name(sheet_name(self.sheet_title.as_deref(), self.sheet_number))
+            .unwrap();
         let datetime_format = rust_xlsxwriter::Format::new().set_num_format("yyyy-mm-ddThh:mm:ss");
         let date_format = rust_xlsxwriter::Format::new().set_num_format("yyyy-mm-dd");
         let time_format = rust_xlsxwriter::Format::new().set_num_format("hh:mm:ss");
 
-        for (idx, column) in result_iterator.get_column_info().iter().enumerate() {
-            worksheet.write(0, idx as u16, column.name.clone()).unwrap();
+        for (idx, column) in self.column_info.iter().enumerate() {
+            worksheet
+                .write_with_format(0, idx as u16, column.name.clone(), &self.header_format)
+                .unwrap();
             match column.data_type {
                 ColumnType::Date => {
                     worksheet
@@ -57,12 +243,135 @@ impl DataDestination for SpreadSheetXLSXDestination {
                 _ => {}
             };
         }
-        self.sheet_row_count += 1;
+        if self.freeze_header || self.freeze_columns > 0 {
+            worksheet
+                .set_freeze_panes(u32::from(self.freeze_header), self.freeze_columns)
+                .unwrap();
+        }
+        self.sheet_row_count = 1;
+    }
+
+    /// add autofilter, table and column widths, which need to know how many rows sheet has
+    fn finish_sheet(&mut self) {
+        let worksheet = self
+            .workbook
+            .worksheet_from_index(self.sheet_index)
+            .unwrap();
+        if self.column_info.is_empty() {
+            return;
+        }
+        let last_column = (self.column_info.len() - 1) as u16;
+        //table needs at least one data row
+        let last_row = (self.sheet_row_count as u32 - 1).max(1);
+        //table would rewrite header row, which is already on disk in constant memory mode
+        if let Some(style) = self.table_style.filter(|_| !self.sheet_low_memory) {
+            //table column names must be unique
+            let mut seen = std::collections::HashSet::new();
+            let columns: Vec<rust_xlsxwriter::TableColumn> = self
+                .column_info
+                .iter()
+                .map(|column| {
+                    let mut unique_name = column.name.clone();
+                    let mut counter = 1;
+                    while !seen.insert(unique_name.to_lowercase()) {
+                        counter += 1;
+                        unique_name = format!("{}_{}", column.name, counter);
+                    }
+                    rust_xlsxwriter::TableColumn::new()
+                        .set_header(unique_name)
+                        .set_header_format(&self.header_format)
+                })
+                .collect();
+            let table = rust_xlsxwriter::Table::new()
+                .set_style(style)
+                .set_autofilter(self.autofilter)
+                .set_columns(&columns);
+            worksheet
+                .add_table(0, 0, last_row, last_column, &table)
+                .unwrap();
+        } else if self.autofilter {
+            worksheet.autofilter(0, 0, last_row, last_column).unwrap();
+        }
+        if self.autofit {
+            for (idx, width) in self.column_widths.iter().enumerate() {
+                worksheet
+                    .set_column_width(idx as u16, (*width).min(MAX_COLUMN_WIDTH) as f64 + 1.0)
+                    .unwrap();
+            }
+        }
+    }
+
+    fn save(&mut self) {
+        let filename = numbered_filename(&self.filename, self.file_number);
+        self.workbook.save(&filename).unwrap_or_else(|e| {
+            eprintln!("xlsx: could not save {}: {}", filename, e);
+            std::process::exit(1);
+        });
+    }
+
+    /// current sheet is full, continue according to --on-row-limit
+    fn overflow(&mut self) {
+        match self.on_row_limit {
+            SheetOverflow::Error => {
+                eprintln!(
+                    "xlsx: result has more than {} rows, which don't fit in one sheet (see --on-row-limit)",
+                    self.max_sheet_rows - 1
+                );
+                std::process::exit(1);
+            }
+            SheetOverflow::NewSheet => {
+                self.finish_sheet();
+                self.start_sheet();
+            }
+            SheetOverflow::NewFile => {
+                self.finish_sheet();
+                self.save();
+                self.workbook = rust_xlsxwriter::Workbook::new();
+                self.file_number += 1;
+                self.sheet_number = 0;
+                self.start_sheet();
+            }
+        }
+    }
+}
+
+impl DataDestination for SpreadSheetXLSXDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let count = result_iterator.get_count();
+        self.sheet_low_memory = use_low_memory(self.low_memory, self.low_memory_threshold, count);
+        if self.sheet_low_memory && !self.low_memory && self.table_style.is_some() {
+            eprintln!(
+                "xlsx: result has {} rows, switching to --low-memory mode, --table is ignored",
+                count.unwrap_or_default()
+            );
+        }
+        self.column_info = result_iterator.get_column_info();
+        //room for autofilter button
+        self.column_widths = self
+            .column_info
+            .iter()
+            .map(|column| column.name.graphemes(true).count() + 3)
+            .collect();
+        self.start_sheet();
     }
 
     fn add_rows(&mut self, rows: &[Row]) {
-        let worksheet = self.workbook.worksheet_from_index(0).unwrap();
         for row in rows {
+            if self.sheet_row_count == self.max_sheet_rows {
+                self.overflow();
+            }
+            if self.autofit && self.row_count < self.autofit_rows {
+                for (idx, val) in row.iter().enumerate() {
+                    self.column_widths[idx] =
+                        self.column_widths[idx].max(value_width(val, self.truncate));
+                }
+            }
+            let worksheet = self
+                .workbook
+                .worksheet_from_index(self.sheet_index)
+                .unwrap();
             for (idx, val) in row.iter().enumerate() {
                 match val {
                     Value::U64(value) => worksheet.write_number(
@@ -99,11 +408,12 @@ impl DataDestination for SpreadSheetXLSXDestination {
                     Value::F32(value) => {
                         worksheet.write_number(self.sheet_row_count as u32, idx as u16, *value)
                     }
-                    Value::String(value) => worksheet.write_string(
-                        self.sheet_row_count as u32,
-                        idx as u16,
-                        truncate_text_with_note(value.to_string(), self.truncate),
-                    ),
+                    Value::String(value) | Value::JSON(value) | Value::Custom(value) => worksheet
+                        .write_string(
+                            self.sheet_row_count as u32,
+                            idx as u16,
+                            truncate_text_with_note(value.to_string(), self.truncate),
+                        ),
                     Value::Bool(value) => {
                         worksheet.write_boolean(self.sheet_row_count as u32, idx as u16, *value)
                     }
@@ -129,15 +439,187 @@ impl DataDestination for SpreadSheetXLSXDestination {
                     Value::DateTime(datetime) => {
                         worksheet.write_datetime(self.sheet_row_count as u32, idx as u16, datetime)
                     }
-                    _ => panic!("spsheet: unsupported type: {:?}", val),
                 }
                 .unwrap();
             }
             self.sheet_row_count += 1;
+            self.row_count += 1;
         }
     }
 
     fn close(&mut self) {
-        self.workbook.save(self.filename.clone()).unwrap()
+        self.finish_sheet();
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{exits_with, export, temp_path, zip_entry};
+    use clap::Parser;
+
+    fn export_xlsx(args: &[&str], rows: Vec<Row>) -> String {
+        let filename = temp_path("export.xlsx");
+        let options = SpreadSheetDestinationOptions::try_parse_from(
+            ["xlsx", filename.as_str()].iter().chain(args),
+        )
+        .unwrap();
+        export(
+            &mut SpreadSheetXLSXDestination::init(&options),
+            &[("id", ColumnType::I64), ("name", ColumnType::String)],
+            vec![rows],
+        );
+        filename
+    }
+
+    #[test]
+    fn test_sheet_format() {
+        let filename = export_xlsx(
+            &[
+                "--header-format",
+                "italic bg:#00FF00",
+                "--freeze-columns",
+                "1",
+            ],
+            vec![
+                vec![Value::I64(1), Value::String("a long name".to_string())],
+                vec![Value::I64(2), Value::None],
+            ],
+        );
+        let sheet = zip_entry(&filename, "xl/worksheets/sheet1.xml");
+        //header cells use second cell format: italic font, green fill
+        assert!(sheet.contains(r#"<c r="A1" s="1" t="s">"#), "{}", sheet);
+        let styles = zip_entry(&filename, "xl/styles.xml");
+        assert!(styles.contains("<font><i/>"), "{}", styles);
+        assert!(
+            styles.contains(r#"<fgColor rgb="FF00FF00"/>"#),
+            "{}",
+            styles
+        );
+        assert!(sheet.contains(r#"<pane xSplit="1" ySplit="1" topLeftCell="B2""#));
+        assert!(sheet.contains(r#"<autoFilter ref="A1:B3"/>"#));
+        //widest value plus one, header width includes autofilter button
+        assert!(sheet.contains(r#"<col min="1" max="1" width="6.7109375""#));
+        assert!(sheet.contains(r#"<col min="2" max="2" width="12.7109375""#));
+        std::fs::remove_file(&filename).unwrap();
+
+        let filename = export_xlsx(
+            &["--no-autofilter", "--no-freeze-header", "--no-autofit"],
+            vec![vec![Value::I64(1), Value::String("a".to_string())]],
+        );
+        let sheet = zip_entry(&filename, "xl/worksheets/sheet1.xml");
+        assert!(!sheet.contains("<pane"), "{}", sheet);
+        assert!(!sheet.contains("<autoFilter"), "{}", sheet);
+        assert!(!sheet.contains("<cols>"), "{}", sheet);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_table() {
+        let filename = export_xlsx(
+            &["--table", "light2"],
+            vec![vec![Value::I64(1), Value::String("a".to_string())]],
+        );
+        let table = zip_entry(&filename, "xl/tables/table1.xml");
+        assert!(table.contains(r#"ref="A1:B2""#), "{}", table);
+        assert!(table.contains(r#"<tableStyleInfo name="TableStyleLight2""#));
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_formats() {
+        assert_eq!(
+            parse_header_format("bold blink").unwrap_err(),
+            "unknown header format: blink"
+        );
+        assert_eq!(
+            parse_header_format("fg:#12345").unwrap_err(),
+            "unknown colour: #12345 (use name or #RRGGBB)"
+        );
+        assert!(parse_table_style("Medium28").is_ok());
+        assert!(parse_table_style("medium29").is_err());
+    }
+
+    fn numbered_rows(count: i64) -> Vec<Row> {
+        (1..=count)
+            .map(|id| vec![Value::I64(id), Value::String(format!("row {}", id))])
+            .collect()
+    }
+
+    #[test]
+    fn test_row_limit() {
+        let filename = export_xlsx(&["--max-sheet-rows", "3"], numbered_rows(5));
+        let workbook = zip_entry(&filename, "xl/workbook.xml");
+        for name in ["Sheet 1", "Sheet 2", "Sheet 3"] {
+            assert!(workbook.contains(&format!(r#"<sheet name="{}""#, name)));
+        }
+        //header is repeated on every sheet
+        let sheet = zip_entry(&filename, "xl/worksheets/sheet3.xml");
+        assert!(sheet.contains(r#"<c r="A1" s="1" t="s">"#), "{}", sheet);
+        assert!(sheet.contains(r#"<c r="A2"><v>5</v></c>"#), "{}", sheet);
+        assert!(!sheet.contains(r#"r="A3""#), "{}", sheet);
+        std::fs::remove_file(&filename).unwrap();
+
+        let filename = export_xlsx(
+            &["--max-sheet-rows", "3", "--on-row-limit", "new-file"],
+            numbered_rows(5),
+        );
+        for (number, last_id) in [(1, 2), (2, 4), (3, 5)] {
+            let numbered = numbered_filename(&filename, number);
+            let workbook = zip_entry(&numbered, "xl/workbook.xml");
+            assert!(
+                workbook.contains(r#"<sheet name="Sheet 1""#),
+                "{}",
+                workbook
+            );
+            assert!(!workbook.contains("Sheet 2"), "{}", workbook);
+            let sheet = zip_entry(&numbered, "xl/worksheets/sheet1.xml");
+            assert!(sheet.contains(&format!("<v>{}</v></c><c r=", last_id)));
+            std::fs::remove_file(&numbered).unwrap();
+        }
+
+        let stderr = exits_with(module_path!(), "test_row_limit", || {
+            export_xlsx(
+                &["--max-sheet-rows", "3", "--on-row-limit", "error"],
+                numbered_rows(3),
+            );
+        });
+        assert!(
+            stderr.contains("xlsx: result has more than 2 rows"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn test_low_memory() {
+        let filename = export_xlsx(&["--low-memory", "--max-sheet-rows", "3"], numbered_rows(3));
+        //constant memory worksheets write strings inline instead of shared strings table
+        let sheet = zip_entry(&filename, "xl/worksheets/sheet1.xml");
+        assert!(
+            sheet.contains(r#"<c r="B3" t="inlineStr"><is><t>row 2</t></is></c>"#),
+            "{}",
+            sheet
+        );
+        assert!(sheet.contains(r#"<autoFilter ref="A1:B3"/>"#), "{}", sheet);
+        let sheet = zip_entry(&filename, "xl/worksheets/sheet2.xml");
+        assert!(sheet.contains(r#"<c r="A1" s="1" t="inlineStr"><is><t>id</t>"#));
+        assert!(sheet.contains(r#"<c r="A2"><v>3</v></c>"#), "{}", sheet);
+        std::fs::remove_file(&filename).unwrap();
+
+        assert!(!use_low_memory(false, 10, None));
+        assert!(!use_low_memory(false, 10, Some(10)));
+        assert!(use_low_memory(false, 10, Some(11)));
+        assert!(use_low_memory(true, 10, None));
+
+        let stderr = exits_with(module_path!(), "test_low_memory", || {
+            export_xlsx(&["--low-memory", "--table"], vec![]);
+        });
+        assert!(
+            stderr.contains("xlsx: --table is not available with --low-memory"),
+            "{}",
+            stderr
+        );
     }
 }
@@ -0,0 +1,224 @@
+use std::io::Write;
+
+use crate::commands::export::{XMLDestinationOptions, XMLMode};
+use crate::definitions::{ColumnType, DataDestination, DataSourceBatchIterator, Row};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::{unique_names, xml_escape};
+
+fn is_xml_name_start_char(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_xml_name_char(c: char) -> bool {
+    is_xml_name_start_char(c) || c.is_numeric() || c == '-' || c == '.'
+}
+
+pub fn is_valid_xml_name(name: &str) -> bool {
+    match name.chars().next() {
+        Some(c) => is_xml_name_start_char(c) && name.chars().all(is_xml_name_char),
+        None => false,
+    }
+}
+
+/// turn column name into valid xml name, encoding invalid characters as _xHHHH_
+/// (same convention as XmlConvert.EncodeName and SQL Server)
+pub fn xml_name(name: &str) -> String {
+    if name.is_empty() {
+        return "_".to_string();
+    }
+    let mut result = String::with_capacity(name.len());
+    for (idx, c) in name.chars().enumerate() {
+        let valid = if idx == 0 {
+            is_xml_name_start_char(c)
+        } else {
+            is_xml_name_char(c)
+        };
+        if valid {
+            result.push(c);
+        } else {
+            result.push_str(&format!("_x{:04X}_", c as u32));
+        }
+    }
+    result
+}
+
+pub struct XMLDestination {
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    column_names: Vec<String>,
+    column_types: Vec<ColumnType>,
+    writer: FileOrStdout,
+    root: String,
+    row: String,
+    mode: XMLMode,
+}
+
+impl XMLDestination {
+    pub fn init(options: &XMLDestinationOptions, formatter: &ValueFormatter) -> XMLDestination {
+        for (option, name) in [("root", &options.root), ("row", &options.row)] {
+            if !is_valid_xml_name(name) {
+                eprintln!(
+                    "xml: {} element name {} is not a valid xml name",
+                    option, name
+                );
+                std::process::exit(1);
+            }
+        }
+        XMLDestination {
+            truncate: options.truncate,
+            formatter: formatter.clone(),
+            column_names: vec![],
+            column_types: vec![],
+            writer: match options.filename.as_ref() {
+                "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                    termcolor::ColorChoice::Auto,
+                )),
+                _ => FileOrStdout::File(std::fs::File::create(options.filename.clone()).unwrap()),
+            },
+            root: options.root.clone(),
+            row: options.row.clone(),
+            mode: options.mode,
+        }
+    }
+}
+
+impl DataDestination for XMLDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        let column_info = result_iterator.get_column_info();
+        self.column_names = column_info.iter().map(|col| xml_name(&col.name)).collect();
+        self.column_types = column_info.into_iter().map(|col| col.data_type).collect();
+        if self.mode == XMLMode::Attributes {
+            //attribute names must be unique within element
+            self.column_names = unique_names(std::mem::take(&mut self.column_names));
+        }
+        let namespace = match self.mode {
+            XMLMode::Elements => " xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"",
+            XMLMode::Attributes => "",
+        };
+        self.writer
+            .write_all(
+                format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{}{}>\n",
+                    self.root, namespace
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let mut batch = String::new();
+        for row in rows {
+            match self.mode {
+                XMLMode::Elements => {
+                    batch.push_str(&format!("  <{}>\n", self.row));
+                    for ((name, data_type), value) in
+                        self.column_names.iter().zip(&self.column_types).zip(row)
+                    {
+                        match self.formatter.format(value, data_type, self.truncate) {
+                            Some(text) => batch.push_str(&format!(
+                                "    <{}>{}</{}>\n",
+                                name,
+                                xml_escape(&text),
+                                name
+                            )),
+                            None => batch.push_str(&format!("    <{} xsi:nil=\"true\"/>\n", name)),
+                        }
+                    }
+                    batch.push_str(&format!("  </{}>\n", self.row));
+                }
+                XMLMode::Attributes => {
+                    //attributes can't be nil, NULL values are omitted
+                    batch.push_str(&format!("  <{}", self.row));
+                    for ((name, data_type), value) in
+                        self.column_names.iter().zip(&self.column_types).zip(row)
+                    {
+                        if let Some(text) = self.formatter.format(value, data_type, self.truncate) {
+                            batch.push_str(&format!(" {}=\"{}\"", name, xml_escape(&text)));
+                        }
+                    }
+                    batch.push_str("/>\n");
+                }
+            }
+        }
+        self.writer.write_all(batch.as_bytes()).unwrap();
+    }
+
+    fn close(&mut self) {
+        self.writer
+            .write_all(format!("</{}>\n", self.root).as_bytes())
+            .unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::Value;
+    use crate::utils::testing::{export, temp_path};
+    use clap::Parser;
+
+    fn xml(args: &[&str], columns: &[(&str, ColumnType)], rows: Vec<Row>) -> String {
+        let filename = temp_path("export.xml");
+        let options =
+            XMLDestinationOptions::try_parse_from(["xml", filename.as_str()].iter().chain(args))
+                .unwrap();
+        export(
+            &mut XMLDestination::init(&options, &ValueFormatter::default()),
+            columns,
+            vec![rows],
+        );
+        let output = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_xml_name() {
+        assert_eq!(xml_name("first name"), "first_x0020_name");
+        assert_eq!(xml_name("1st"), "_x0031_st");
+        assert_eq!(xml_name(""), "_");
+        assert!(is_valid_xml_name("a-b.c"));
+    }
+
+    #[test]
+    fn test_elements() {
+        let output = xml(
+            &["--root", "users"],
+            &[("id", ColumnType::I64), ("bio", ColumnType::String)],
+            vec![
+                vec![Value::I64(1), Value::String("<b>&\"".to_string())],
+                vec![Value::I64(2), Value::None],
+            ],
+        );
+        assert_eq!(
+            output,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <users xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n\
+             \x20 <row>\n    <id>1</id>\n    <bio>&lt;b&gt;&amp;&quot;</bio>\n  </row>\n\
+             \x20 <row>\n    <id>2</id>\n    <bio xsi:nil=\"true\"/>\n  </row>\n\
+             </users>\n"
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        let output = xml(
+            &["--mode", "attributes", "--row", "r"],
+            &[
+                ("a", ColumnType::I64),
+                ("a", ColumnType::I64),
+                ("a_2", ColumnType::I64),
+            ],
+            vec![vec![Value::I64(1), Value::I64(2), Value::None]],
+        );
+        assert_eq!(
+            output,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rows>\n  <r a=\"1\" a_3=\"2\"/>\n</rows>\n"
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::commands::export::YAMLDestinationOptions;
+use crate::definitions::{DataDestination, DataSourceBatchIterator, Row, Value};
+use crate::utils::fileorstdout::FileOrStdout;
+use crate::utils::formatter::ValueFormatter;
+use crate::utils::truncate_text_with_note;
+
+/// words that yaml parsers (1.1 and 1.2) would read as something else than a string
+const YAML_RESERVED_WORDS: [&str; 26] = [
+    "~", "null", "Null", "NULL", "true", "True", "TRUE", "false", "False", "FALSE", "yes", "Yes",
+    "YES", "no", "No", "NO", "on", "On", "ON", "off", "Off", "OFF", "y", "Y", "n", "N",
+];
+
+/// double quoted yaml string
+pub fn yaml_quote(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// string as yaml scalar, quoted unless it's safe to leave it plain
+pub fn yaml_string(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || " _-./".contains(c))
+        && !text.ends_with(' ')
+        && !YAML_RESERVED_WORDS.contains(&text)
+        && !text.starts_with(".");
+    if plain {
+        text.to_string()
+    } else {
+        yaml_quote(text)
+    }
+}
+
+pub fn yaml_float(value: f64) -> String {
+    if value.is_nan() {
+        ".nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { ".inf" } else { "-.inf" }.to_string()
+    } else {
+        //debug format always keeps decimal point, so value is read back as float
+        format!("{:?}", value)
+    }
+}
+
+pub struct YAMLDestination {
+    writer: FileOrStdout,
+    truncate: Option<u64>,
+    formatter: ValueFormatter,
+    column_names: Vec<String>,
+    key_column: Option<String>,
+    key_column_idx: Option<usize>,
+    seen_keys: HashSet<String>,
+    rows_written: u64,
+}
+
+impl YAMLDestination {
+    pub fn init(options: &YAMLDestinationOptions, formatter: &ValueFormatter) -> YAMLDestination {
+        YAMLDestination {
+            writer: match options.filename.as_str() {
+                "-" => FileOrStdout::ColorStdout(termcolor::StandardStream::stdout(
+                    termcolor::ColorChoice::Never,
+                )),
+                _ => FileOrStdout::File(std::fs::File::create(&options.filename).unwrap()),
+            },
+            truncate: options.truncate,
+            formatter: formatter.clone(),
+            column_names: vec![],
+            key_column: options.key_column.clone(),
+            key_column_idx: None,
+            seen_keys: HashSet::new(),
+            rows_written: 0,
+        }
+    }
+
+    pub fn value_to_yaml(&self, value: &Value) -> String {
+        match value {
+            Value::U64(value) => value.to_string(),
+            Value::I64(value) => value.to_string(),
+            Value::U32(value) => value.to_string(),
+            Value::I32(value) => value.to_string(),
+            Value::U16(value) => value.to_string(),
+            Value::I16(value) => value.to_string(),
+            Value::U8(value) => value.to_string(),
+            Value::I8(value) => value.to_string(),
+            Value::F64(value) => yaml_float(self.formatter.round(*value)),
+            Value::F32(value) => yaml_float(f64::from(self.formatter.round(*value))),
+            Value::String(value) => {
+                yaml_string(&truncate_text_with_note(value.to_string(), self.truncate))
+            }
+            Value::Bool(value) => value.to_string(),
+            Value::Bytes(value) => yaml_string(&self.formatter.bytes(value)),
+            Value::None => "~".to_string(),
+            Value::Timestamp(value) => value.to_string(),
+            //iso dates stay yaml timestamps, other formats are strings
+            Value::Date(date) => {
+                let text = self.formatter.date(date);
+                if text == date.format("%Y-%m-%d").to_string() {
+                    text
+                } else {
+                    yaml_string(&text)
+                }
+            }
+            //unquoted hh:mm:ss would be read as sexagesimal number by yaml 1.1 parsers
+            Value::Time(time) => yaml_quote(&self.formatter.time(time)),
+            Value::DateTime(datetime) => {
+                let text = self.formatter.datetime(datetime);
+                if text == datetime.format("%Y-%m-%d %H:%M:%S").to_string() {
+                    text
+                } else {
+                    yaml_string(&text)
+                }
+            }
+            Value::JSON(value) => yaml_quote(value),
+            Value::Custom(value) => {
+                yaml_string(&truncate_text_with_note(value.to_string(), self.truncate))
+            }
+        }
+    }
+}
+
+impl DataDestination for YAMLDestination {
+    fn prepare(&mut self) {}
+
+    fn prepare_for_results(&mut self, result_iterator: &dyn DataSourceBatchIterator) {
+        self.column_names = result_iterator
+            .get_column_info()
+            .iter()
+            .map(|c| c.name.clone())
+            .collect();
+        if let Some(key_column) = &self.key_column {
+            self.key_column_idx = self.column_names.iter().position(|c| c == key_column);
+            if self.key_column_idx.is_none() {
+                eprintln!(
+                    "yaml: key column {} is not present in query results",
+                    key_column
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    fn add_rows(&mut self, rows: &[Row]) {
+        let mut batch = String::new();
+        for row in rows {
+            let (prefix, indent) = match self.key_column_idx {
+                None => ("- ".to_string(), "  "),
+                Some(key_idx) => {
+                    let key = self.value_to_yaml(&row[key_idx]);
+                    if !self.seen_keys.insert(key.clone()) {
+                        eprintln!("yaml: duplicate value {} in key column", key);
+                        std::process::exit(1);
+                    }
+                    batch.push_str(&key);
+                    batch.push_str(":\n");
+                    ("  ".to_string(), "  ")
+                }
+            };
+            let mut first = true;
+            for (idx, value) in row.iter().enumerate() {
+                if Some(idx) == self.key_column_idx {
+                    continue;
+                }
+                batch.push_str(if first { &prefix } else { indent });
+                batch.push_str(&yaml_string(&self.column_names[idx]));
+                batch.push_str(": ");
+                batch.push_str(&self.value_to_yaml(value));
+                batch.push('\n');
+                first = false;
+            }
+            if first {
+                //no columns other than key
+                batch.push_str(&prefix);
+                batch.push_str("{}\n");
+            }
+            self.rows_written += 1;
+        }
+        self.writer.write_all(batch.as_bytes()).unwrap();
+    }
+
+    fn close(&mut self) {
+        if self.rows_written == 0 {
+            self.writer
+                .write_all(if self.key_column.is_some() {
+                    b"{}\n"
+                } else {
+                    b"[]\n"
+                })
+                .unwrap();
+        }
+        self.writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::ColumnType;
+    use crate::utils::testing::{export, temp_path};
+    use clap::Parser;
+
+    fn yaml(args: &[&str], rows: Vec<Row>) -> String {
+        let filename = temp_path("export.yaml");
+        let options =
+            YAMLDestinationOptions::try_parse_from(["yaml", filename.as_str()].iter().chain(args))
+                .unwrap();
+        export(
+            &mut YAMLDestination::init(&options, &ValueFormatter::default()),
+            &[("id", ColumnType::I64), ("answer", ColumnType::String)],
+            vec![rows],
+        );
+        let output = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        output
+    }
+
+    /// reserved words and special characters come back as the same strings
+    #[cfg(feature = "use_json")]
+    #[test]
+    fn test_yaml_string_round_trip() {
+        let mut texts: Vec<&str> = YAML_RESERVED_WORDS.to_vec();
+        texts.extend(["", " lead", "a: b", "- x", "#c", "1.5", "\"q\"\n\\\t\u{1}"]);
+        for text in texts {
+            let scalar = yaml_string(text);
+            let read_back = if scalar.starts_with('"') {
+                //double quoted yaml escapes used here are the same as json ones
+                json::parse(&scalar).unwrap().as_str().unwrap().to_string()
+            } else {
+                scalar.clone()
+            };
+            assert_eq!(read_back, text, "{}", scalar);
+            assert_ne!(scalar, text, "{} must be quoted", text);
+        }
+        assert_eq!(yaml_string("plain text"), "plain text");
+    }
+
+    #[test]
+    fn test_yaml_rows() {
+        let rows = vec![
+            vec![Value::I64(1), Value::String("y".to_string())],
+            vec![Value::I64(2), Value::None],
+        ];
+        assert_eq!(
+            yaml(&[], rows.clone()),
+            "- id: 1\n  answer: \"y\"\n- id: 2\n  answer: ~\n"
+        );
+        assert_eq!(
+            yaml(&["--key-column", "id"], rows),
+            "1:\n  answer: \"y\"\n2:\n  answer: ~\n"
+        );
+        assert_eq!(yaml(&[], vec![]), "[]\n");
+    }
+}
@@ -8,6 +8,7 @@ pub mod config;
 pub mod definitions;
 pub mod destinations;
 pub mod sources;
+pub mod transform;
 pub mod utils;
 
 use commands::{
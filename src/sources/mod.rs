@@ -16,6 +16,7 @@ pub enum Source {
     Postgres(postgres::PostgresSource),
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum SourceConnection<'source> {
     #[cfg(feature = "use_sqlite")]
     SqliteConnection(sqlite::SqliteSourceConnection<'source>),
@@ -70,7 +71,7 @@ impl<'source, 'conn> DataSourceConnection<'conn> for SourceConnection<'source> {
     fn batch_iterator(
         &'conn mut self,
         batch_size: u64,
-    ) -> Box<(dyn DataSourceBatchIterator<'conn> + 'conn)> {
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
         match self {
             #[cfg(feature = "use_sqlite")]
             SourceConnection::SqliteConnection(sqlite_connection) => {
@@ -86,4 +87,25 @@ impl<'source, 'conn> DataSourceConnection<'conn> for SourceConnection<'source> {
             }
         }
     }
+
+    fn query_batch_iterator(
+        &'conn mut self,
+        query: &str,
+        batch_size: u64,
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
+        match self {
+            #[cfg(feature = "use_sqlite")]
+            SourceConnection::SqliteConnection(sqlite_connection) => {
+                sqlite_connection.query_batch_iterator(query, batch_size)
+            }
+            #[cfg(feature = "use_mysql")]
+            SourceConnection::MysqlConnection(mysql_connection) => {
+                mysql_connection.query_batch_iterator(query, batch_size)
+            }
+            #[cfg(feature = "use_postgres")]
+            SourceConnection::PostgresConnection(postgres_connection) => {
+                postgres_connection.query_batch_iterator(query, batch_size)
+            }
+        }
+    }
 }
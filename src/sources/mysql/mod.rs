@@ -236,7 +236,7 @@ where
 impl<'source: 'conn, 'conn> DataSource<'source, 'conn, MysqlSourceConnection<'source>>
     for MysqlSource
 {
-    fn connect(&'source self) -> MysqlSourceConnection {
+    fn connect(&'source self) -> MysqlSourceConnection<'source> {
         let connection = establish_mysql_connection(&self.options);
 
         MysqlSourceConnection {
@@ -257,7 +257,7 @@ impl<'source: 'conn, 'conn> DataSourceConnection<'conn> for MysqlSourceConnectio
     fn batch_iterator(
         &'conn mut self,
         batch_size: u64,
-    ) -> Box<(dyn DataSourceBatchIterator<'conn> + 'conn)> {
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
         let query = match &self.source.options.query {
             Some(q) => q.to_owned(),
             None => match &self.source.options.query_file {
@@ -273,6 +273,14 @@ impl<'source: 'conn, 'conn> DataSourceConnection<'conn> for MysqlSourceConnectio
             },
         };
 
+        self.query_batch_iterator(&query, batch_size)
+    }
+
+    fn query_batch_iterator(
+        &'conn mut self,
+        query: &str,
+        batch_size: u64,
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
         let count: Option<u64> = if self.source.options.count {
             let count_query = format!("select count(*) from ({}) q", query);
             let count_value: u64 = self
@@ -286,10 +294,10 @@ impl<'source: 'conn, 'conn> DataSourceConnection<'conn> for MysqlSourceConnectio
         } else {
             None
         };
-        let mysql_result = match self.connection.exec_iter(query.clone(), ()) {
+        let mysql_result = match self.connection.exec_iter(query, ()) {
             Ok(v) => v,
             Err(e) => {
-                report_query_error(&query, &format!("{:?}", e));
+                report_query_error(query, &format!("{:?}", e));
                 std::process::exit(1);
             }
         };
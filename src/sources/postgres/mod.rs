@@ -140,27 +140,12 @@ impl PostgresSource {
 
 impl<'source: 'conn, 'conn> PostgresSourceConnection<'source> {
     pub fn _batch_iterator(
-        source: &'source PostgresSource,
+        query: &str,
         connection: &'source mut Client,
         batch_size: u64,
-    ) -> Box<(dyn DataSourceBatchIterator<'conn> + 'conn)> {
-        let query = match &source.options.query {
-            Some(q) => q.to_owned(),
-            None => match &source.options.query_file {
-                Some(path_buf) => {
-                    let mut sql = String::new();
-                    File::open(path_buf)
-                        .unwrap()
-                        .read_to_string(&mut sql)
-                        .unwrap();
-                    sql
-                }
-                None => panic!("You need to pass either q or query-file option"),
-            },
-        };
-
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
         let mut batch_iterator = connection
-            .query_raw::<str, Vec<String>, _>(&query, vec![])
+            .query_raw::<str, Vec<String>, _>(query, vec![])
             .unwrap();
         let first_row = batch_iterator.by_ref().peekable().peek().unwrap().cloned();
         /*let batch_iterator =
@@ -185,7 +170,7 @@ impl<'source: 'conn, 'conn> PostgresSourceConnection<'source> {
 impl<'source: 'conn, 'conn> DataSource<'source, 'conn, PostgresSourceConnection<'source>>
     for PostgresSource
 {
-    fn connect(&'source self) -> PostgresSourceConnection {
+    fn connect(&'source self) -> PostgresSourceConnection<'source> {
         let mut connection = establish_postgres_connection(&self.options);
         if !self.options.init.is_empty() {
             for sql in self.options.init.iter() {
@@ -217,8 +202,31 @@ impl<'source: 'conn, 'conn> DataSourceConnection<'conn> for PostgresSourceConnec
     fn batch_iterator(
         &'conn mut self,
         batch_size: u64,
-    ) -> Box<(dyn DataSourceBatchIterator<'conn> + 'conn)> {
-        PostgresSourceConnection::_batch_iterator(self.source, &mut self.connection, batch_size)
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
+        let query = match &self.source.options.query {
+            Some(q) => q.to_owned(),
+            None => match &self.source.options.query_file {
+                Some(path_buf) => {
+                    let mut sql = String::new();
+                    File::open(path_buf)
+                        .unwrap()
+                        .read_to_string(&mut sql)
+                        .unwrap();
+                    sql
+                }
+                None => panic!("You need to pass either q or query-file option"),
+            },
+        };
+
+        PostgresSourceConnection::_batch_iterator(&query, &mut self.connection, batch_size)
+    }
+
+    fn query_batch_iterator(
+        &'conn mut self,
+        query: &str,
+        batch_size: u64,
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
+        PostgresSourceConnection::_batch_iterator(query, &mut self.connection, batch_size)
     }
 }
 
@@ -71,7 +71,7 @@ impl<'source, 'conn> DataSource<'source, 'conn, SqliteSourceConnection<'source>>
 where
     'source: 'conn,
 {
-    fn connect(&'source self) -> SqliteSourceConnection {
+    fn connect(&'source self) -> SqliteSourceConnection<'source> {
         let connection = establish_sqlite_connection(&self.options);
         if !self.options.init.is_empty() {
             for sql in self.options.init.iter() {
@@ -103,7 +103,7 @@ impl<'source: 'conn, 'conn> DataSourceConnection<'conn> for SqliteSourceConnecti
     fn batch_iterator(
         &'conn mut self,
         batch_size: u64,
-    ) -> Box<(dyn DataSourceBatchIterator<'conn> + 'conn)> {
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
         let query = match &self.source.options.query {
             Some(q) => q.to_owned(),
             None => match &self.source.options.query_file {
@@ -119,15 +119,37 @@ impl<'source: 'conn, 'conn> DataSourceConnection<'conn> for SqliteSourceConnecti
             },
         };
 
+        self.query_batch_iterator(&query, batch_size)
+    }
+
+    fn query_batch_iterator(
+        &'conn mut self,
+        query: &str,
+        batch_size: u64,
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
+        let count: Option<u64> = if self.source.options.count {
+            let count_query = format!("select count(*) from ({}) q", query);
+            let mut statement = match self.connection.prepare(&count_query) {
+                Ok(v) => v,
+                Err(e) => {
+                    report_query_error(&count_query, &format!("{:?}", e));
+                    std::process::exit(1);
+                }
+            };
+            statement.next().unwrap();
+            Some(statement.read::<i64, _>(0).unwrap() as u64)
+        } else {
+            None
+        };
         Box::new(SqliteSourceBatchIterator {
             batch_size,
             _connection: &self.connection,
-            count: None,
+            count,
             done: false,
-            statement: match self.connection.prepare(&query) {
+            statement: match self.connection.prepare(query) {
                 Ok(v) => v,
                 Err(e) => {
-                    report_query_error(&query, &format!("{:?}", e));
+                    report_query_error(query, &format!("{:?}", e));
                     std::process::exit(1);
                 }
             },
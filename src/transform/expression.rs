@@ -0,0 +1,818 @@
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use crate::definitions::{ColumnInfo, ColumnType, Row, Value};
+use crate::transform::schema::{value_as_datetime, value_as_text};
+use crate::utils::formatter::check_date_format;
+
+/// expressions used by --filter and --compute:
+///
+/// * literals: 1, 2.5, 'text' ('' for quote), true, false, null
+/// * columns: name, or "name with spaces"
+/// * operators: + - * / % || = != <> < <= > >= and or not, is null, is not null
+/// * functions: upper, lower, trim, length, substr(text, start[, length]), replace(text, from, to),
+///   concat(...), coalesce(...), round(number[, digits]), abs, date_format(value, '%Y-%m-%d'),
+///   if(condition, then, else)
+#[derive(Clone, Debug)]
+pub enum Expression {
+    Literal(Value),
+    //column name and its index, set by bind
+    Column(String, usize),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    IsNull(Box<Expression>, bool),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    Function(Function, Vec<Expression>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    Upper,
+    Lower,
+    Trim,
+    Length,
+    Substr,
+    Replace,
+    Concat,
+    Coalesce,
+    Round,
+    Abs,
+    DateFormat,
+    If,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        Some(match name.to_lowercase().as_str() {
+            "upper" => Function::Upper,
+            "lower" => Function::Lower,
+            "trim" => Function::Trim,
+            "length" => Function::Length,
+            "substr" => Function::Substr,
+            "replace" => Function::Replace,
+            "concat" => Function::Concat,
+            "coalesce" => Function::Coalesce,
+            "round" => Function::Round,
+            "abs" => Function::Abs,
+            "date_format" => Function::DateFormat,
+            "if" => Function::If,
+            _ => return None,
+        })
+    }
+
+    /// minimum and maximum number of arguments
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::Upper | Function::Lower | Function::Trim | Function::Length => (1, 1),
+            Function::Abs => (1, 1),
+            Function::Round => (1, 2),
+            Function::Substr => (2, 3),
+            Function::Replace | Function::If => (3, 3),
+            Function::DateFormat => (2, 2),
+            Function::Concat | Function::Coalesce => (1, usize::MAX),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Number(String),
+    Text(String),
+    Identifier(String),
+    QuotedIdentifier(String),
+    Symbol(&'static str),
+}
+
+//longer symbols first
+const SYMBOLS: [&str; 16] = [
+    "||", "<=", ">=", "<>", "!=", "(", ")", ",", "+", "-", "*", "/", "%", "=", "<", ">",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '.') {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut identifier = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                identifier.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Identifier(identifier));
+        } else if c == '\'' || c == '"' {
+            //quote is escaped by doubling it
+            chars.next();
+            let mut content = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err(format!("unterminated {} at {}", c, position)),
+                    Some((_, q)) if q == c => {
+                        if chars.peek().map(|&(_, next)| next) == Some(c) {
+                            content.push(c);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    Some((_, other)) => content.push(other),
+                }
+            }
+            tokens.push(if c == '\'' {
+                Token::Text(content)
+            } else {
+                Token::QuotedIdentifier(content)
+            });
+        } else if let Some(symbol) = SYMBOLS
+            .iter()
+            .find(|symbol| text[position..].starts_with(**symbol))
+        {
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push(Token::Symbol(symbol));
+        } else {
+            return Err(format!("unexpected character {} at {}", c, position));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_is_symbol(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next_is_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.next_is_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected {}, got {}", symbol, self.describe_next()))
+        }
+    }
+
+    fn describe_next(&self) -> String {
+        match self.peek() {
+            None => "end of expression".to_string(),
+            Some(Token::Number(v)) | Some(Token::Identifier(v)) => v.to_string(),
+            Some(Token::Text(v)) => format!("'{}'", v),
+            Some(Token::QuotedIdentifier(v)) => format!("\"{}\"", v),
+            Some(Token::Symbol(v)) => v.to_string(),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operators: &[(&str, BinaryOperator)],
+        keywords: bool,
+        operand: fn(&mut Parser) -> Result<Expression, String>,
+    ) -> Result<Expression, String> {
+        let mut left = operand(self)?;
+        'outer: loop {
+            for (name, operator) in operators {
+                let matched = if keywords {
+                    self.next_is_keyword(name)
+                } else {
+                    self.next_is_symbol(name)
+                };
+                if matched {
+                    let right = operand(self)?;
+                    left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        self.binary(&[("or", BinaryOperator::Or)], true, Parser::and)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        self.binary(&[("and", BinaryOperator::And)], true, Parser::not)
+    }
+
+    fn not(&mut self) -> Result<Expression, String> {
+        if self.next_is_keyword("not") {
+            Ok(Expression::Not(Box::new(self.not()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expression, String> {
+        let left = self.concat()?;
+        if self.next_is_keyword("is") {
+            let negated = self.next_is_keyword("not");
+            if !self.next_is_keyword("null") {
+                return Err(format!("expected null, got {}", self.describe_next()));
+            }
+            return Ok(Expression::IsNull(Box::new(left), negated));
+        }
+        for (symbol, operator) in [
+            ("=", BinaryOperator::Eq),
+            ("!=", BinaryOperator::NotEq),
+            ("<>", BinaryOperator::NotEq),
+            ("<=", BinaryOperator::LtEq),
+            (">=", BinaryOperator::GtEq),
+            ("<", BinaryOperator::Lt),
+            (">", BinaryOperator::Gt),
+        ] {
+            if self.next_is_symbol(symbol) {
+                let right = self.concat()?;
+                return Ok(Expression::Binary(
+                    operator,
+                    Box::new(left),
+                    Box::new(right),
+                ));
+            }
+        }
+        Ok(left)
+    }
+
+    fn concat(&mut self) -> Result<Expression, String> {
+        self.binary(&[("||", BinaryOperator::Concat)], false, Parser::additive)
+    }
+
+    fn additive(&mut self) -> Result<Expression, String> {
+        self.binary(
+            &[("+", BinaryOperator::Add), ("-", BinaryOperator::Sub)],
+            false,
+            Parser::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Expression, String> {
+        self.binary(
+            &[
+                ("*", BinaryOperator::Mul),
+                ("/", BinaryOperator::Div),
+                ("%", BinaryOperator::Rem),
+            ],
+            false,
+            Parser::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.next_is_symbol("-") {
+            Ok(Expression::Negate(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        let description = self.describe_next();
+        match self.next() {
+            Some(Token::Number(number)) => {
+                if let Ok(v) = number.parse::<i64>() {
+                    Ok(Expression::Literal(Value::I64(v)))
+                } else if let Ok(v) = number.parse::<f64>() {
+                    Ok(Expression::Literal(Value::F64(v)))
+                } else {
+                    Err(format!("invalid number {}", number))
+                }
+            }
+            Some(Token::Text(text)) => Ok(Expression::Literal(Value::String(text))),
+            Some(Token::QuotedIdentifier(name)) => Ok(Expression::Column(name, 0)),
+            Some(Token::Identifier(name)) => match name.to_lowercase().as_str() {
+                "true" => Ok(Expression::Literal(Value::Bool(true))),
+                "false" => Ok(Expression::Literal(Value::Bool(false))),
+                "null" => Ok(Expression::Literal(Value::None)),
+                _ if self.next_is_symbol("(") => {
+                    let function = Function::from_name(&name)
+                        .ok_or_else(|| format!("unknown function {}", name))?;
+                    let mut arguments = vec![];
+                    if !self.next_is_symbol(")") {
+                        loop {
+                            arguments.push(self.or()?);
+                            if self.next_is_symbol(")") {
+                                break;
+                            }
+                            self.expect_symbol(",")?;
+                        }
+                    }
+                    let (min, max) = function.arity();
+                    if arguments.len() < min || arguments.len() > max {
+                        return Err(format!(
+                            "wrong number of arguments for {}: {}",
+                            name,
+                            arguments.len()
+                        ));
+                    }
+                    if let (
+                        Function::DateFormat,
+                        Some(Expression::Literal(Value::String(format))),
+                    ) = (function, arguments.get(1))
+                    {
+                        check_date_format(format)?;
+                    }
+                    Ok(Expression::Function(function, arguments))
+                }
+                _ => Ok(Expression::Column(name, 0)),
+            },
+            Some(Token::Symbol("(")) => {
+                let expression = self.or()?;
+                self.expect_symbol(")")?;
+                Ok(expression)
+            }
+            _ => Err(format!("unexpected {}", description)),
+        }
+    }
+}
+
+pub fn parse_expression(text: &str) -> Result<Expression, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+    };
+    let expression = parser.or()?;
+    if parser.peek().is_some() {
+        return Err(format!("unexpected {}", parser.describe_next()));
+    }
+    Ok(expression)
+}
+
+fn is_integer_type(data_type: &ColumnType) -> bool {
+    matches!(
+        data_type,
+        ColumnType::U64
+            | ColumnType::I64
+            | ColumnType::U32
+            | ColumnType::I32
+            | ColumnType::U16
+            | ColumnType::I16
+            | ColumnType::U8
+            | ColumnType::I8
+    )
+}
+
+fn numeric_result_type(types: &[ColumnType]) -> ColumnType {
+    if types.iter().all(is_integer_type) {
+        ColumnType::I64
+    } else {
+        ColumnType::F64
+    }
+}
+
+impl Expression {
+    /// resolve column names to indices, returns type of expression result
+    pub fn bind(&mut self, columns: &[ColumnInfo]) -> Result<ColumnType, String> {
+        Ok(match self {
+            Expression::Literal(value) => match value {
+                Value::I64(_) => ColumnType::I64,
+                Value::F64(_) => ColumnType::F64,
+                Value::Bool(_) => ColumnType::Bool,
+                Value::String(_) => ColumnType::String,
+                _ => ColumnType::None,
+            },
+            Expression::Column(name, idx) => {
+                *idx = columns
+                    .iter()
+                    .position(|column| &column.name == name)
+                    .ok_or_else(|| format!("unknown column {}", name))?;
+                columns[*idx].data_type.clone()
+            }
+            Expression::Negate(expression) => numeric_result_type(&[expression.bind(columns)?]),
+            Expression::Not(expression) | Expression::IsNull(expression, _) => {
+                expression.bind(columns)?;
+                ColumnType::Bool
+            }
+            Expression::Binary(operator, left, right) => {
+                let types = [left.bind(columns)?, right.bind(columns)?];
+                match operator {
+                    BinaryOperator::Concat => ColumnType::String,
+                    BinaryOperator::Add
+                    | BinaryOperator::Sub
+                    | BinaryOperator::Mul
+                    | BinaryOperator::Rem => numeric_result_type(&types),
+                    BinaryOperator::Div => ColumnType::F64,
+                    _ => ColumnType::Bool,
+                }
+            }
+            Expression::Function(function, arguments) => {
+                let types = arguments
+                    .iter_mut()
+                    .map(|argument| argument.bind(columns))
+                    .collect::<Result<Vec<ColumnType>, String>>()?;
+                match function {
+                    Function::Length => ColumnType::I64,
+                    Function::Round => ColumnType::F64,
+                    Function::Abs => numeric_result_type(&types),
+                    //first argument that is not null literal decides
+                    Function::Coalesce | Function::If => types
+                        .iter()
+                        .skip(if *function == Function::If { 1 } else { 0 })
+                        .find(|data_type| !matches!(data_type, ColumnType::None))
+                        .cloned()
+                        .unwrap_or(ColumnType::None),
+                    _ => ColumnType::String,
+                }
+            }
+        })
+    }
+
+    pub fn evaluate(&self, row: &Row) -> Result<Value, String> {
+        match self {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Column(_, idx) => Ok(row[*idx].clone()),
+            Expression::Negate(expression) => match number(&expression.evaluate(row)?)? {
+                None => Ok(Value::None),
+                Some(Number::Int(v)) => v
+                    .checked_neg()
+                    .map(Value::I64)
+                    .ok_or_else(|| "integer overflow".to_string()),
+                Some(Number::Float(v)) => Ok(Value::F64(-v)),
+            },
+            Expression::Not(expression) => Ok(match truth(&expression.evaluate(row)?)? {
+                None => Value::None,
+                Some(v) => Value::Bool(!v),
+            }),
+            Expression::IsNull(expression, negated) => {
+                let is_null = matches!(expression.evaluate(row)?, Value::None);
+                Ok(Value::Bool(is_null != *negated))
+            }
+            Expression::Binary(BinaryOperator::And, left, right) => {
+                //false wins over null
+                match (truth(&left.evaluate(row)?)?, truth(&right.evaluate(row)?)?) {
+                    (Some(false), _) | (_, Some(false)) => Ok(Value::Bool(false)),
+                    (Some(true), Some(true)) => Ok(Value::Bool(true)),
+                    _ => Ok(Value::None),
+                }
+            }
+            Expression::Binary(BinaryOperator::Or, left, right) => {
+                match (truth(&left.evaluate(row)?)?, truth(&right.evaluate(row)?)?) {
+                    (Some(true), _) | (_, Some(true)) => Ok(Value::Bool(true)),
+                    (Some(false), Some(false)) => Ok(Value::Bool(false)),
+                    _ => Ok(Value::None),
+                }
+            }
+            Expression::Binary(operator, left, right) => {
+                binary(*operator, left.evaluate(row)?, right.evaluate(row)?)
+            }
+            Expression::Function(function, arguments) => call(*function, arguments, row),
+        }
+    }
+}
+
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+fn number(value: &Value) -> Result<Option<Number>, String> {
+    Ok(Some(match value {
+        Value::None => return Ok(None),
+        Value::U64(v) => match i64::try_from(*v) {
+            Ok(v) => Number::Int(v),
+            Err(_) => Number::Float(*v as f64),
+        },
+        Value::I64(v) => Number::Int(*v),
+        Value::U32(v) => Number::Int(i64::from(*v)),
+        Value::I32(v) => Number::Int(i64::from(*v)),
+        Value::U16(v) => Number::Int(i64::from(*v)),
+        Value::I16(v) => Number::Int(i64::from(*v)),
+        Value::U8(v) => Number::Int(i64::from(*v)),
+        Value::I8(v) => Number::Int(i64::from(*v)),
+        Value::F64(v) => Number::Float(*v),
+        Value::F32(v) => Number::Float(f64::from(*v)),
+        Value::Bool(v) => Number::Int(i64::from(*v)),
+        _ => {
+            let text = value_as_text(value).unwrap_or_default();
+            let text = text.trim();
+            if let Ok(v) = text.parse() {
+                Number::Int(v)
+            } else if let Ok(v) = text.parse() {
+                Number::Float(v)
+            } else {
+                return Err(format!("{:?} is not a number", value));
+            }
+        }
+    }))
+}
+
+fn truth(value: &Value) -> Result<Option<bool>, String> {
+    match value {
+        Value::None => Ok(None),
+        Value::Bool(v) => Ok(Some(*v)),
+        _ => match number(value) {
+            Ok(Some(Number::Int(v))) => Ok(Some(v != 0)),
+            Ok(Some(Number::Float(v))) => Ok(Some(v != 0.0)),
+            _ => Err(format!("{:?} is not a boolean", value)),
+        },
+    }
+}
+
+fn is_number(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::U64(_)
+            | Value::I64(_)
+            | Value::U32(_)
+            | Value::I32(_)
+            | Value::U16(_)
+            | Value::I16(_)
+            | Value::U8(_)
+            | Value::I8(_)
+            | Value::F64(_)
+            | Value::F32(_)
+    )
+}
+
+/// numbers are compared by value, dates and times directly, everything else as text
+fn compare(left: &Value, right: &Value) -> Result<Option<Ordering>, String> {
+    Ok(match (left, right) {
+        (Value::Date(l), Value::Date(r)) => l.partial_cmp(r),
+        (Value::Time(l), Value::Time(r)) => l.partial_cmp(r),
+        (Value::DateTime(l), Value::DateTime(r)) => l.partial_cmp(r),
+        (Value::Bool(l), Value::Bool(r)) => l.partial_cmp(r),
+        _ if is_number(left) || is_number(right) => match (number(left)?, number(right)?) {
+            (Some(Number::Int(l)), Some(Number::Int(r))) => l.partial_cmp(&r),
+            (Some(l), Some(r)) => as_f64(l).partial_cmp(&as_f64(r)),
+            _ => None,
+        },
+        _ => match (value_as_text(left), value_as_text(right)) {
+            (Some(l), Some(r)) => l.partial_cmp(&r),
+            _ => None,
+        },
+    })
+}
+
+fn as_f64(number: Number) -> f64 {
+    match number {
+        Number::Int(v) => v as f64,
+        Number::Float(v) => v,
+    }
+}
+
+fn binary(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+    if matches!(left, Value::None) || matches!(right, Value::None) {
+        return Ok(Value::None);
+    }
+    let ordering = |expected: &[Ordering]| -> Result<Value, String> {
+        Ok(match compare(&left, &right)? {
+            Some(ordering) => Value::Bool(expected.contains(&ordering)),
+            None => Value::None,
+        })
+    };
+    match operator {
+        BinaryOperator::Eq => ordering(&[Ordering::Equal]),
+        BinaryOperator::NotEq => ordering(&[Ordering::Less, Ordering::Greater]),
+        BinaryOperator::Lt => ordering(&[Ordering::Less]),
+        BinaryOperator::LtEq => ordering(&[Ordering::Less, Ordering::Equal]),
+        BinaryOperator::Gt => ordering(&[Ordering::Greater]),
+        BinaryOperator::GtEq => ordering(&[Ordering::Greater, Ordering::Equal]),
+        BinaryOperator::Concat => Ok(Value::String(
+            text(&left).unwrap_or_default() + &text(&right).unwrap_or_default(),
+        )),
+        _ => {
+            let (l, r) = (number(&left)?.unwrap(), number(&right)?.unwrap());
+            match (operator, l, r) {
+                (BinaryOperator::Div, l, r) => {
+                    let r = as_f64(r);
+                    //division by zero gives null, like in sql
+                    Ok(if r == 0.0 {
+                        Value::None
+                    } else {
+                        Value::F64(as_f64(l) / r)
+                    })
+                }
+                (BinaryOperator::Rem, _, Number::Int(0)) => Ok(Value::None),
+                (_, Number::Int(l), Number::Int(r)) => match operator {
+                    BinaryOperator::Add => l.checked_add(r),
+                    BinaryOperator::Sub => l.checked_sub(r),
+                    BinaryOperator::Mul => l.checked_mul(r),
+                    _ => l.checked_rem(r),
+                }
+                .map(Value::I64)
+                .ok_or_else(|| "integer overflow".to_string()),
+                (_, l, r) => {
+                    let (l, r) = (as_f64(l), as_f64(r));
+                    Ok(Value::F64(match operator {
+                        BinaryOperator::Add => l + r,
+                        BinaryOperator::Sub => l - r,
+                        BinaryOperator::Mul => l * r,
+                        _ => l % r,
+                    }))
+                }
+            }
+        }
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Bytes(v) => Some(String::from_utf8_lossy(v).to_string()),
+        _ => value_as_text(value),
+    }
+}
+
+fn call(function: Function, arguments: &[Expression], row: &Row) -> Result<Value, String> {
+    //functions that don't evaluate all arguments or accept nulls
+    match function {
+        Function::Coalesce => {
+            for argument in arguments {
+                let value = argument.evaluate(row)?;
+                if !matches!(value, Value::None) {
+                    return Ok(value);
+                }
+            }
+            return Ok(Value::None);
+        }
+        Function::If => {
+            let condition = truth(&arguments[0].evaluate(row)?)?;
+            return arguments[if condition == Some(true) { 1 } else { 2 }].evaluate(row);
+        }
+        Function::Concat => {
+            let mut result = String::new();
+            for argument in arguments {
+                result.push_str(&text(&argument.evaluate(row)?).unwrap_or_default());
+            }
+            return Ok(Value::String(result));
+        }
+        _ => {}
+    }
+    let values = arguments
+        .iter()
+        .map(|argument| argument.evaluate(row))
+        .collect::<Result<Vec<Value>, String>>()?;
+    if values.iter().any(|value| matches!(value, Value::None)) {
+        return Ok(Value::None);
+    }
+    let text_argument = |idx: usize| text(&values[idx]).unwrap_or_default();
+    let int_argument = |idx: usize| -> Result<i64, String> {
+        match number(&values[idx])? {
+            Some(Number::Int(v)) => Ok(v),
+            _ => Err(format!("{:?} is not an integer", values[idx])),
+        }
+    };
+    Ok(match function {
+        Function::Upper => Value::String(text_argument(0).to_uppercase()),
+        Function::Lower => Value::String(text_argument(0).to_lowercase()),
+        Function::Trim => Value::String(text_argument(0).trim().to_string()),
+        Function::Length => Value::I64(text_argument(0).chars().count() as i64),
+        Function::Substr => {
+            //1-based, like in sql
+            let start = usize::try_from(int_argument(1)?.max(1) - 1).unwrap_or_default();
+            let length = match values.get(2) {
+                Some(_) => usize::try_from(int_argument(2)?.max(0)).unwrap_or_default(),
+                None => usize::MAX,
+            };
+            Value::String(text_argument(0).chars().skip(start).take(length).collect())
+        }
+        Function::Replace => {
+            Value::String(text_argument(0).replace(&text_argument(1), &text_argument(2)))
+        }
+        Function::Round => {
+            let digits = match values.get(1) {
+                Some(_) => int_argument(1)?,
+                None => 0,
+            };
+            let factor = 10f64.powi(i32::try_from(digits).unwrap_or_default());
+            match number(&values[0])?.unwrap() {
+                Number::Int(v) => Value::F64(v as f64),
+                Number::Float(v) => Value::F64((v * factor).round() / factor),
+            }
+        }
+        Function::Abs => match number(&values[0])?.unwrap() {
+            Number::Int(v) => Value::I64(
+                v.checked_abs()
+                    .ok_or_else(|| "integer overflow".to_string())?,
+            ),
+            Number::Float(v) => Value::F64(v.abs()),
+        },
+        Function::DateFormat => {
+            let format = text_argument(1);
+            check_date_format(&format)?;
+            let mut result = String::new();
+            let written = match &values[0] {
+                Value::Time(v) => write!(result, "{}", v.format(&format)),
+                value => match value_as_datetime(value) {
+                    Some(v) => write!(result, "{}", v.format(&format)),
+                    None => return Err(format!("{:?} is not a date", value)),
+                },
+            };
+            //ie. time format used for a date
+            if written.is_err() {
+                return Err(format!("can't format {:?} with '{}'", values[0], format));
+            }
+            Value::String(result)
+        }
+        Function::Coalesce | Function::If | Function::Concat => unreachable!(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::parse_expression;
+    use crate::definitions::{ColumnInfo, ColumnType, Value};
+
+    fn evaluate(text: &str) -> String {
+        let columns = vec![
+            ColumnInfo {
+                name: "age".to_string(),
+                data_type: ColumnType::I64,
+            },
+            ColumnInfo {
+                name: "first name".to_string(),
+                data_type: ColumnType::String,
+            },
+        ];
+        let row = vec![Value::I64(20), Value::String("Ann".to_string())];
+        let mut expression = parse_expression(text).unwrap();
+        expression.bind(&columns).unwrap();
+        format!("{:?}", expression.evaluate(&row).unwrap())
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(evaluate("age + 2 * 3"), "I64(26)");
+        assert_eq!(evaluate("(age + 2) * 3"), "I64(66)");
+        assert_eq!(evaluate("age / 8"), "F64(2.5)");
+        assert_eq!(
+            evaluate("age >= 18 and not \"first name\" = 'Bob'"),
+            "Bool(true)"
+        );
+        assert_eq!(
+            evaluate("upper(\"first name\") || '''s'"),
+            "String(\"ANN's\")"
+        );
+        assert_eq!(
+            evaluate("coalesce(null, substr('abcdef', 2, 3))"),
+            "String(\"bcd\")"
+        );
+        assert_eq!(evaluate("null = 1 or age is null"), "None");
+        assert_eq!(
+            evaluate("date_format('2024-03-01 10:00:00', '%d.%m.%Y')"),
+            "String(\"01.03.2024\")"
+        );
+        assert!(parse_expression("age +").is_err());
+        assert!(parse_expression("nope(1)").is_err());
+        assert!(parse_expression("date_format(age, '%Q')").is_err());
+    }
+}
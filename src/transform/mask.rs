@@ -0,0 +1,571 @@
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde_derive::Deserialize;
+use sha2::Sha256;
+
+use crate::definitions::{ColumnInfo, ColumnType, DataSourceBatchIterator, Row, Value};
+use crate::transform::schema::value_as_text;
+
+/// how masked values are replaced
+#[derive(Clone, Debug, PartialEq)]
+pub enum MaskMethod {
+    /// salted hash, the same value always gives the same hash
+    Hash,
+    /// random letters and digits in place of original ones, punctuation is kept
+    Fake,
+    Null,
+    /// first character (and e-mail domain) kept, with given number of trailing characters
+    Redact(usize),
+    /// dates moved by up to given number of days, the same date always moves the same way
+    ShiftDate(u32),
+}
+
+impl MaskMethod {
+    fn needs_salt(&self) -> bool {
+        !matches!(self, MaskMethod::Null | MaskMethod::Redact(_))
+    }
+
+    //keeps different methods from producing related values
+    fn tag(&self) -> &'static str {
+        match self {
+            MaskMethod::Hash => "hash",
+            MaskMethod::Fake => "fake",
+            MaskMethod::Null => "null",
+            MaskMethod::Redact(_) => "redact",
+            MaskMethod::ShiftDate(_) => "shift-date",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ColumnMatcher {
+    Name(String),
+    Pattern(Regex),
+}
+
+impl ColumnMatcher {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            ColumnMatcher::Name(column) => column == name,
+            ColumnMatcher::Pattern(pattern) => pattern.is_match(name),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MaskRule {
+    pub matcher: ColumnMatcher,
+    pub method: MaskMethod,
+}
+
+/// rules with salt, ready to apply
+#[derive(Clone, Debug, Default)]
+pub struct Masking {
+    rules: Vec<MaskRule>,
+    salt: Option<String>,
+}
+
+/// mask file (toml), when several rules match a column the last one wins:
+///
+/// ```toml
+/// salt = "keep me secret"
+///
+/// [[rule]]
+/// column = "email"
+/// method = "redact"
+///
+/// [[rule]]
+/// pattern = "(?i)phone|mobile"
+/// method = "fake"
+///
+/// [[rule]]
+/// column = "birth_date"
+/// method = "shift-date:30"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+struct MaskFile {
+    salt: Option<String>,
+    #[serde(default)]
+    rule: Vec<MaskFileRule>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct MaskFileRule {
+    column: Option<String>,
+    pattern: Option<String>,
+    method: String,
+}
+
+/// parse hash, fake, null, redact, redact:N or shift-date:N
+pub fn parse_mask_method(value: &str) -> Result<MaskMethod, String> {
+    let (name, argument) = match value.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (value, None),
+    };
+    match (name.to_lowercase().as_str(), argument) {
+        ("hash", None) => Ok(MaskMethod::Hash),
+        ("fake", None) => Ok(MaskMethod::Fake),
+        ("null", None) => Ok(MaskMethod::Null),
+        ("redact", None) => Ok(MaskMethod::Redact(0)),
+        ("redact", Some(n)) => n
+            .parse()
+            .map(MaskMethod::Redact)
+            .map_err(|_| format!("expected number of kept characters, got {}", n)),
+        ("shift-date", Some(n)) => match n.parse() {
+            Ok(days) if days > 0 => Ok(MaskMethod::ShiftDate(days)),
+            _ => Err(format!("expected positive number of days, got {}", n)),
+        },
+        _ => Err(format!(
+            "unknown masking method {}, expected one of: hash, fake, null, redact, redact:N, shift-date:N",
+            value
+        )),
+    }
+}
+
+fn parse_matcher(column: &str) -> Result<ColumnMatcher, String> {
+    match column.strip_prefix('/').and_then(|c| c.strip_suffix('/')) {
+        Some(pattern) => Regex::new(pattern)
+            .map(ColumnMatcher::Pattern)
+            .map_err(|e| format!("invalid column pattern {}: {}", pattern, e)),
+        None => Ok(ColumnMatcher::Name(column.to_string())),
+    }
+}
+
+/// parse column=method, where column is a name or /regex/
+pub fn parse_mask_rule(value: &str) -> Result<MaskRule, String> {
+    match value.rsplit_once('=') {
+        Some((column, method)) if !column.is_empty() => Ok(MaskRule {
+            matcher: parse_matcher(column)?,
+            method: parse_mask_method(method)?,
+        }),
+        _ => Err(format!("expected column=method, got {}", value)),
+    }
+}
+
+/// rules from mask file followed by ones given in command line, salt from command line wins
+pub fn load_masking(rules: &[MaskRule], mask_file: Option<&str>, salt: Option<&str>) -> Masking {
+    let mut masking = Masking::default();
+    if let Some(filename) = mask_file {
+        let content = std::fs::read_to_string(filename).unwrap_or_else(|e| {
+            eprintln!("export: could not read mask file {}: {}", filename, e);
+            std::process::exit(1);
+        });
+        let mask_file = toml::from_str::<MaskFile>(&content).unwrap_or_else(|e| {
+            eprintln!("export: invalid mask file {}: {}", filename, e);
+            std::process::exit(1);
+        });
+        for rule in mask_file.rule {
+            let matcher = match (rule.column, rule.pattern) {
+                (Some(column), None) => Ok(ColumnMatcher::Name(column)),
+                (None, Some(pattern)) => Regex::new(&pattern)
+                    .map(ColumnMatcher::Pattern)
+                    .map_err(|e| format!("invalid pattern {}: {}", pattern, e)),
+                _ => Err("each rule needs either column or pattern".to_string()),
+            };
+            let rule = matcher.and_then(|matcher| {
+                Ok(MaskRule {
+                    matcher,
+                    method: parse_mask_method(&rule.method)?,
+                })
+            });
+            masking.rules.push(rule.unwrap_or_else(|e| {
+                eprintln!("export: invalid mask file {}: {}", filename, e);
+                std::process::exit(1);
+            }));
+        }
+        masking.salt = mask_file.salt;
+    }
+    masking.rules.extend(rules.iter().cloned());
+    if let Some(salt) = salt {
+        masking.salt = Some(salt.to_string());
+    }
+    if masking.salt.as_deref().unwrap_or("").is_empty()
+        && masking.rules.iter().any(|rule| rule.method.needs_salt())
+    {
+        eprintln!("export: masking with hash, fake or shift-date needs a salt (--mask-salt, or salt in mask file)");
+        std::process::exit(1);
+    }
+    masking
+}
+
+/// pseudo-random bytes derived from salt and value
+struct Keystream {
+    mac: Hmac<Sha256>,
+    block: Vec<u8>,
+    position: usize,
+    counter: u32,
+}
+
+impl Keystream {
+    fn new(salt: &str, method: &MaskMethod, value: &[u8]) -> Keystream {
+        let mut mac = Hmac::<Sha256>::new_from_slice(salt.as_bytes()).expect("any key length");
+        mac.update(method.tag().as_bytes());
+        mac.update(&[0]);
+        mac.update(value);
+        Keystream {
+            mac,
+            block: vec![],
+            position: 0,
+            counter: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        if self.position == self.block.len() {
+            let mut mac = self.mac.clone();
+            mac.update(&self.counter.to_be_bytes());
+            self.block = mac.finalize().into_bytes().to_vec();
+            self.position = 0;
+            self.counter += 1;
+        }
+        self.position += 1;
+        self.block[self.position - 1]
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (0..8).fold(0, |acc, _| (acc << 8) | self.next_byte() as u64)
+    }
+
+    /// number in 0..n, n must be positive
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+fn value_bytes(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Bytes(v) => v.clone(),
+        _ => value_as_text(value).unwrap_or_default().into_bytes(),
+    }
+}
+
+//range of integer variants, None for other values
+fn integer_range(value: &Value) -> Option<(i128, i128, i128)> {
+    Some(match value {
+        Value::U64(v) => (*v as i128, 0, u64::MAX as i128),
+        Value::I64(v) => (*v as i128, i64::MIN as i128, i64::MAX as i128),
+        Value::U32(v) => (*v as i128, 0, u32::MAX as i128),
+        Value::I32(v) => (*v as i128, i32::MIN as i128, i32::MAX as i128),
+        Value::U16(v) => (*v as i128, 0, u16::MAX as i128),
+        Value::I16(v) => (*v as i128, i16::MIN as i128, i16::MAX as i128),
+        Value::U8(v) => (*v as i128, 0, u8::MAX as i128),
+        Value::I8(v) => (*v as i128, i8::MIN as i128, i8::MAX as i128),
+        _ => return None,
+    })
+}
+
+//integer of the same variant as given one, v must be within its range
+fn integer_like(value: &Value, v: i128) -> Value {
+    match value {
+        Value::U64(_) => Value::U64(v as u64),
+        Value::I64(_) => Value::I64(v as i64),
+        Value::U32(_) => Value::U32(v as u32),
+        Value::I32(_) => Value::I32(v as i32),
+        Value::U16(_) => Value::U16(v as u16),
+        Value::I16(_) => Value::I16(v as i16),
+        Value::U8(_) => Value::U8(v as u8),
+        _ => Value::I8(v as i8),
+    }
+}
+
+fn fake_text(text: &str, keystream: &mut Keystream) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_digit() {
+                (b'0' + keystream.below(10) as u8) as char
+            } else if c.is_uppercase() {
+                (b'A' + keystream.below(26) as u8) as char
+            } else if c.is_alphabetic() {
+                (b'a' + keystream.below(26) as u8) as char
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+//same sign and number of digits, as long as the type allows
+fn fake_integer(v: i128, min: i128, max: i128, keystream: &mut Keystream) -> i128 {
+    let digits = v.unsigned_abs().to_string().len() as u32;
+    let low = if digits == 1 {
+        0
+    } else {
+        10i128.pow(digits - 1)
+    };
+    let high = 10i128.checked_pow(digits).unwrap_or(i128::MAX) - 1;
+    let (low, high) = if v < 0 {
+        (low, high.min(-min))
+    } else {
+        (low, high.min(max))
+    };
+    let faked = low + (keystream.next_u64() as i128).rem_euclid(high - low + 1);
+    if v < 0 {
+        -faked
+    } else {
+        faked
+    }
+}
+
+fn redact_text(text: &str, kept: usize) -> String {
+    //for e-mails only the part before @ is redacted
+    let (text, domain) = match text.rsplit_once('@') {
+        Some((local, domain)) => (local, format!("@{}", domain)),
+        None => (text, String::new()),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= kept + 1 {
+        return format!("***{}", domain);
+    }
+    let tail: String = chars[chars.len() - kept..].iter().collect();
+    format!("{}***{}{}", chars[0], tail, domain)
+}
+
+fn mask_value(value: Value, method: &MaskMethod, salt: &str) -> Result<Value, String> {
+    if matches!(value, Value::None) {
+        return Ok(Value::None);
+    }
+    let mut keystream = Keystream::new(salt, method, &value_bytes(&value));
+    Ok(match method {
+        MaskMethod::Null => Value::None,
+        MaskMethod::Hash => match integer_range(&value) {
+            //integer keys stay integers, so they can still be joined
+            Some(_) => Value::I64((keystream.next_u64() >> 1) as i64),
+            None => {
+                let mut hash = String::with_capacity(64);
+                for _ in 0..32 {
+                    hash.push_str(&format!("{:02x}", keystream.next_byte()));
+                }
+                Value::String(hash)
+            }
+        },
+        MaskMethod::Redact(kept) => match value_as_text(&value) {
+            Some(text) => Value::String(redact_text(&text, *kept)),
+            None => Value::String("***".to_string()),
+        },
+        MaskMethod::Fake => match value {
+            Value::String(v) => Value::String(fake_text(&v, &mut keystream)),
+            Value::Custom(v) => Value::Custom(fake_text(&v, &mut keystream)),
+            Value::F64(v) if v.is_finite() => Value::F64(
+                fake_text(&v.to_string(), &mut keystream)
+                    .parse()
+                    .unwrap_or(v),
+            ),
+            Value::F32(v) if v.is_finite() => Value::F32(
+                fake_text(&v.to_string(), &mut keystream)
+                    .parse()
+                    .unwrap_or(v),
+            ),
+            Value::F64(_) | Value::F32(_) => value,
+            Value::Bool(_) => Value::Bool(keystream.below(2) == 1),
+            Value::Bytes(v) => Value::Bytes(v.iter().map(|_| keystream.next_byte()).collect()),
+            value => match integer_range(&value) {
+                Some((v, min, max)) => {
+                    integer_like(&value, fake_integer(v, min, max, &mut keystream))
+                }
+                None => return Err(format!("can't fake {:?}, use shift-date for dates", value)),
+            },
+        },
+        MaskMethod::ShiftDate(days) => {
+            let shift = keystream.below(2 * *days as u64 + 1) as i64 - *days as i64;
+            let shift = chrono::Duration::days(shift);
+            match value {
+                Value::Date(v) => Value::Date(v + shift),
+                Value::DateTime(v) => Value::DateTime(v + shift),
+                Value::Timestamp(v) => {
+                    Value::Timestamp(v.saturating_add_signed(shift.num_seconds()))
+                }
+                value => return Err(format!("can't shift {:?}, it is not a date", value)),
+            }
+        }
+    })
+}
+
+/// column type after masking
+fn masked_type(data_type: &ColumnType, method: &MaskMethod) -> ColumnType {
+    match method {
+        MaskMethod::Hash
+            if matches!(
+                data_type,
+                ColumnType::U64
+                    | ColumnType::I64
+                    | ColumnType::U32
+                    | ColumnType::I32
+                    | ColumnType::U16
+                    | ColumnType::I16
+                    | ColumnType::U8
+                    | ColumnType::I8
+            ) =>
+        {
+            ColumnType::I64
+        }
+        MaskMethod::Hash | MaskMethod::Redact(_) => ColumnType::String,
+        _ => data_type.clone(),
+    }
+}
+
+/// wraps source iterator, masking values of columns matched by rules
+pub struct MaskIterator<'conn> {
+    inner: Box<dyn DataSourceBatchIterator<'conn> + 'conn>,
+    columns: Vec<ColumnInfo>,
+    //column index and method of masked columns
+    masked: Vec<(usize, MaskMethod)>,
+    salt: String,
+    row_number: u64,
+}
+
+impl<'conn> MaskIterator<'conn> {
+    /// apply masking to given iterator, returning it unchanged if no column is masked
+    pub fn wrap(
+        inner: Box<dyn DataSourceBatchIterator<'conn> + 'conn>,
+        masking: &Masking,
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
+        if masking.rules.is_empty() {
+            return inner;
+        }
+        let mut columns = inner.get_column_info();
+        //a typo in column name must not leave data unmasked
+        for rule in masking.rules.iter() {
+            if let ColumnMatcher::Name(name) = &rule.matcher {
+                if !columns.iter().any(|column| &column.name == name) {
+                    eprintln!(
+                        "export: can't mask column {}, there is no such column",
+                        name
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        let mut masked = vec![];
+        for (idx, column) in columns.iter_mut().enumerate() {
+            let rule = masking
+                .rules
+                .iter()
+                .rev()
+                .find(|rule| rule.matcher.matches(&column.name));
+            if let Some(rule) = rule {
+                let unsupported = match rule.method {
+                    MaskMethod::ShiftDate(_) => !matches!(
+                        column.data_type,
+                        ColumnType::Date | ColumnType::DateTime | ColumnType::Timestamp
+                    ),
+                    MaskMethod::Fake => matches!(
+                        column.data_type,
+                        ColumnType::Date
+                            | ColumnType::DateTime
+                            | ColumnType::Time
+                            | ColumnType::Timestamp
+                            | ColumnType::JSON
+                    ),
+                    _ => false,
+                };
+                if unsupported {
+                    eprintln!(
+                        "export: can't mask column {} of type {:?} with {}{}",
+                        column.name,
+                        column.data_type,
+                        rule.method.tag(),
+                        match rule.method {
+                            MaskMethod::ShiftDate(_) =>
+                                format!(", convert it first with --column {}:date", column.name),
+                            _ => String::new(),
+                        }
+                    );
+                    std::process::exit(1);
+                }
+                column.data_type = masked_type(&column.data_type, &rule.method);
+                masked.push((idx, rule.method.clone()));
+            }
+        }
+        if masked.is_empty() {
+            return inner;
+        }
+        Box::new(MaskIterator {
+            inner,
+            columns,
+            masked,
+            salt: masking.salt.clone().unwrap_or_default(),
+            row_number: 0,
+        })
+    }
+}
+
+impl<'conn> DataSourceBatchIterator<'conn> for MaskIterator<'conn> {
+    fn get_column_info(&self) -> Vec<ColumnInfo> {
+        self.columns.clone()
+    }
+
+    fn get_count(&self) -> Option<u64> {
+        self.inner.get_count()
+    }
+
+    fn next(&mut self) -> Option<Vec<Row>> {
+        let mut rows = self.inner.next()?;
+        for row in rows.iter_mut() {
+            self.row_number += 1;
+            for (idx, method) in self.masked.iter() {
+                let value = std::mem::replace(&mut row[*idx], Value::None);
+                row[*idx] = mask_value(value, method, &self.salt).unwrap_or_else(|e| {
+                    eprintln!(
+                        "export: can't mask column {} in row {}: {}",
+                        self.columns[*idx].name, self.row_number, e
+                    );
+                    std::process::exit(1);
+                });
+            }
+        }
+        Some(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masking() {
+        let salt = "salt";
+        let email = Value::String("john.doe@example.com".to_string());
+        assert_eq!(
+            value_as_text(&mask_value(email.clone(), &MaskMethod::Redact(0), salt).unwrap()),
+            Some("j***@example.com".to_string())
+        );
+        assert_eq!(redact_text("+48 123 456 789", 3), "+***789");
+        assert_eq!(redact_text("ab", 1), "***");
+
+        let hash = |value: &Value, salt| {
+            value_as_text(&mask_value(value.clone(), &MaskMethod::Hash, salt).unwrap())
+        };
+        assert_eq!(hash(&email, salt), hash(&email, salt));
+        assert_ne!(hash(&email, salt), hash(&email, "other salt"));
+        assert!(matches!(
+            mask_value(Value::I32(7), &MaskMethod::Hash, salt),
+            Ok(Value::I64(v)) if v >= 0
+        ));
+
+        let fake = value_as_text(&mask_value(email, &MaskMethod::Fake, salt).unwrap()).unwrap();
+        assert_eq!(fake.len(), "john.doe@example.com".len());
+        assert_eq!(fake.find('@'), Some(8));
+        assert!(matches!(
+            mask_value(Value::I8(-100), &MaskMethod::Fake, salt),
+            Ok(Value::I8(v)) if v <= -100
+        ));
+        assert!(matches!(
+            mask_value(Value::U16(5), &MaskMethod::Fake, salt),
+            Ok(Value::U16(v)) if v < 10
+        ));
+
+        let date = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        match mask_value(Value::Date(date), &MaskMethod::ShiftDate(10), salt) {
+            Ok(Value::Date(shifted)) => assert!((shifted - date).num_days().abs() <= 10),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            mask_value(Value::None, &MaskMethod::Fake, salt),
+            Ok(Value::None)
+        ));
+
+        assert!(parse_mask_rule("/(?i)phone/=fake").is_ok());
+        assert!(parse_mask_rule("birth=shift-date:0").is_err());
+        assert!(parse_mask_rule("email").is_err());
+    }
+}
@@ -0,0 +1,5 @@
+//! changes applied to results between source and destination
+pub mod expression;
+pub mod mask;
+pub mod pipeline;
+pub mod schema;
@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+
+use serde_derive::Deserialize;
+
+use crate::definitions::{ColumnInfo, ColumnType, DataSourceBatchIterator, Row, Value};
+use crate::transform::expression::{parse_expression, Expression};
+use crate::transform::schema::coerce_value;
+
+/// one step of transformation, steps are applied in order
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// add column, or replace existing one with the same name
+    Compute(String, Expression),
+    Filter(Expression),
+    Select(Vec<String>),
+    Drop(Vec<String>),
+    /// old and new names, all of them applied at once
+    Rename(Vec<(String, String)>),
+}
+
+/// transformation file (toml), steps are applied in given order:
+///
+/// ```toml
+/// [[step]]
+/// compute = "full_name = first_name || ' ' || last_name"
+///
+/// [[step]]
+/// filter = "age >= 18"
+///
+/// [[step]]
+/// select = ["id", "full_name", "age"]
+///
+/// [[step]]
+/// rename = { full_name = "name" }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+struct TransformFile {
+    step: Vec<TransformFileStep>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TransformFileStep {
+    Compute(String),
+    Filter(String),
+    Select(Vec<String>),
+    Drop(Vec<String>),
+    Rename(BTreeMap<String, String>),
+}
+
+/// parse name = expression
+pub fn parse_compute(value: &str) -> Result<(String, Expression), String> {
+    let (name, expression) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected name = expression, got {}", value))?;
+    let name = name.trim().trim_matches('"');
+    if name.is_empty() {
+        return Err(format!("expected name = expression, got {}", value));
+    }
+    Ok((name.to_string(), parse_expression(expression)?))
+}
+
+/// parse old=new
+pub fn parse_rename(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+            Ok((old.to_string(), new.to_string()))
+        }
+        _ => Err(format!("expected old=new, got {}", value)),
+    }
+}
+
+/// steps from transformation file, followed by ones given in command line
+pub fn load_steps(transform_file: Option<&str>, command_line_steps: Vec<Step>) -> Vec<Step> {
+    let mut steps = vec![];
+    if let Some(filename) = transform_file {
+        let content = std::fs::read_to_string(filename).unwrap_or_else(|e| {
+            eprintln!("export: could not read transform file {}: {}", filename, e);
+            std::process::exit(1);
+        });
+        let transform_file = toml::from_str::<TransformFile>(&content).unwrap_or_else(|e| {
+            eprintln!("export: invalid transform file {}: {}", filename, e);
+            std::process::exit(1);
+        });
+        for step in transform_file.step {
+            let step = match step {
+                TransformFileStep::Compute(v) => {
+                    parse_compute(&v).map(|(n, e)| Step::Compute(n, e))
+                }
+                TransformFileStep::Filter(v) => parse_expression(&v).map(Step::Filter),
+                TransformFileStep::Select(v) => Ok(Step::Select(v)),
+                TransformFileStep::Drop(v) => Ok(Step::Drop(v)),
+                TransformFileStep::Rename(v) => Ok(Step::Rename(v.into_iter().collect())),
+            };
+            steps.push(step.unwrap_or_else(|e| {
+                eprintln!("export: invalid transform file {}: {}", filename, e);
+                std::process::exit(1);
+            }));
+        }
+    }
+    steps.extend(command_line_steps);
+    steps
+}
+
+/// step with column names resolved to indices
+enum Operation {
+    //target column (None to append), expression and its result type
+    Compute(Option<usize>, Expression, ColumnType),
+    Filter(Expression),
+    //indices of columns to keep, in output order
+    Project(Vec<usize>),
+}
+
+/// wraps source iterator, computing, filtering, selecting and renaming columns
+pub struct TransformIterator<'conn> {
+    inner: Box<dyn DataSourceBatchIterator<'conn> + 'conn>,
+    columns: Vec<ColumnInfo>,
+    operations: Vec<Operation>,
+    row_number: u64,
+}
+
+fn column_index(columns: &[ColumnInfo], name: &str) -> usize {
+    columns
+        .iter()
+        .position(|column| column.name == name)
+        .unwrap_or_else(|| {
+            eprintln!(
+                "export: can't transform column {}, there is no such column",
+                name
+            );
+            std::process::exit(1);
+        })
+}
+
+impl<'conn> TransformIterator<'conn> {
+    /// apply steps to given iterator, returning it unchanged if there are none
+    pub fn wrap(
+        inner: Box<dyn DataSourceBatchIterator<'conn> + 'conn>,
+        steps: &[Step],
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
+        if steps.is_empty() {
+            return inner;
+        }
+        let mut columns = inner.get_column_info();
+        let mut operations = vec![];
+        for step in steps {
+            match step {
+                Step::Compute(name, expression) => {
+                    let mut expression = expression.clone();
+                    let data_type = expression.bind(&columns).unwrap_or_else(|e| {
+                        eprintln!("export: invalid expression for column {}: {}", name, e);
+                        std::process::exit(1);
+                    });
+                    let target = columns.iter().position(|column| &column.name == name);
+                    let column = ColumnInfo {
+                        name: name.clone(),
+                        data_type: data_type.clone(),
+                    };
+                    match target {
+                        Some(idx) => columns[idx] = column,
+                        None => columns.push(column),
+                    }
+                    operations.push(Operation::Compute(target, expression, data_type));
+                }
+                Step::Filter(expression) => {
+                    let mut expression = expression.clone();
+                    expression.bind(&columns).unwrap_or_else(|e| {
+                        eprintln!("export: invalid filter: {}", e);
+                        std::process::exit(1);
+                    });
+                    operations.push(Operation::Filter(expression));
+                }
+                Step::Select(names) => {
+                    let indices: Vec<usize> = names
+                        .iter()
+                        .map(|name| column_index(&columns, name))
+                        .collect();
+                    columns = indices.iter().map(|idx| columns[*idx].clone()).collect();
+                    operations.push(Operation::Project(indices));
+                }
+                Step::Drop(names) => {
+                    let dropped: Vec<usize> = names
+                        .iter()
+                        .map(|name| column_index(&columns, name))
+                        .collect();
+                    let indices: Vec<usize> = (0..columns.len())
+                        .filter(|idx| !dropped.contains(idx))
+                        .collect();
+                    columns = indices.iter().map(|idx| columns[*idx].clone()).collect();
+                    operations.push(Operation::Project(indices));
+                }
+                Step::Rename(names) => {
+                    //all old names refer to columns before renaming, so a=b,b=a swaps them
+                    let indices: Vec<usize> = names
+                        .iter()
+                        .map(|(old, _)| column_index(&columns, old))
+                        .collect();
+                    for (idx, (_, new)) in indices.into_iter().zip(names) {
+                        columns[idx].name = new.clone();
+                    }
+                }
+            }
+        }
+        Box::new(TransformIterator {
+            inner,
+            columns,
+            operations,
+            row_number: 0,
+        })
+    }
+
+    /// transformed row, or None if it was filtered out
+    fn transform(&self, mut row: Row) -> Result<Option<Row>, String> {
+        for operation in self.operations.iter() {
+            match operation {
+                Operation::Compute(target, expression, data_type) => {
+                    let value = expression.evaluate(&row)?;
+                    //result has the type announced in column info
+                    let value = match data_type {
+                        ColumnType::None => value,
+                        _ => coerce_value(value, data_type).map_err(|value| {
+                            format!("can't convert {:?} to {:?}", value, data_type)
+                        })?,
+                    };
+                    match target {
+                        Some(idx) => row[*idx] = value,
+                        None => row.push(value),
+                    }
+                }
+                Operation::Filter(expression) => {
+                    if !matches!(expression.evaluate(&row)?, Value::Bool(true)) {
+                        return Ok(None);
+                    }
+                }
+                Operation::Project(indices) => {
+                    let mut projected = Vec::with_capacity(indices.len());
+                    for (n, idx) in indices.iter().enumerate() {
+                        //column selected more than once is copied, last one is moved
+                        if indices[n + 1..].contains(idx) {
+                            projected.push(row[*idx].clone());
+                        } else {
+                            projected.push(std::mem::replace(&mut row[*idx], Value::None));
+                        }
+                    }
+                    row = projected;
+                }
+            }
+        }
+        Ok(Some(row))
+    }
+}
+
+impl<'conn> DataSourceBatchIterator<'conn> for TransformIterator<'conn> {
+    fn get_column_info(&self) -> Vec<ColumnInfo> {
+        self.columns.clone()
+    }
+
+    /// number of source rows, filters may reduce it
+    fn get_count(&self) -> Option<u64> {
+        self.inner.get_count()
+    }
+
+    fn next(&mut self) -> Option<Vec<Row>> {
+        //skip batches where every row was filtered out, empty batch is not the end of results
+        loop {
+            let rows = self.inner.next()?;
+            let mut result = Vec::with_capacity(rows.len());
+            for row in rows {
+                self.row_number += 1;
+                match self.transform(row) {
+                    Ok(Some(row)) => result.push(row),
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!(
+                            "export: transformation failed in row {}: {}",
+                            self.row_number, e
+                        );
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if !result.is_empty() {
+                return Some(result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::RowsIterator;
+
+    fn people() -> Box<RowsIterator> {
+        let person = |id: i64, name: &str, age: i64| {
+            vec![
+                Value::I64(id),
+                Value::String(name.to_string()),
+                Value::I64(age),
+            ]
+        };
+        Box::new(RowsIterator::new(
+            &[
+                ("id", ColumnType::I64),
+                ("name", ColumnType::String),
+                ("age", ColumnType::I64),
+            ],
+            vec![
+                vec![person(1, "Ann", 20), person(2, "Bob", 15)],
+                vec![person(3, "Cid", 12)],
+                vec![person(4, "Dan", 40)],
+            ],
+        ))
+    }
+
+    fn compute(value: &str) -> Step {
+        let (name, expression) = parse_compute(value).unwrap();
+        Step::Compute(name, expression)
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// column names and all transformed batches
+    fn transform(steps: &[Step]) -> (Vec<String>, Vec<String>) {
+        let mut it = TransformIterator::wrap(people(), steps);
+        let columns = it
+            .get_column_info()
+            .iter()
+            .map(|column| format!("{} {:?}", column.name, column.data_type))
+            .collect();
+        let mut batches = vec![];
+        while let Some(rows) = it.next() {
+            batches.push(format!("{:?}", rows));
+        }
+        (columns, batches)
+    }
+
+    #[test]
+    fn test_filter() {
+        let (columns, batches) = transform(&[
+            Step::Filter(parse_expression("age >= 18").unwrap()),
+            Step::Select(names(&["name"])),
+        ]);
+        assert_eq!(columns, ["name String"]);
+        //batch with no rows left is skipped
+        assert_eq!(batches, ["[[String(\"Ann\")]]", "[[String(\"Dan\")]]"]);
+    }
+
+    #[test]
+    fn test_project() {
+        let (columns, batches) = transform(&[
+            Step::Select(names(&["age", "id", "age"])),
+            Step::Drop(names(&["id"])),
+        ]);
+        assert_eq!(columns, ["age I64", "age I64"]);
+        assert_eq!(batches[0], "[[I64(20), I64(20)], [I64(15), I64(15)]]");
+    }
+
+    #[test]
+    fn test_compute() {
+        let (columns, batches) = transform(&[
+            compute("age = age + 1"),
+            compute("adult = age >= 18"),
+            Step::Filter(parse_expression("id = 2").unwrap()),
+        ]);
+        //existing column is replaced in place, new one is appended
+        assert_eq!(columns, ["id I64", "name String", "age I64", "adult Bool"]);
+        assert_eq!(
+            batches,
+            ["[[I64(2), String(\"Bob\"), I64(16), Bool(false)]]"]
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let (columns, batches) = transform(&[
+            Step::Rename(vec![
+                ("id".to_string(), "name".to_string()),
+                ("name".to_string(), "id".to_string()),
+            ]),
+            Step::Rename(vec![("age".to_string(), "years".to_string())]),
+        ]);
+        assert_eq!(columns, ["name I64", "id String", "years I64"]);
+        assert_eq!(batches.len(), 3);
+    }
+}
@@ -0,0 +1,638 @@
+use serde_derive::Deserialize;
+
+use crate::definitions::{ColumnInfo, ColumnType, DataSourceBatchIterator, Row, Value};
+
+/// column type and name forced with --column or schema file
+#[derive(Clone, Debug)]
+pub struct ColumnOverride {
+    pub name: String,
+    pub data_type: ColumnType,
+    pub rename: Option<String>,
+}
+
+/// schema file (toml):
+///
+/// ```toml
+/// [[column]]
+/// name = "created"
+/// type = "datetime"
+/// rename = "created_at"
+///
+/// [[column]]
+/// name = "year"
+/// type = "i16"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+struct SchemaFile {
+    column: Vec<SchemaFileColumn>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct SchemaFileColumn {
+    name: String,
+    #[serde(rename = "type")]
+    data_type: String,
+    rename: Option<String>,
+}
+
+pub fn parse_column_type(name: &str) -> Result<ColumnType, String> {
+    Ok(match name.to_lowercase().as_str() {
+        "u64" => ColumnType::U64,
+        "i64" => ColumnType::I64,
+        "u32" => ColumnType::U32,
+        "i32" => ColumnType::I32,
+        "u16" => ColumnType::U16,
+        "i16" => ColumnType::I16,
+        "u8" => ColumnType::U8,
+        "i8" => ColumnType::I8,
+        "f64" => ColumnType::F64,
+        "f32" => ColumnType::F32,
+        "string" => ColumnType::String,
+        "bytes" => ColumnType::Bytes,
+        "bool" => ColumnType::Bool,
+        "date" => ColumnType::Date,
+        "datetime" => ColumnType::DateTime,
+        "time" => ColumnType::Time,
+        "timestamp" => ColumnType::Timestamp,
+        "decimal" => ColumnType::Decimal,
+        "json" => ColumnType::JSON,
+        _ => {
+            return Err(format!(
+                "unknown column type {}, expected one of: u64, i64, u32, i32, u16, i16, u8, i8, f64, f32, string, bytes, bool, date, datetime, time, timestamp, decimal, json",
+                name
+            ))
+        }
+    })
+}
+
+/// parse name:type or name:type:newname
+pub fn parse_column_override(value: &str) -> Result<ColumnOverride, String> {
+    let parts: Vec<&str> = value.split(':').collect();
+    match parts[..] {
+        [name, data_type] | [name, data_type, _] if !name.is_empty() => Ok(ColumnOverride {
+            name: name.to_string(),
+            data_type: parse_column_type(data_type)?,
+            rename: parts
+                .get(2)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+        }),
+        _ => Err(format!(
+            "expected name:type or name:type:newname, got {}",
+            value
+        )),
+    }
+}
+
+/// overrides from schema file followed by ones given in command line, so the latter win
+pub fn load_overrides(
+    columns: &[ColumnOverride],
+    schema_file: Option<&str>,
+) -> Vec<ColumnOverride> {
+    let mut overrides = vec![];
+    if let Some(filename) = schema_file {
+        let content = std::fs::read_to_string(filename).unwrap_or_else(|e| {
+            eprintln!("export: could not read schema file {}: {}", filename, e);
+            std::process::exit(1);
+        });
+        let schema_file = toml::from_str::<SchemaFile>(&content).unwrap_or_else(|e| {
+            eprintln!("export: invalid schema file {}: {}", filename, e);
+            std::process::exit(1);
+        });
+        for column in schema_file.column {
+            let data_type = parse_column_type(&column.data_type).unwrap_or_else(|e| {
+                eprintln!("export: invalid schema file {}: {}", filename, e);
+                std::process::exit(1);
+            });
+            overrides.push(ColumnOverride {
+                name: column.name,
+                data_type,
+                rename: column.rename,
+            });
+        }
+    }
+    overrides.extend(columns.iter().cloned());
+    overrides
+}
+
+/// text representation of scalar values
+pub fn value_as_text(value: &Value) -> Option<String> {
+    Some(match value {
+        Value::U64(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::U16(v) => v.to_string(),
+        Value::I16(v) => v.to_string(),
+        Value::U8(v) => v.to_string(),
+        Value::I8(v) => v.to_string(),
+        Value::F64(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::String(v) | Value::JSON(v) | Value::Custom(v) => v.clone(),
+        Value::Bytes(v) => String::from_utf8(v.clone()).ok()?,
+        Value::Bool(v) => v.to_string(),
+        Value::Timestamp(v) => v.to_string(),
+        Value::Date(v) => v.format("%Y-%m-%d").to_string(),
+        Value::Time(v) => v.format("%H:%M:%S%.f").to_string(),
+        Value::DateTime(v) => v.format("%Y-%m-%d %H:%M:%S%.f").to_string(),
+        Value::None => return None,
+    })
+}
+
+/// integer value of numbers, booleans, timestamps and numeric text, if it is exact
+pub fn value_as_i128(value: &Value) -> Option<i128> {
+    match value {
+        Value::U64(v) => Some(i128::from(*v)),
+        Value::I64(v) => Some(i128::from(*v)),
+        Value::U32(v) => Some(i128::from(*v)),
+        Value::I32(v) => Some(i128::from(*v)),
+        Value::U16(v) => Some(i128::from(*v)),
+        Value::I16(v) => Some(i128::from(*v)),
+        Value::U8(v) => Some(i128::from(*v)),
+        Value::I8(v) => Some(i128::from(*v)),
+        Value::Bool(v) => Some(i128::from(*v)),
+        Value::Timestamp(v) => Some(i128::from(*v)),
+        Value::F64(v) if v.fract() == 0.0 && v.abs() < 2f64.powi(100) => Some(*v as i128),
+        Value::F32(v) if v.fract() == 0.0 && v.abs() < 2f32.powi(100) => Some(*v as i128),
+        Value::F64(_) | Value::F32(_) => None,
+        _ => value_as_text(value)?.trim().parse().ok(),
+    }
+}
+
+/// float value of numbers and numeric text
+pub fn value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::F64(v) => Some(*v),
+        Value::F32(v) => Some(f64::from(*v)),
+        Value::String(_) | Value::Bytes(_) | Value::Custom(_) | Value::JSON(_) => {
+            value_as_text(value)?.trim().parse().ok()
+        }
+        _ => value_as_i128(value).map(|v| v as f64),
+    }
+}
+
+pub fn value_as_datetime(value: &Value) -> Option<chrono::NaiveDateTime> {
+    match value {
+        Value::DateTime(v) => Some(*v),
+        Value::Date(v) => v.and_hms_opt(0, 0, 0),
+        Value::Timestamp(v) => {
+            chrono::DateTime::from_timestamp(i64::try_from(*v).ok()?, 0).map(|v| v.naive_utc())
+        }
+        _ => {
+            let text = value_as_text(value)?;
+            let text = text.trim();
+            ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+                .iter()
+                .find_map(|format| chrono::NaiveDateTime::parse_from_str(text, format).ok())
+                .or_else(|| value_as_date(value)?.and_hms_opt(0, 0, 0))
+        }
+    }
+}
+
+fn value_as_date(value: &Value) -> Option<chrono::NaiveDate> {
+    match value {
+        Value::Date(v) => Some(*v),
+        Value::DateTime(_) | Value::Timestamp(_) => value_as_datetime(value).map(|v| v.date()),
+        _ => chrono::NaiveDate::parse_from_str(value_as_text(value)?.trim(), "%Y-%m-%d").ok(),
+    }
+}
+
+fn value_as_time(value: &Value) -> Option<chrono::NaiveTime> {
+    match value {
+        Value::Time(v) => Some(*v),
+        Value::DateTime(v) => Some(v.time()),
+        _ => chrono::NaiveTime::parse_from_str(value_as_text(value)?.trim(), "%H:%M:%S%.f").ok(),
+    }
+}
+
+fn value_as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(v) => Some(*v),
+        Value::F64(_) | Value::F32(_) => None,
+        _ => match value_as_text(value)?.trim().to_lowercase().as_str() {
+            "1" | "t" | "true" | "y" | "yes" => Some(true),
+            "0" | "f" | "false" | "n" | "no" => Some(false),
+            _ => None,
+        },
+    }
+}
+
+/// decimal number as text: optional sign, digits and optional fraction
+fn value_as_decimal(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::F64(v) if !v.is_finite() => return None,
+        Value::F32(v) if !v.is_finite() => return None,
+        _ => value_as_text(value)?.trim().to_string(),
+    };
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(&text);
+    let (integer_part, fraction_part) = digits.split_once('.').unwrap_or((digits, ""));
+    if (integer_part.is_empty() && fraction_part.is_empty())
+        || !integer_part.chars().all(|c| c.is_ascii_digit())
+        || !fraction_part.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(text)
+}
+
+/// value converted to given type, None if that's not possible
+fn convert_value(value: &Value, data_type: &ColumnType) -> Option<Value> {
+    fn int<T: TryFrom<i128>>(value: &Value) -> Option<T> {
+        T::try_from(value_as_i128(value)?).ok()
+    }
+    Some(match data_type {
+        ColumnType::U64 => Value::U64(int(value)?),
+        ColumnType::I64 => Value::I64(int(value)?),
+        ColumnType::U32 => Value::U32(int(value)?),
+        ColumnType::I32 => Value::I32(int(value)?),
+        ColumnType::U16 => Value::U16(int(value)?),
+        ColumnType::I16 => Value::I16(int(value)?),
+        ColumnType::U8 => Value::U8(int(value)?),
+        ColumnType::I8 => Value::I8(int(value)?),
+        ColumnType::F64 => Value::F64(value_as_f64(value)?),
+        ColumnType::F32 => Value::F32(value_as_f64(value)? as f32),
+        ColumnType::String => Value::String(value_as_text(value)?),
+        ColumnType::Bytes => match value {
+            Value::Bytes(v) => Value::Bytes(v.clone()),
+            _ => Value::Bytes(value_as_text(value)?.into_bytes()),
+        },
+        ColumnType::Bool => Value::Bool(value_as_bool(value)?),
+        ColumnType::Date => Value::Date(value_as_date(value)?),
+        ColumnType::DateTime => Value::DateTime(value_as_datetime(value)?),
+        ColumnType::Time => Value::Time(value_as_time(value)?),
+        //seconds since epoch, or date and time in UTC
+        ColumnType::Timestamp => Value::Timestamp(
+            int(value)
+                .or_else(|| u64::try_from(value_as_datetime(value)?.and_utc().timestamp()).ok())?,
+        ),
+        //sources deliver decimals as text
+        ColumnType::Decimal => Value::String(value_as_decimal(value)?),
+        ColumnType::JSON => Value::JSON(value_as_text(value)?),
+        ColumnType::None | ColumnType::Custom(_) => return None,
+    })
+}
+
+/// value converted to given type, or original value if that's not possible
+pub fn coerce_value(value: Value, data_type: &ColumnType) -> Result<Value, Value> {
+    //text is moved rather than copied
+    match (value, data_type) {
+        (Value::None, _) => Ok(Value::None),
+        (Value::String(v), ColumnType::String) => Ok(Value::String(v)),
+        (Value::String(v), ColumnType::JSON) | (Value::JSON(v), ColumnType::JSON) => {
+            Ok(Value::JSON(v))
+        }
+        (Value::Bytes(v), ColumnType::Bytes) => Ok(Value::Bytes(v)),
+        (value, _) => convert_value(&value, data_type).ok_or(value),
+    }
+}
+
+/// wraps source iterator, changing column types and names
+pub struct SchemaOverrideIterator<'conn> {
+    inner: Box<dyn DataSourceBatchIterator<'conn> + 'conn>,
+    columns: Vec<ColumnInfo>,
+    //column index and original name of converted columns
+    converted: Vec<(usize, String)>,
+    row_number: u64,
+}
+
+impl<'conn> SchemaOverrideIterator<'conn> {
+    /// apply overrides to given iterator, returning it unchanged if there are none
+    pub fn wrap(
+        inner: Box<dyn DataSourceBatchIterator<'conn> + 'conn>,
+        overrides: &[ColumnOverride],
+    ) -> Box<dyn DataSourceBatchIterator<'conn> + 'conn> {
+        if overrides.is_empty() {
+            return inner;
+        }
+        let original_columns = inner.get_column_info();
+        let mut columns = original_columns.clone();
+        let mut converted = vec![];
+        for column_override in overrides {
+            let idx = match original_columns
+                .iter()
+                .position(|column| column.name == column_override.name)
+            {
+                Some(idx) => idx,
+                None => {
+                    eprintln!(
+                        "export: can't override column {}, result has no such column",
+                        column_override.name
+                    );
+                    std::process::exit(1);
+                }
+            };
+            columns[idx].data_type = column_override.data_type.clone();
+            if let Some(rename) = &column_override.rename {
+                columns[idx].name = rename.clone();
+            }
+            if !converted.iter().any(|(i, _)| *i == idx) {
+                converted.push((idx, column_override.name.clone()));
+            }
+        }
+        Box::new(SchemaOverrideIterator {
+            inner,
+            columns,
+            converted,
+            row_number: 0,
+        })
+    }
+}
+
+impl<'conn> DataSourceBatchIterator<'conn> for SchemaOverrideIterator<'conn> {
+    fn get_column_info(&self) -> Vec<ColumnInfo> {
+        self.columns.clone()
+    }
+
+    fn get_count(&self) -> Option<u64> {
+        self.inner.get_count()
+    }
+
+    fn next(&mut self) -> Option<Vec<Row>> {
+        let mut rows = self.inner.next()?;
+        for row in rows.iter_mut() {
+            self.row_number += 1;
+            for (idx, name) in self.converted.iter() {
+                let data_type = &self.columns[*idx].data_type;
+                let value = std::mem::replace(&mut row[*idx], Value::None);
+                row[*idx] = coerce_value(value, data_type).unwrap_or_else(|value| {
+                    eprintln!(
+                        "export: can't convert value {:?} of column {} in row {} to {:?}",
+                        value, name, self.row_number, data_type
+                    );
+                    std::process::exit(1);
+                });
+            }
+        }
+        Some(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{exits_with, temp_path, RowsIterator};
+
+    fn convert(value: Value, data_type: ColumnType) -> String {
+        format!("{:?}", coerce_value(value, &data_type))
+    }
+
+    #[test]
+    fn test_parse_column_override() {
+        let column = parse_column_override("created:DateTime:created_at").unwrap();
+        assert_eq!(column.name, "created");
+        assert!(matches!(column.data_type, ColumnType::DateTime));
+        assert_eq!(column.rename.as_deref(), Some("created_at"));
+        let column = parse_column_override("year:i16:").unwrap();
+        assert!(matches!(column.data_type, ColumnType::I16));
+        assert_eq!(column.rename, None);
+        assert_eq!(
+            parse_column_override("year").unwrap_err(),
+            "expected name:type or name:type:newname, got year"
+        );
+        assert!(parse_column_override(":i16").is_err());
+        assert!(parse_column_override("a:i16:b:c").is_err());
+        assert!(parse_column_override("year:int")
+            .unwrap_err()
+            .starts_with("unknown column type int"));
+    }
+
+    #[test]
+    fn test_load_overrides() {
+        let filename = temp_path("schema.toml");
+        std::fs::write(
+            &filename,
+            r#"
+[[column]]
+name = "created"
+type = "datetime"
+rename = "created_at"
+
+[[column]]
+name = "year"
+type = "i16"
+"#,
+        )
+        .unwrap();
+        let columns = [parse_column_override("year:u8").unwrap()];
+        let overrides = load_overrides(&columns, Some(&filename));
+        std::fs::remove_file(&filename).unwrap();
+        //command line ones come last
+        let summary: Vec<String> = overrides
+            .iter()
+            .map(|o| format!("{} {:?} {:?}", o.name, o.data_type, o.rename))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "created DateTime Some(\"created_at\")",
+                "year I16 None",
+                "year U8 None"
+            ]
+        );
+        assert_eq!(load_overrides(&columns, None).len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_schema_file() {
+        let filename = temp_path("invalid_schema.toml");
+        std::fs::write(&filename, "[[column]]\nname = \"a\"\ntype = \"int\"\n").unwrap();
+        let stderr = exits_with(module_path!(), "test_invalid_schema_file", || {
+            load_overrides(&[], Some(&filename));
+        });
+        std::fs::remove_file(&filename).unwrap();
+        assert!(
+            stderr.contains("export: invalid schema file")
+                && stderr.contains("unknown column type int"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(convert(Value::I64(255), ColumnType::U8), "Ok(U8(255))");
+        assert_eq!(convert(Value::I64(256), ColumnType::U8), "Err(I64(256))");
+        assert_eq!(convert(Value::I32(-1), ColumnType::U64), "Err(I32(-1))");
+        assert_eq!(
+            convert(Value::U64(u64::MAX), ColumnType::I64),
+            "Err(U64(18446744073709551615))"
+        );
+        assert_eq!(
+            convert(Value::String(" -128 ".to_string()), ColumnType::I8),
+            "Ok(I8(-128))"
+        );
+        assert_eq!(convert(Value::F64(3.0), ColumnType::I32), "Ok(I32(3))");
+        assert_eq!(convert(Value::F64(3.5), ColumnType::I32), "Err(F64(3.5))");
+        assert_eq!(convert(Value::Bool(true), ColumnType::U16), "Ok(U16(1))");
+        assert_eq!(convert(Value::None, ColumnType::U8), "Ok(None)");
+    }
+
+    #[test]
+    fn test_bool() {
+        assert_eq!(
+            convert(Value::String("Yes".to_string()), ColumnType::Bool),
+            "Ok(Bool(true))"
+        );
+        assert_eq!(convert(Value::I64(0), ColumnType::Bool), "Ok(Bool(false))");
+        assert_eq!(convert(Value::I64(2), ColumnType::Bool), "Err(I64(2))");
+        assert_eq!(convert(Value::F64(1.0), ColumnType::Bool), "Err(F64(1.0))");
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(
+            convert(Value::String("2024-02-29".to_string()), ColumnType::Date),
+            "Ok(Date(2024-02-29))"
+        );
+        assert_eq!(
+            convert(Value::String("2023-02-29".to_string()), ColumnType::Date),
+            "Err(String(\"2023-02-29\"))"
+        );
+        assert_eq!(
+            convert(
+                Value::String("2024-01-02T03:04:05".to_string()),
+                ColumnType::DateTime
+            ),
+            "Ok(DateTime(2024-01-02T03:04:05))"
+        );
+        assert_eq!(
+            convert(
+                Value::String("2024-01-02".to_string()),
+                ColumnType::DateTime
+            ),
+            "Ok(DateTime(2024-01-02T00:00:00))"
+        );
+        assert_eq!(
+            convert(Value::String("12:30:00.5".to_string()), ColumnType::Time),
+            "Ok(Time(12:30:00.500))"
+        );
+        assert_eq!(
+            convert(Value::Timestamp(86400), ColumnType::Date),
+            "Ok(Date(1970-01-02))"
+        );
+        assert_eq!(
+            convert(
+                Value::String("1970-01-01 00:01:00".to_string()),
+                ColumnType::Timestamp
+            ),
+            "Ok(Timestamp(60))"
+        );
+        assert_eq!(
+            convert(Value::I64(60), ColumnType::Timestamp),
+            "Ok(Timestamp(60))"
+        );
+        //before epoch
+        assert_eq!(
+            convert(
+                Value::String("1969-12-31".to_string()),
+                ColumnType::Timestamp
+            ),
+            "Err(String(\"1969-12-31\"))"
+        );
+    }
+
+    #[test]
+    fn test_decimal() {
+        assert_eq!(
+            convert(Value::String(" -12.50 ".to_string()), ColumnType::Decimal),
+            "Ok(String(\"-12.50\"))"
+        );
+        assert_eq!(
+            convert(Value::I64(7), ColumnType::Decimal),
+            "Ok(String(\"7\"))"
+        );
+        assert_eq!(
+            convert(Value::F64(0.25), ColumnType::Decimal),
+            "Ok(String(\"0.25\"))"
+        );
+        assert_eq!(
+            convert(Value::F64(f64::NAN), ColumnType::Decimal),
+            "Err(F64(NaN))"
+        );
+        for text in ["1e5", ".", "1.2.3", "abc", ""] {
+            assert_eq!(
+                convert(Value::String(text.to_string()), ColumnType::Decimal),
+                format!("Err(String({:?}))", text)
+            );
+        }
+    }
+
+    #[test]
+    fn test_impossible_conversions() {
+        assert_eq!(
+            convert(Value::String("x".to_string()), ColumnType::F64),
+            "Err(String(\"x\"))"
+        );
+        assert_eq!(
+            convert(Value::Bytes(vec![0xff]), ColumnType::String),
+            "Err(Bytes([255]))"
+        );
+        assert_eq!(convert(Value::I64(1), ColumnType::None), "Err(I64(1))");
+        assert_eq!(
+            convert(Value::I64(1), ColumnType::Custom("point".to_string())),
+            "Err(I64(1))"
+        );
+        assert!(convert_value(&Value::String("1".to_string()), &ColumnType::None).is_none());
+        //text moves to json and back
+        assert_eq!(
+            convert(Value::String("{}".to_string()), ColumnType::JSON),
+            "Ok(JSON(\"{}\"))"
+        );
+        assert_eq!(
+            convert(Value::JSON("[]".to_string()), ColumnType::String),
+            "Ok(String(\"[]\"))"
+        );
+    }
+
+    #[test]
+    fn test_iterator() {
+        let inner = RowsIterator::new(
+            &[("id", ColumnType::String), ("flag", ColumnType::String)],
+            vec![vec![vec![
+                Value::String("1".to_string()),
+                Value::String("y".to_string()),
+            ]]],
+        );
+        let overrides = [
+            parse_column_override("id:u32:number").unwrap(),
+            parse_column_override("flag:bool").unwrap(),
+        ];
+        let mut it = SchemaOverrideIterator::wrap(Box::new(inner), &overrides);
+        let columns: Vec<String> = it
+            .get_column_info()
+            .iter()
+            .map(|c| format!("{} {:?}", c.name, c.data_type))
+            .collect();
+        assert_eq!(columns, ["number U32", "flag Bool"]);
+        assert_eq!(
+            format!("{:?}", it.next().unwrap()),
+            "[[U32(1), Bool(true)]]"
+        );
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn test_conversion_failure() {
+        let stderr = exits_with(module_path!(), "test_conversion_failure", || {
+            let inner = RowsIterator::new(
+                &[("id", ColumnType::String)],
+                vec![
+                    vec![vec![Value::String("1".to_string())]],
+                    vec![
+                        vec![Value::String("2".to_string())],
+                        vec![Value::String("x".to_string())],
+                    ],
+                ],
+            );
+            let overrides = [parse_column_override("id:i64:number").unwrap()];
+            let mut it = SchemaOverrideIterator::wrap(Box::new(inner), &overrides);
+            while it.next().is_some() {}
+        });
+        assert!(
+            stderr
+                .contains("export: can't convert value String(\"x\") of column id in row 3 to I64"),
+            "{}",
+            stderr
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use base64::Engine;
+
+use crate::config::CONFIG;
+use crate::definitions::{ColumnType, Value};
+use crate::utils::{escape_binary_data, truncate_text_with_note};
+
+/// how binary values are written as text
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum BinaryFormat {
+    /// printable ascii kept, other bytes as \xNN
+    Escaped,
+    Hex,
+    Base64,
+}
+
+/// renders values as text for text based destinations. Defaults can be changed
+/// in [format] section of ~/.dbfish/config.toml, and per export with command line options:
+///
+/// ```toml
+/// [format]
+/// date_format = "%d.%m.%Y"
+/// time_format = "%H:%M"
+/// datetime_format = "%d.%m.%Y %H:%M"
+/// float_precision = 2
+/// thousands_separator = " "
+/// decimal_comma = true
+/// bool_format = "yes,no"
+/// null_marker = "NULL"
+/// binary_format = "hex"
+/// ```
+#[derive(Clone, Debug)]
+pub struct ValueFormatter {
+    pub date_format: String,
+    pub time_format: String,
+    pub datetime_format: String,
+    pub float_precision: Option<usize>,
+    pub thousands_separator: Option<String>,
+    pub decimal_comma: bool,
+    pub true_value: String,
+    pub false_value: String,
+    /// None leaves NULL rendering to destination
+    pub null_marker: Option<String>,
+    pub binary_format: BinaryFormat,
+}
+
+impl Default for ValueFormatter {
+    fn default() -> ValueFormatter {
+        ValueFormatter {
+            date_format: "%Y-%m-%d".to_string(),
+            time_format: "%H:%M:%S".to_string(),
+            datetime_format: "%Y-%m-%d %H:%M:%S".to_string(),
+            float_precision: None,
+            thousands_separator: None,
+            decimal_comma: false,
+            true_value: "true".to_string(),
+            false_value: "false".to_string(),
+            null_marker: None,
+            binary_format: BinaryFormat::Escaped,
+        }
+    }
+}
+
+/// error for invalid strftime format
+pub fn check_date_format(format: &str) -> Result<(), String> {
+    use chrono::format::{Item, StrftimeItems};
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid date format '{}'", format));
+    }
+    Ok(())
+}
+
+/// parse true,false texts, ie. yes,no
+pub fn parse_bool_format(value: &str) -> Result<(String, String), String> {
+    match value.split_once(',') {
+        Some((t, f)) if t != f => Ok((t.to_string(), f.to_string())),
+        _ => Err(format!(
+            "expected two different texts separated by comma (ie. yes,no), got {}",
+            value
+        )),
+    }
+}
+
+fn config_error(key: &str, message: &str) -> ! {
+    eprintln!("config: format.{} {}", key, message);
+    std::process::exit(1);
+}
+
+impl ValueFormatter {
+    /// default formatter updated with [format] section of config file
+    pub fn from_config() -> ValueFormatter {
+        let mut formatter = ValueFormatter::default();
+        let Some(config) = CONFIG.get("format") else {
+            return formatter;
+        };
+        let Some(table) = config.as_table() else {
+            eprintln!("config: format must be a table");
+            std::process::exit(1);
+        };
+        for (key, value) in table.iter() {
+            match key.as_str() {
+                "float_precision" => match value.as_integer() {
+                    Some(precision) if precision >= 0 => {
+                        formatter.float_precision = Some(precision as usize)
+                    }
+                    _ => config_error(key, "must be a non-negative integer"),
+                },
+                "decimal_comma" => match value.as_bool() {
+                    Some(decimal_comma) => formatter.decimal_comma = decimal_comma,
+                    None => config_error(key, "must be true or false"),
+                },
+                _ => {
+                    let Some(text) = value.as_str() else {
+                        config_error(key, "must be a string");
+                    };
+                    let text = text.to_string();
+                    match key.as_str() {
+                        "date_format" => formatter.date_format = text,
+                        "time_format" => formatter.time_format = text,
+                        "datetime_format" => formatter.datetime_format = text,
+                        "thousands_separator" => formatter.thousands_separator = Some(text),
+                        "null_marker" => formatter.null_marker = Some(text),
+                        "bool_format" => {
+                            (formatter.true_value, formatter.false_value) =
+                                parse_bool_format(&text).unwrap_or_else(|e| config_error(key, &e));
+                        }
+                        "binary_format" => {
+                            formatter.binary_format = clap::ValueEnum::from_str(&text, true)
+                                .unwrap_or_else(|_| {
+                                    config_error(key, "must be one of: escaped, hex, base64")
+                                })
+                        }
+                        _ => {
+                            eprintln!("config: unknown format entry: {}", key);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+        }
+        formatter
+    }
+
+    /// exit with a message if options contradict each other or formats are invalid
+    pub fn validate(&self) {
+        for format in [&self.date_format, &self.time_format, &self.datetime_format] {
+            if let Err(e) = check_date_format(format) {
+                eprintln!("export: {}", e);
+                std::process::exit(1);
+            }
+        }
+        let decimal_point = if self.decimal_comma { "," } else { "." };
+        if self.thousands_separator.as_deref() == Some(decimal_point) {
+            eprintln!(
+                "export: thousands separator can't be the same as decimal point ({})",
+                decimal_point
+            );
+            std::process::exit(1);
+        }
+    }
+
+    pub fn date(&self, date: &chrono::NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    pub fn time(&self, time: &chrono::NaiveTime) -> String {
+        time.format(&self.time_format).to_string()
+    }
+
+    pub fn datetime(&self, datetime: &chrono::NaiveDateTime) -> String {
+        datetime.format(&self.datetime_format).to_string()
+    }
+
+    pub fn bytes(&self, value: &[u8]) -> String {
+        match self.binary_format {
+            BinaryFormat::Escaped => escape_binary_data(value),
+            BinaryFormat::Hex => value.iter().map(|b| format!("{:02x}", b)).collect(),
+            BinaryFormat::Base64 => base64::engine::general_purpose::STANDARD.encode(value),
+        }
+    }
+
+    pub fn bool(&self, value: bool) -> &str {
+        if value {
+            &self.true_value
+        } else {
+            &self.false_value
+        }
+    }
+
+    /// NULL marker, or destination's own one if not configured
+    pub fn null<'a>(&'a self, default: &'a str) -> &'a str {
+        self.null_marker.as_deref().unwrap_or(default)
+    }
+
+    /// float rounded to precision, for destinations keeping numbers as numbers
+    pub fn round<T: std::fmt::Display + std::str::FromStr + Copy>(&self, value: T) -> T {
+        match self.float_precision {
+            Some(precision) => format!("{:.*}", precision, value).parse().unwrap_or(value),
+            None => value,
+        }
+    }
+
+    //thousands separator and decimal comma applied to number text
+    fn number(&self, text: String) -> String {
+        if self.thousands_separator.is_none() && !self.decimal_comma {
+            return text;
+        }
+        let (sign, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => ("-", unsigned),
+            None => ("", text.as_str()),
+        };
+        let split = unsigned
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(unsigned.len());
+        let (integer, fraction) = unsigned.split_at(split);
+        //inf, NaN
+        if integer.is_empty() {
+            return text;
+        }
+        let mut result = String::from(sign);
+        for (idx, digit) in integer.chars().enumerate() {
+            if let Some(separator) = &self.thousands_separator {
+                if idx > 0 && (integer.len() - idx) % 3 == 0 {
+                    result.push_str(separator);
+                }
+            }
+            result.push(digit);
+        }
+        match fraction.strip_prefix('.') {
+            Some(fraction) if self.decimal_comma => {
+                result.push(',');
+                result.push_str(fraction);
+            }
+            _ => result.push_str(fraction),
+        }
+        result
+    }
+
+    fn float(&self, value: f64, shortest: String) -> String {
+        if !value.is_finite() {
+            return shortest;
+        }
+        self.number(match self.float_precision {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => shortest,
+        })
+    }
+
+    /// text of a value in column of given type, None for NULL
+    pub fn format(
+        &self,
+        value: &Value,
+        data_type: &ColumnType,
+        truncate: Option<u64>,
+    ) -> Option<String> {
+        Some(match value {
+            //sources deliver decimals as text
+            Value::String(value) if matches!(data_type, ColumnType::Decimal) => {
+                self.number(value.to_string())
+            }
+            Value::U64(value) => self.number(value.to_string()),
+            Value::I64(value) => self.number(value.to_string()),
+            Value::U32(value) => self.number(value.to_string()),
+            Value::I32(value) => self.number(value.to_string()),
+            Value::U16(value) => self.number(value.to_string()),
+            Value::I16(value) => self.number(value.to_string()),
+            Value::U8(value) => self.number(value.to_string()),
+            Value::I8(value) => self.number(value.to_string()),
+            Value::F64(value) => self.float(*value, value.to_string()),
+            Value::F32(value) => self.float(f64::from(*value), value.to_string()),
+            Value::String(value) => truncate_text_with_note(value.to_string(), truncate),
+            Value::Bool(value) => self.bool(*value).to_string(),
+            Value::Bytes(value) => self.bytes(value),
+            Value::None => return None,
+            Value::Timestamp(value) => value.to_string(),
+            Value::Date(date) => self.date(date),
+            Value::Time(time) => self.time(time),
+            Value::DateTime(datetime) => self.datetime(datetime),
+            Value::JSON(value) => value.to_string(),
+            Value::Custom(value) => truncate_text_with_note(value.to_string(), truncate),
+        })
+    }
+
+    /// text of a value, NULL rendered as configured marker or given default
+    pub fn format_or_null(
+        &self,
+        value: &Value,
+        data_type: &ColumnType,
+        truncate: Option<u64>,
+        null: &str,
+    ) -> String {
+        self.format(value, data_type, truncate)
+            .unwrap_or_else(|| self.null(null).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_formatter() {
+        let default = ValueFormatter::default();
+        assert_eq!(
+            default.format_or_null(&Value::F64(1234.5), &ColumnType::F64, None, ""),
+            "1234.5"
+        );
+        assert_eq!(
+            default.format_or_null(&Value::None, &ColumnType::I64, None, "\\N"),
+            "\\N"
+        );
+
+        let formatter = ValueFormatter {
+            date_format: "%d.%m.%Y".to_string(),
+            float_precision: Some(2),
+            thousands_separator: Some(" ".to_string()),
+            decimal_comma: true,
+            true_value: "yes".to_string(),
+            null_marker: Some("NULL".to_string()),
+            binary_format: BinaryFormat::Hex,
+            ..ValueFormatter::default()
+        };
+        let format = |value: &Value| formatter.format_or_null(value, &ColumnType::None, None, "");
+        assert_eq!(format(&Value::F64(-1234567.891)), "-1 234 567,89");
+        assert_eq!(format(&Value::F32(0.5)), "0,50");
+        assert_eq!(format(&Value::I32(-123)), "-123");
+        assert_eq!(format(&Value::U64(1000)), "1 000");
+        assert_eq!(format(&Value::F64(f64::NAN)), "NaN");
+        assert_eq!(format(&Value::Bool(true)), "yes");
+        assert_eq!(format(&Value::None), "NULL");
+        assert_eq!(format(&Value::Bytes(vec![0, 255])), "00ff");
+        assert_eq!(
+            format(&Value::Date(
+                chrono::NaiveDate::from_ymd_opt(2024, 3, 5).unwrap()
+            )),
+            "05.03.2024"
+        );
+        let decimal = |text: &str| {
+            formatter.format_or_null(
+                &Value::String(text.to_string()),
+                &ColumnType::Decimal,
+                None,
+                "",
+            )
+        };
+        assert_eq!(decimal("-1234567.500"), "-1 234 567,500");
+        assert_eq!(decimal("12"), "12");
+        //text columns are left alone
+        assert_eq!(format(&Value::String("1234.5".to_string())), "1234.5");
+        assert_eq!(formatter.round(2.345678), 2.35);
+        assert!(parse_bool_format("yes,yes").is_err());
+    }
+}
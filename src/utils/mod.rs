@@ -1,6 +1,10 @@
 use unicode_segmentation::UnicodeSegmentation;
 
 pub mod fileorstdout;
+pub mod formatter;
+#[cfg(test)]
+pub mod testing;
+pub mod theme;
 
 pub fn truncate_text(text: &str, max_length: u64) -> String {
     UnicodeSegmentation::graphemes(text, true)
@@ -9,24 +13,104 @@ pub fn truncate_text(text: &str, max_length: u64) -> String {
         .join("")
 }
 
-pub fn truncate_text_with_note(text: String, truncate: Option<u64>) -> String {
+/// truncated text and note describing how much was trimmed (if anything was)
+pub fn truncate_text_split(text: String, truncate: Option<u64>) -> (String, Option<String>) {
     match truncate {
-        None => text,
+        None => (text, None),
         Some(max_length) => {
             let truncated_text = truncate_text(&text, max_length);
             if truncated_text.len() < text.len() {
-                format!(
-                    "{} ...({} bytes trimmed)",
-                    truncated_text,
-                    text.len() - truncated_text.len()
-                )
+                let note = format!(" ...({} bytes trimmed)", text.len() - truncated_text.len());
+                (truncated_text, Some(note))
             } else {
-                text
+                (text, None)
             }
         }
     }
 }
 
+pub fn truncate_text_with_note(text: String, truncate: Option<u64>) -> String {
+    match truncate_text_split(text, truncate) {
+        (text, None) => text,
+        (text, Some(note)) => text + &note,
+    }
+}
+
+/// parse decimal text into unscaled integer with given scale
+#[cfg(any(feature = "use_avro", feature = "use_arrow"))]
+pub fn parse_decimal(text: &str, scale: u32) -> Option<i128> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (integer_part, fraction_part) = match digits.split_once('.') {
+        Some((i, f)) => (i, f),
+        None => (digits, ""),
+    };
+    if (integer_part.is_empty() && fraction_part.is_empty())
+        || !integer_part.chars().all(|c| c.is_ascii_digit())
+        || !fraction_part.chars().all(|c| c.is_ascii_digit())
+        || fraction_part.len() > scale as usize
+    {
+        return None;
+    }
+    let mut unscaled: i128 = 0;
+    for c in integer_part
+        .chars()
+        .chain(fraction_part.chars())
+        .chain(std::iter::repeat_n(
+            '0',
+            scale as usize - fraction_part.len(),
+        ))
+    {
+        unscaled = unscaled
+            .checked_mul(10)?
+            .checked_add(c.to_digit(10).unwrap() as i128)?;
+    }
+    Some(if negative { -unscaled } else { unscaled })
+}
+
+/// names with repeated ones made unique by adding _2, _3... to them.
+/// Renamed ones don't clash with any other name either
+#[cfg(any(feature = "use_avro", feature = "use_xml"))]
+pub fn unique_names(mut names: Vec<String>) -> Vec<String> {
+    for idx in 1..names.len() {
+        if !names[..idx].contains(&names[idx]) {
+            continue;
+        }
+        let mut suffix = 2;
+        let mut candidate = format!("{}_{}", names[idx], suffix);
+        while names.contains(&candidate) {
+            suffix += 1;
+            candidate = format!("{}_{}", names[idx], suffix);
+        }
+        names[idx] = candidate;
+    }
+    names
+}
+
+/// escape text for use in xml content or attribute value.
+/// Characters not allowed in xml 1.0 are replaced with U+FFFD
+#[cfg(any(feature = "use_ods", feature = "use_xml"))]
+pub fn xml_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            '\t' | '\n' => result.push(c),
+            '\r' => result.push_str("&#13;"),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => result.push('\u{fffd}'),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
 pub fn report_query_error(query: &str, error: &str) {
     eprintln!(
         "The following query have failed:\n\n{}\n\nwith error:\n\n{}",
@@ -50,9 +134,45 @@ pub fn escape_binary_data(value: &[u8]) -> String {
 mod tests {
 
     use super::escape_binary_data;
+    #[cfg(any(feature = "use_avro", feature = "use_arrow"))]
+    use super::parse_decimal;
+    #[cfg(any(feature = "use_avro", feature = "use_xml"))]
+    use super::unique_names;
+    #[cfg(any(feature = "use_ods", feature = "use_xml"))]
+    use super::xml_escape;
 
     #[test]
     fn test_escape_binary_data() {
         assert_eq!(escape_binary_data(&[b'a', 0x0, b'b', 0x9]), "a\\x00b\\t");
     }
+
+    #[cfg(any(feature = "use_avro", feature = "use_arrow"))]
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("12.5", 2), Some(1250));
+        assert_eq!(parse_decimal("-0.01", 2), Some(-1));
+        assert_eq!(parse_decimal("1.234", 2), None);
+        assert_eq!(parse_decimal("abc", 2), None);
+    }
+
+    #[cfg(any(feature = "use_avro", feature = "use_xml"))]
+    #[test]
+    fn test_unique_names() {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        assert_eq!(
+            unique_names(names(&["a", "b", "a", "a", "a_2"])),
+            ["a", "b", "a_3", "a_4", "a_2"]
+        );
+        assert_eq!(unique_names(names(&["a", "a"])), ["a", "a_2"]);
+    }
+
+    #[cfg(any(feature = "use_ods", feature = "use_xml"))]
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(
+            xml_escape("<a href='x'>\"&\"</a>"),
+            "&lt;a href=&apos;x&apos;&gt;&quot;&amp;&quot;&lt;/a&gt;"
+        );
+        assert_eq!(xml_escape("a\tb\r\nc\u{1}"), "a\tb&#13;\nc\u{fffd}");
+    }
 }
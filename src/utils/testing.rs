@@ -0,0 +1,134 @@
+//! helpers for destination tests: in-memory results, temporary files and exit paths
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::definitions::{ColumnInfo, ColumnType, DataDestination, DataSourceBatchIterator, Row};
+
+/// results given upfront, returned in batches
+pub struct RowsIterator {
+    columns: Vec<ColumnInfo>,
+    batches: std::vec::IntoIter<Vec<Row>>,
+}
+
+impl RowsIterator {
+    pub fn new(columns: &[(&str, ColumnType)], batches: Vec<Vec<Row>>) -> RowsIterator {
+        RowsIterator {
+            columns: columns
+                .iter()
+                .map(|(name, data_type)| ColumnInfo {
+                    name: name.to_string(),
+                    data_type: data_type.clone(),
+                })
+                .collect(),
+            batches: batches.into_iter(),
+        }
+    }
+}
+
+impl<'conn> DataSourceBatchIterator<'conn> for RowsIterator {
+    fn get_column_info(&self) -> Vec<ColumnInfo> {
+        self.columns.clone()
+    }
+
+    fn get_count(&self) -> Option<u64> {
+        None
+    }
+
+    fn next(&mut self) -> Option<Vec<Row>> {
+        self.batches.next()
+    }
+}
+
+/// pass all batches through destination, like export does
+pub fn export(
+    destination: &mut dyn DataDestination,
+    columns: &[(&str, ColumnType)],
+    batches: Vec<Vec<Row>>,
+) {
+    let mut iterator = RowsIterator::new(columns, batches);
+    destination.prepare();
+    destination.prepare_for_results(&iterator);
+    while let Some(rows) = iterator.next() {
+        destination.add_rows(&rows);
+    }
+    destination.close();
+}
+
+/// path of a not yet existing file in temporary directory, unique for every call
+pub fn temp_path(name: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "dbfish-test-{}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        name
+    ));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().to_string()
+}
+
+//printed by child process before running test code, so that its stdout can be told apart
+//from libtest output
+const CHILD_OUTPUT_MARKER: &str = "\n--- dbfish child output ---\n";
+
+/// run given test again in a child process, where body is executed instead
+fn run_in_child(module: &str, test_name: &str, body: impl FnOnce()) -> std::process::Output {
+    //libtest names tests without crate name
+    let module = module.split_once("::").map_or("", |(_, module)| module);
+    let test_path = format!("{}::{}", module, test_name);
+    if std::env::var("DBFISH_CHILD_TEST").as_deref() == Ok(test_path.as_str()) {
+        print!("{}", CHILD_OUTPUT_MARKER);
+        std::io::Write::flush(&mut std::io::stdout()).unwrap();
+        body();
+        std::io::Write::flush(&mut std::io::stdout()).unwrap();
+        std::process::exit(0);
+    }
+    std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            test_path.as_str(),
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env("DBFISH_CHILD_TEST", &test_path)
+        .output()
+        .unwrap()
+}
+
+/// run code which is expected to exit the process (ie. on invalid data) in a child
+/// process running only given test, returns its stderr. Call it from the test itself:
+/// `exits_with(module_path!(), "test_name", || ...)`
+pub fn exits_with(module: &str, test_name: &str, body: impl FnOnce()) -> String {
+    let output = run_in_child(module, test_name, body);
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    //reaching end of body is a failure too
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    stderr
+}
+
+/// run code writing to stdout in a child process running only given test, returns
+/// what it has written. Called like exits_with
+pub fn child_stdout(module: &str, test_name: &str, body: impl FnOnce()) -> Vec<u8> {
+    let output = run_in_child(module, test_name, body);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let marker = CHILD_OUTPUT_MARKER.as_bytes();
+    let start = output
+        .stdout
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .expect("child output marker")
+        + marker.len();
+    output.stdout[start..].to_vec()
+}
+
+/// content of given file inside zip archive (xlsx and ods files)
+pub fn zip_entry(filename: &str, entry: &str) -> String {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(filename).unwrap()).unwrap();
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name(entry).unwrap(), &mut content).unwrap();
+    content
+}
@@ -0,0 +1,217 @@
+use std::io::Write;
+
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::config::CONFIG;
+use crate::definitions::Value;
+
+/// kind of value, used to pick its style
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueKind {
+    Null,
+    Number,
+    Bool,
+    Date,
+    Text,
+}
+
+impl ValueKind {
+    pub fn of(value: &Value) -> ValueKind {
+        match value {
+            Value::None => ValueKind::Null,
+            Value::U64(_)
+            | Value::I64(_)
+            | Value::U32(_)
+            | Value::I32(_)
+            | Value::U16(_)
+            | Value::I16(_)
+            | Value::U8(_)
+            | Value::I8(_)
+            | Value::F64(_)
+            | Value::F32(_) => ValueKind::Number,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::Date(_) | Value::Time(_) | Value::DateTime(_) | Value::Timestamp(_) => {
+                ValueKind::Date
+            }
+            _ => ValueKind::Text,
+        }
+    }
+}
+
+/// colours used by text destinations. Can be changed in [theme] section of ~/.dbfish/config.toml:
+///
+/// ```toml
+/// [theme]
+/// header = "bold"
+/// null = "dimmed"
+/// null_marker = "NULL"
+/// number = "cyan"
+/// bool = "magenta"
+/// date = "green"
+/// text = ""
+/// truncated = "yellow italic"
+/// ```
+///
+/// Style is a space separated list of colour names (black, blue, green, red, cyan, magenta,
+/// yellow, white), bg:<colour> and modifiers (bold, dimmed, italic, underline, intense)
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub header: ColorSpec,
+    pub null: ColorSpec,
+    pub null_marker: String,
+    pub number: ColorSpec,
+    pub bool: ColorSpec,
+    pub date: ColorSpec,
+    pub text: ColorSpec,
+    pub truncated: ColorSpec,
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    Some(match name {
+        "black" => Color::Black,
+        "blue" => Color::Blue,
+        "green" => Color::Green,
+        "red" => Color::Red,
+        "cyan" => Color::Cyan,
+        "magenta" => Color::Magenta,
+        "yellow" => Color::Yellow,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+pub fn parse_style(style: &str) -> Result<ColorSpec, String> {
+    let mut spec = ColorSpec::new();
+    for token in style.split_whitespace() {
+        match token {
+            "bold" => spec.set_bold(true),
+            "dimmed" => spec.set_dimmed(true),
+            "italic" => spec.set_italic(true),
+            "underline" => spec.set_underline(true),
+            "intense" => spec.set_intense(true),
+            _ => match token.strip_prefix("bg:") {
+                Some(color) => spec.set_bg(Some(
+                    parse_color(color).ok_or(format!("unknown colour: {}", color))?,
+                )),
+                None => spec.set_fg(Some(
+                    parse_color(token).ok_or(format!("unknown colour or modifier: {}", token))?,
+                )),
+            },
+        };
+    }
+    Ok(spec)
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            header: parse_style("bold").unwrap(),
+            null: parse_style("dimmed").unwrap(),
+            null_marker: "NULL".to_string(),
+            number: parse_style("cyan").unwrap(),
+            bool: parse_style("magenta").unwrap(),
+            date: parse_style("green").unwrap(),
+            text: ColorSpec::new(),
+            truncated: parse_style("yellow italic").unwrap(),
+        }
+    }
+}
+
+impl Theme {
+    /// default theme updated with [theme] section of config file
+    pub fn from_config() -> Theme {
+        let mut theme = Theme::default();
+        let Some(config) = CONFIG.get("theme") else {
+            return theme;
+        };
+        let Some(table) = config.as_table() else {
+            eprintln!("config: theme must be a table");
+            std::process::exit(1);
+        };
+        for (key, value) in table.iter() {
+            let Some(value) = value.as_str() else {
+                eprintln!("config: theme.{} must be a string", key);
+                std::process::exit(1);
+            };
+            if key == "null_marker" {
+                theme.null_marker = value.to_string();
+                continue;
+            }
+            let spec = parse_style(value).unwrap_or_else(|e| {
+                eprintln!("config: theme.{}: {}", key, e);
+                std::process::exit(1);
+            });
+            match key.as_str() {
+                "header" => theme.header = spec,
+                "null" => theme.null = spec,
+                "number" => theme.number = spec,
+                "bool" => theme.bool = spec,
+                "date" => theme.date = spec,
+                "text" => theme.text = spec,
+                "truncated" => theme.truncated = spec,
+                _ => {
+                    eprintln!("config: unknown theme entry: {}", key);
+                    std::process::exit(1);
+                }
+            }
+        }
+        theme
+    }
+
+    pub fn style_for(&self, kind: ValueKind) -> &ColorSpec {
+        match kind {
+            ValueKind::Null => &self.null,
+            ValueKind::Number => &self.number,
+            ValueKind::Bool => &self.bool,
+            ValueKind::Date => &self.date,
+            ValueKind::Text => &self.text,
+        }
+    }
+
+    /// text wrapped in ansi escape codes
+    pub fn paint(text: &str, spec: &ColorSpec) -> String {
+        if text.is_empty() || spec.is_none() {
+            return text.to_string();
+        }
+        let mut buffer = termcolor::Ansi::new(vec![]);
+        buffer.set_color(spec).unwrap();
+        buffer.write_all(text.as_bytes()).unwrap();
+        buffer.reset().unwrap();
+        String::from_utf8(buffer.into_inner()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_styles() {
+        let spec = parse_style("red bg:white bold").unwrap();
+        assert_eq!(spec.fg(), Some(&Color::Red));
+        assert_eq!(spec.bg(), Some(&Color::White));
+        assert!(spec.bold());
+        assert_eq!(
+            parse_style("bold pink").unwrap_err(),
+            "unknown colour or modifier: pink"
+        );
+        assert_eq!(parse_style("bg:bold").unwrap_err(), "unknown colour: bold");
+
+        assert_eq!(
+            Theme::paint("1", &spec),
+            "\x1b[0m\x1b[1m\x1b[31m\x1b[47m1\x1b[0m"
+        );
+        assert_eq!(Theme::paint("1", &ColorSpec::new()), "1");
+        let theme = Theme::default();
+        assert_eq!(
+            theme.style_for(ValueKind::of(&Value::F32(1.0))),
+            &theme.number
+        );
+        assert_eq!(ValueKind::of(&Value::None), ValueKind::Null);
+        assert_eq!(ValueKind::of(&Value::Timestamp(0)), ValueKind::Date);
+        assert_eq!(
+            ValueKind::of(&Value::JSON("1".to_string())),
+            ValueKind::Text
+        );
+    }
+}